@@ -0,0 +1,40 @@
+//! Rough timings for common expressions. Run with `cargo bench`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use reroll::compile::Program;
+use reroll::eval::eval_expr;
+use reroll::parser::parse;
+
+const ITERATIONS: u32 = 200_000;
+
+const EXPRESSIONS: &[&str] = &["1d20", "4d6kh3", "2d20kl1", "8d6!", "6(4d6dl1)", "20d6kh10"];
+
+fn main() {
+    fastrand::seed(1);
+
+    for input in EXPRESSIONS {
+        let ast = parse(input).unwrap();
+        let root = ast.roots()[0];
+        let program = Program::compile(&ast, root);
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(eval_expr(black_box(&ast), root));
+        }
+        let tree = start.elapsed() / ITERATIONS;
+
+        let mut stack = Vec::new();
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(program.run(black_box(&mut stack)));
+        }
+        let compiled = start.elapsed() / ITERATIONS;
+
+        println!(
+            "{:<12} eval {:>8.0?}   program {:>8.0?}",
+            input, tree, compiled
+        );
+    }
+}
@@ -0,0 +1,880 @@
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use smallvec::SmallVec;
+
+/// A byte range into the source expression, `start..end`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Self {
+        Span::new(span.start(), span.end())
+    }
+}
+
+/// Refers to an expression node stored in an [`Ast`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(u32);
+
+/// An expression node together with the source range it was parsed from.
+#[derive(Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum ExprKind {
+    Number(i32),
+    Dice {
+        count: ExprId,
+        sides: ExprId,
+        modifiers: Vec<DiceModifier>,
+    },
+    /// Fudge/Fate dice (`4dF`), each showing -1, 0 or +1.
+    Fudge {
+        count: ExprId,
+        modifiers: Vec<DiceModifier>,
+    },
+    BinaryOp(ExprId, char, ExprId),
+    /// A built-in function applied to the totals of its arguments.
+    Call {
+        function: Function,
+        args: Vec<ExprId>,
+    },
+    /// A single value read off a pool instead of its sum (`6d6straight`,
+    /// `6d6h` for the highest die, or `10(1d20+5) >= 15` for how many
+    /// repetitions meet a number).
+    Read {
+        pool: ExprId,
+        reading: Annotation,
+    },
+    /// `count` rolls of `expr`. Modifiers inside `expr` apply within each
+    /// roll, and `modifiers` apply across the results: `6(4d6kh3)dl1` keeps
+    /// the best three dice of each group, then drops the lowest group.
+    Repetition {
+        count: ExprId,
+        expr: ExprId,
+        modifiers: Vec<DiceModifier>,
+    },
+    /// The dice of several pools taken together (`{1d12, 2d6}kh1`), so
+    /// `modifiers` keep or drop single dice across all of them. A member
+    /// that is not a pool adds its total as one die.
+    Group {
+        members: Vec<ExprId>,
+        modifiers: Vec<DiceModifier>,
+    },
+    /// A pool rolled again while `condition` holds on it, up to `limit`
+    /// times (`4d6rr(sum<8)`). Its result is the last roll.
+    Reroll {
+        pool: ExprId,
+        condition: Condition,
+        limit: u32,
+    },
+    /// A weighted pick between words (`choose{sword:3, bow, staff}`). Its
+    /// total is the index of the pick.
+    Choose(Vec<Choice>),
+    /// Two sides rolled against each other (`1d20+5 vs 1d20+3`). Only
+    /// appears as a root; its total is the first side's margin.
+    Contest(ExprId, ExprId),
+    /// A roll checked against ascending thresholds (`1d20+7 vs 10/15/20`).
+    /// Only appears as a root; its total is the number of thresholds
+    /// reached.
+    Tiers {
+        expr: ExprId,
+        thresholds: Vec<i32>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiceModifierType {
+    KeepHigh,
+    KeepLow,
+    DropHigh,
+    DropLow,
+    Explode,
+    /// Exploding dice that add into the die that exploded instead of adding
+    /// new dice (`!!`).
+    Compound,
+    /// A die showing the value (default: the highest face) rolls once more
+    /// and adds the new roll into itself (`ra`). Unlike `!!` it does not
+    /// chain, and it can trigger below the highest face.
+    RerollAdd,
+    /// A die showing the value or lower (default 1) is rolled again until it
+    /// shows something higher, at most [`DEFAULT_REROLL_LIMIT`] times (`r`).
+    Reroll,
+    /// Like [`Reroll`](DiceModifierType::Reroll), but rolls again only once
+    /// and keeps the new roll (`ro`).
+    RerollOnce,
+    /// Counts dice at or above the value as successes instead of summing.
+    Target,
+    /// World of Darkness: 10-again, then successes at the value (default 8).
+    Wod,
+    /// In a success pool, dice at or above the value (default: the highest
+    /// face) count as two successes.
+    Double,
+}
+
+impl DiceModifierType {
+    /// Whether the modifier turns a pool into a count of successes.
+    pub fn counts_successes(&self) -> bool {
+        matches!(self, DiceModifierType::Target | DiceModifierType::Wod)
+    }
+}
+
+#[derive(Debug)]
+pub struct DiceModifier {
+    pub kind: DiceModifierType,
+    pub value: Option<ExprId>,
+    /// Faces that trigger an explode instead of a threshold (`!{1,6}`).
+    pub faces: Vec<i32>,
+    pub span: Span,
+}
+
+impl DiceModifier {
+    pub fn new(kind: DiceModifierType, value: Option<ExprId>) -> Self {
+        DiceModifier {
+            kind,
+            value,
+            faces: Vec::new(),
+            span: Span::default(),
+        }
+    }
+}
+
+/// How `half` rounds an odd total.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rounding {
+    #[default]
+    Down,
+    Up,
+}
+
+/// The built-in functions, e.g. `half(8d6)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    /// Halves the total, for resistance: `half(8d6)`, `half(8d6, up)`.
+    Half(Rounding),
+    /// Doubles the total, for vulnerability.
+    Double,
+    /// Bounds the first argument by the other two: `clamp(1d20-5, 1, 20)`.
+    Clamp,
+    /// A uniform roll between two bounds, inclusive: `between(37, 213)`,
+    /// also written `d[37..213]`.
+    Between,
+}
+
+impl Function {
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::Half(_) => "half",
+            Function::Double => "double",
+            Function::Clamp => "clamp",
+            Function::Between => "between",
+        }
+    }
+
+    /// How many expressions the function takes.
+    pub fn arity(self) -> usize {
+        match self {
+            Function::Half(_) | Function::Double => 1,
+            Function::Between => 2,
+            Function::Clamp => 3,
+        }
+    }
+}
+
+/// A comparison against a value, as in `count>=5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Compare {
+    pub fn test(self, lhs: i32, rhs: i32) -> bool {
+        match self {
+            Compare::Less => lhs < rhs,
+            Compare::LessOrEqual => lhs <= rhs,
+            Compare::Equal => lhs == rhs,
+            Compare::GreaterOrEqual => lhs >= rhs,
+            Compare::Greater => lhs > rhs,
+        }
+    }
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compare::Less => "<",
+            Compare::LessOrEqual => "<=",
+            Compare::Equal => "=",
+            Compare::GreaterOrEqual => ">=",
+            Compare::Greater => ">",
+        })
+    }
+}
+
+/// A value read off a root's roll besides its total, as in
+/// `8d6 [sum, count>=5, max]`. Pools are read die by die; anything else as
+/// its total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Annotation {
+    Sum,
+    Max,
+    Min,
+    Count(Compare, i32),
+    /// How many different faces show.
+    Distinct,
+    /// The length of the longest run of consecutive faces.
+    Straight,
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Annotation::Sum => f.write_str("sum"),
+            Annotation::Max => f.write_str("max"),
+            Annotation::Min => f.write_str("min"),
+            Annotation::Distinct => f.write_str("distinct"),
+            Annotation::Straight => f.write_str("straight"),
+            Annotation::Count(compare, value) => write!(f, "count{}{}", compare, value),
+        }
+    }
+}
+
+/// A test on a value read off a pool, as in `sum<8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub stat: Annotation,
+    pub compare: Compare,
+    pub value: i32,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.stat, self.compare, self.value)
+    }
+}
+
+/// One option of `choose`, picked with a chance proportional to its weight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choice {
+    pub text: String,
+    pub weight: i32,
+}
+
+/// How many times `rr` rerolls a pool unless told otherwise.
+pub const DEFAULT_REROLL_LIMIT: u32 = 100;
+
+/// A set of expressions stored in one flat arena. Nodes refer to their
+/// children by [`ExprId`], and `roots` lists the top-level expressions in
+/// source order. A root may carry a label, as in `perception: 1d20+5`, and
+/// annotations, as in `8d6 [sum, max]`.
+///
+/// Nodes are only ever added, so ids stay valid for the life of the `Ast`;
+/// passes that rewrite a node leave its old children in place, unreferenced.
+#[derive(Debug, Default)]
+pub struct Ast {
+    nodes: Vec<Expr>,
+    roots: Vec<ExprId>,
+    labels: Vec<(ExprId, String)>,
+    annotations: Vec<(ExprId, Vec<Annotation>)>,
+}
+
+impl Ast {
+    pub fn new() -> Self {
+        Ast::default()
+    }
+
+    pub fn push(&mut self, kind: ExprKind, span: Span) -> ExprId {
+        let id = ExprId(self.nodes.len() as u32);
+        self.nodes.push(Expr { kind, span });
+        id
+    }
+
+    pub fn roots(&self) -> &[ExprId] {
+        &self.roots
+    }
+
+    pub fn push_root(&mut self, id: ExprId) {
+        self.roots.push(id);
+    }
+
+    /// Puts `new` in the place of the root `old`, which passes its label and
+    /// annotations on to it.
+    pub fn replace_root(&mut self, old: ExprId, new: ExprId) {
+        for root in &mut self.roots {
+            if *root == old {
+                *root = new;
+            }
+        }
+        for (id, _) in &mut self.labels {
+            if *id == old {
+                *id = new;
+            }
+        }
+        for (id, _) in &mut self.annotations {
+            if *id == old {
+                *id = new;
+            }
+        }
+    }
+
+    pub fn set_label(&mut self, root: ExprId, label: String) {
+        self.labels.retain(|(id, _)| *id != root);
+        self.labels.push((root, label));
+    }
+
+    pub fn label(&self, root: ExprId) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(id, _)| *id == root)
+            .map(|(_, label)| label.as_str())
+    }
+
+    pub fn set_annotations(&mut self, root: ExprId, annotations: Vec<Annotation>) {
+        self.annotations.retain(|(id, _)| *id != root);
+        self.annotations.push((root, annotations));
+    }
+
+    /// The root's annotations, empty if it has none.
+    pub fn annotations(&self, root: ExprId) -> &[Annotation] {
+        self.annotations
+            .iter()
+            .find(|(id, _)| *id == root)
+            .map_or(&[], |(_, annotations)| annotations.as_slice())
+    }
+
+    pub fn number(&mut self, n: i32) -> ExprId {
+        self.push(ExprKind::Number(n), Span::default())
+    }
+
+    pub fn dice(&mut self, count: ExprId, sides: ExprId, modifiers: Vec<DiceModifier>) -> ExprId {
+        let kind = ExprKind::Dice {
+            count,
+            sides,
+            modifiers,
+        };
+        self.push(kind, Span::default())
+    }
+
+    pub fn fudge(&mut self, count: ExprId, modifiers: Vec<DiceModifier>) -> ExprId {
+        self.push(ExprKind::Fudge { count, modifiers }, Span::default())
+    }
+
+    pub fn binary(&mut self, lhs: ExprId, op: char, rhs: ExprId) -> ExprId {
+        self.push(ExprKind::BinaryOp(lhs, op, rhs), Span::default())
+    }
+
+    pub fn contest(&mut self, first: ExprId, second: ExprId) -> ExprId {
+        self.push(ExprKind::Contest(first, second), Span::default())
+    }
+
+    pub fn tiers(&mut self, expr: ExprId, thresholds: Vec<i32>) -> ExprId {
+        self.push(ExprKind::Tiers { expr, thresholds }, Span::default())
+    }
+
+    pub fn read(&mut self, pool: ExprId, reading: Annotation) -> ExprId {
+        self.push(ExprKind::Read { pool, reading }, Span::default())
+    }
+
+    pub fn call(&mut self, function: Function, args: Vec<ExprId>) -> ExprId {
+        self.push(ExprKind::Call { function, args }, Span::default())
+    }
+
+    pub fn repetition(
+        &mut self,
+        count: ExprId,
+        expr: ExprId,
+        modifiers: Vec<DiceModifier>,
+    ) -> ExprId {
+        let kind = ExprKind::Repetition {
+            count,
+            expr,
+            modifiers,
+        };
+        self.push(kind, Span::default())
+    }
+
+    pub fn group(&mut self, members: Vec<ExprId>, modifiers: Vec<DiceModifier>) -> ExprId {
+        self.push(ExprKind::Group { members, modifiers }, Span::default())
+    }
+
+    pub fn reroll(&mut self, pool: ExprId, condition: Condition, limit: u32) -> ExprId {
+        let kind = ExprKind::Reroll {
+            pool,
+            condition,
+            limit,
+        };
+        self.push(kind, Span::default())
+    }
+
+    pub fn choose(&mut self, choices: Vec<Choice>) -> ExprId {
+        self.push(ExprKind::Choose(choices), Span::default())
+    }
+
+    /// The direct children of a node, in source order.
+    pub fn children(&self, id: ExprId) -> SmallVec<[ExprId; 4]> {
+        let modifier_values = |modifiers: &[DiceModifier]| {
+            modifiers
+                .iter()
+                .filter_map(|m| m.value)
+                .collect::<SmallVec<[ExprId; 4]>>()
+        };
+        match &self[id].kind {
+            ExprKind::Number(_) | ExprKind::Choose(_) => SmallVec::new(),
+            ExprKind::Dice {
+                count,
+                sides,
+                modifiers,
+            } => {
+                let mut children = SmallVec::from_slice(&[*count, *sides]);
+                children.extend(modifier_values(modifiers));
+                children
+            }
+            ExprKind::Fudge { count, modifiers } => {
+                let mut children = SmallVec::from_slice(&[*count]);
+                children.extend(modifier_values(modifiers));
+                children
+            }
+            ExprKind::BinaryOp(lhs, _, rhs) | ExprKind::Contest(lhs, rhs) => {
+                SmallVec::from_slice(&[*lhs, *rhs])
+            }
+            ExprKind::Tiers { expr, .. }
+            | ExprKind::Read { pool: expr, .. }
+            | ExprKind::Reroll { pool: expr, .. } => SmallVec::from_slice(&[*expr]),
+            ExprKind::Call { args, .. } => SmallVec::from_slice(args),
+            ExprKind::Group { members, modifiers } => {
+                let mut children = SmallVec::from_slice(members);
+                children.extend(modifier_values(modifiers));
+                children
+            }
+            ExprKind::Repetition {
+                count,
+                expr,
+                modifiers,
+            } => {
+                let mut children = SmallVec::from_slice(&[*count, *expr]);
+                children.extend(modifier_values(modifiers));
+                children
+            }
+        }
+    }
+
+    /// Whether two subtrees, possibly from different arenas, have the same
+    /// shape and values. Spans are ignored.
+    pub fn same_structure(&self, id: ExprId, other: &Ast, other_id: ExprId) -> bool {
+        let same_modifiers = |a: &[DiceModifier], b: &[DiceModifier]| {
+            a.len() == b.len()
+                && a.iter().zip(b).all(|(a, b)| {
+                    a.kind == b.kind
+                        && a.faces == b.faces
+                        && match (a.value, b.value) {
+                            (Some(x), Some(y)) => self.same_structure(x, other, y),
+                            (None, None) => true,
+                            _ => false,
+                        }
+                })
+        };
+        match (&self[id].kind, &other[other_id].kind) {
+            (ExprKind::Number(a), ExprKind::Number(b)) => a == b,
+            (ExprKind::Choose(a), ExprKind::Choose(b)) => a == b,
+            (
+                ExprKind::Dice {
+                    count: c1,
+                    sides: s1,
+                    modifiers: m1,
+                },
+                ExprKind::Dice {
+                    count: c2,
+                    sides: s2,
+                    modifiers: m2,
+                },
+            ) => {
+                self.same_structure(*c1, other, *c2)
+                    && self.same_structure(*s1, other, *s2)
+                    && same_modifiers(m1, m2)
+            }
+            (
+                ExprKind::Fudge {
+                    count: c1,
+                    modifiers: m1,
+                },
+                ExprKind::Fudge {
+                    count: c2,
+                    modifiers: m2,
+                },
+            ) => self.same_structure(*c1, other, *c2) && same_modifiers(m1, m2),
+            (ExprKind::BinaryOp(l1, op1, r1), ExprKind::BinaryOp(l2, op2, r2)) => {
+                op1 == op2
+                    && self.same_structure(*l1, other, *l2)
+                    && self.same_structure(*r1, other, *r2)
+            }
+            (ExprKind::Contest(l1, r1), ExprKind::Contest(l2, r2)) => {
+                self.same_structure(*l1, other, *l2) && self.same_structure(*r1, other, *r2)
+            }
+            (
+                ExprKind::Tiers {
+                    expr: e1,
+                    thresholds: t1,
+                },
+                ExprKind::Tiers {
+                    expr: e2,
+                    thresholds: t2,
+                },
+            ) => t1 == t2 && self.same_structure(*e1, other, *e2),
+            (
+                ExprKind::Read {
+                    pool: p1,
+                    reading: r1,
+                },
+                ExprKind::Read {
+                    pool: p2,
+                    reading: r2,
+                },
+            ) => r1 == r2 && self.same_structure(*p1, other, *p2),
+            (
+                ExprKind::Call {
+                    function: f1,
+                    args: a1,
+                },
+                ExprKind::Call {
+                    function: f2,
+                    args: a2,
+                },
+            ) => {
+                f1 == f2
+                    && a1.len() == a2.len()
+                    && a1
+                        .iter()
+                        .zip(a2)
+                        .all(|(a, b)| self.same_structure(*a, other, *b))
+            }
+            (
+                ExprKind::Reroll {
+                    pool: p1,
+                    condition: c1,
+                    limit: l1,
+                },
+                ExprKind::Reroll {
+                    pool: p2,
+                    condition: c2,
+                    limit: l2,
+                },
+            ) => c1 == c2 && l1 == l2 && self.same_structure(*p1, other, *p2),
+            (
+                ExprKind::Group {
+                    members: a1,
+                    modifiers: m1,
+                },
+                ExprKind::Group {
+                    members: a2,
+                    modifiers: m2,
+                },
+            ) => {
+                a1.len() == a2.len()
+                    && a1
+                        .iter()
+                        .zip(a2)
+                        .all(|(a, b)| self.same_structure(*a, other, *b))
+                    && same_modifiers(m1, m2)
+            }
+            (
+                ExprKind::Repetition {
+                    count: c1,
+                    expr: e1,
+                    modifiers: m1,
+                },
+                ExprKind::Repetition {
+                    count: c2,
+                    expr: e2,
+                    modifiers: m2,
+                },
+            ) => {
+                self.same_structure(*c1, other, *c2)
+                    && self.same_structure(*e1, other, *e2)
+                    && same_modifiers(m1, m2)
+            }
+            _ => false,
+        }
+    }
+
+    /// Formats one expression in canonical form; see [`DisplayExpr`].
+    pub fn display(&self, id: ExprId) -> DisplayExpr<'_> {
+        DisplayExpr { ast: self, id }
+    }
+}
+
+impl Index<ExprId> for Ast {
+    type Output = Expr;
+
+    fn index(&self, id: ExprId) -> &Expr {
+        &self.nodes[id.0 as usize]
+    }
+}
+
+impl IndexMut<ExprId> for Ast {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expr {
+        &mut self.nodes[id.0 as usize]
+    }
+}
+
+/// Prints every root expression in canonical form, separated by spaces.
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, root) in self.roots.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            if let Some(label) = self.label(*root) {
+                write!(f, "{}: ", label)?;
+            }
+            write!(f, "{}", self.display(*root))?;
+            let annotations: Vec<String> = self
+                .annotations(*root)
+                .iter()
+                .map(|a| a.to_string())
+                .collect();
+            if !annotations.is_empty() {
+                write!(f, " [{}]", annotations.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DiceModifierType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let token = match self {
+            DiceModifierType::KeepHigh => "kh",
+            DiceModifierType::KeepLow => "kl",
+            DiceModifierType::DropHigh => "dh",
+            DiceModifierType::DropLow => "dl",
+            DiceModifierType::Explode => "!",
+            DiceModifierType::Compound => "!!",
+            DiceModifierType::Target => "t",
+            DiceModifierType::Wod => "wod",
+            DiceModifierType::Double => "x",
+            DiceModifierType::RerollAdd => "ra",
+            DiceModifierType::Reroll => "r",
+            DiceModifierType::RerollOnce => "ro",
+        };
+        f.write_str(token)
+    }
+}
+
+/// Prints the canonical form of an expression, e.g. `4d6kh3 + 2`.
+///
+/// The output parses back to the same tree, so it can be used to normalize
+/// user input.
+pub struct DisplayExpr<'a> {
+    ast: &'a Ast,
+    id: ExprId,
+}
+
+impl DisplayExpr<'_> {
+    fn child(&self, id: ExprId) -> Self {
+        DisplayExpr { ast: self.ast, id }
+    }
+
+    fn precedence(&self) -> u8 {
+        match &self.ast[self.id].kind {
+            ExprKind::BinaryOp(_, op, _) => precedence(*op),
+            _ => u8::MAX,
+        }
+    }
+
+    fn write_operand(&self, f: &mut fmt::Formatter<'_>, parens: bool) -> fmt::Result {
+        if parens {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+
+    /// Dice counts, sides and modifier values must be a bare number or parens.
+    fn write_term(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let is_number = matches!(self.ast[self.id].kind, ExprKind::Number(_));
+        self.write_operand(f, !is_number)
+    }
+
+    fn write_modifiers(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        modifiers: &[DiceModifier],
+    ) -> fmt::Result {
+        for modifier in modifiers {
+            write!(f, "{}", modifier.kind)?;
+            if let Some(value) = modifier.value {
+                self.child(value).write_term(f)?;
+            }
+            if !modifier.faces.is_empty() {
+                let faces: Vec<String> = modifier.faces.iter().map(i32::to_string).collect();
+                write!(f, "{{{}}}", faces.join(","))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DisplayExpr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.ast[self.id].kind {
+            ExprKind::Number(n) => write!(f, "{}", n),
+            ExprKind::Dice {
+                count,
+                sides,
+                modifiers,
+            } => {
+                self.child(*count).write_term(f)?;
+                write!(f, "d")?;
+                self.child(*sides).write_term(f)?;
+                self.write_modifiers(f, modifiers)
+            }
+            ExprKind::Fudge { count, modifiers } => {
+                self.child(*count).write_term(f)?;
+                write!(f, "dF")?;
+                self.write_modifiers(f, modifiers)
+            }
+            ExprKind::BinaryOp(lhs, op, rhs) => {
+                let prec = precedence(*op);
+                let (lhs, rhs) = (self.child(*lhs), self.child(*rhs));
+                lhs.write_operand(f, lhs.precedence() < prec)?;
+                write!(f, " {} ", op)?;
+                rhs.write_operand(f, rhs.precedence() <= prec)
+            }
+            ExprKind::Contest(first, second) => {
+                write!(f, "{} vs {}", self.child(*first), self.child(*second))
+            }
+            ExprKind::Tiers { expr, thresholds } => {
+                write!(
+                    f,
+                    "{} vs {}",
+                    self.child(*expr),
+                    format_thresholds(thresholds)
+                )
+            }
+            ExprKind::Read { pool, reading } => {
+                write!(f, "{}", self.child(*pool))?;
+                match reading {
+                    Annotation::Max => write!(f, "h"),
+                    Annotation::Min => write!(f, "l"),
+                    Annotation::Count(compare, value) => write!(f, " {} {}", compare, value),
+                    reading => write!(f, "{}", reading),
+                }
+            }
+            ExprKind::Call { function, args } => {
+                write!(f, "{}(", function.name())?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", self.child(*arg))?;
+                }
+                if let Function::Half(Rounding::Up) = function {
+                    write!(f, ", up")?;
+                }
+                write!(f, ")")
+            }
+            ExprKind::Choose(choices) => {
+                let choices: Vec<String> = choices
+                    .iter()
+                    .map(|c| match c.weight {
+                        1 => c.text.clone(),
+                        weight => format!("{}:{}", c.text, weight),
+                    })
+                    .collect();
+                write!(f, "choose{{{}}}", choices.join(", "))
+            }
+            ExprKind::Reroll {
+                pool,
+                condition,
+                limit,
+            } => {
+                write!(f, "{}rr({}", self.child(*pool), condition)?;
+                if *limit != DEFAULT_REROLL_LIMIT {
+                    write!(f, ", {}", limit)?;
+                }
+                write!(f, ")")
+            }
+            ExprKind::Group { members, modifiers } => {
+                let members: Vec<String> =
+                    members.iter().map(|&m| self.child(m).to_string()).collect();
+                write!(f, "{{{}}}", members.join(", "))?;
+                self.write_modifiers(f, modifiers)
+            }
+            ExprKind::Repetition {
+                count,
+                expr,
+                modifiers,
+            } => {
+                self.child(*count).write_term(f)?;
+                write!(f, "({})", self.child(*expr))?;
+                self.write_modifiers(f, modifiers)
+            }
+        }
+    }
+}
+
+/// Formats tier thresholds as written, e.g. `10/15/20`.
+pub fn format_thresholds(thresholds: &[i32]) -> String {
+    let thresholds: Vec<String> = thresholds.iter().map(i32::to_string).collect();
+    thresholds.join("/")
+}
+
+pub(crate) fn precedence(op: char) -> u8 {
+    match op {
+        '+' | '-' => 1,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_and_displays() {
+        let mut ast = Ast::new();
+        let (four, six, three) = (ast.number(4), ast.number(6), ast.number(3));
+        let keep = DiceModifier::new(DiceModifierType::KeepHigh, Some(three));
+        let dice = ast.dice(four, six, vec![keep]);
+        let two = ast.number(2);
+        let root = ast.binary(dice, '+', two);
+        ast.push_root(root);
+
+        assert_eq!(ast.to_string(), "4d6kh3 + 2");
+        assert_eq!(ast.children(dice).as_slice(), &[four, six, three]);
+        assert_eq!(ast.children(root).as_slice(), &[dice, two]);
+    }
+
+    #[test]
+    fn same_structure_ignores_ids_and_spans() {
+        let mut a = Ast::new();
+        let (one, two) = (a.number(1), a.number(2));
+        let sum = a.binary(one, '+', two);
+
+        let mut b = Ast::new();
+        b.push(ExprKind::Number(0), Span::new(3, 4));
+        let (one, two) = (b.number(1), b.number(2));
+        let same = b.binary(one, '+', two);
+        let different = b.binary(two, '+', one);
+
+        assert!(a.same_structure(sum, &b, same));
+        assert!(!a.same_structure(sum, &b, different));
+    }
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::Path;
+
+use reroll::macros::Macros;
+
+use crate::cli::config;
+use crate::cli::state;
+
+/// `roll aliases`: lists the named rolls and macros in the config, and
+/// exports or imports them as a library to share with a group.
+pub fn run(args: &[String]) -> i32 {
+    let mut merge = false;
+    let mut force = false;
+    let mut rest = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--merge" => merge = true,
+            "--force" => force = true,
+            _ => rest.push(arg.as_str()),
+        }
+    }
+
+    let result = match rest.as_slice() {
+        ["list"] | [] => list(),
+        ["export"] => export(None),
+        ["export", path] => export(Some(path)),
+        ["import", path] => import(path, merge, force),
+        _ => return usage(),
+    };
+    match result {
+        Ok(output) => {
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn usage() -> i32 {
+    eprintln!(
+        "Usage: roll aliases [list]\n       \
+         roll aliases export [<file>]\n       \
+         roll aliases import <file> [--merge] [--force]\n\n\
+         Export writes the config's named rolls and macros as a `[macros]`\n\
+         file. Import replaces them with a file's, or with --merge adds the\n\
+         file's to them; either way it stops before changing or removing a\n\
+         macro unless --force is given."
+    );
+    1
+}
+
+fn list() -> Result<String, String> {
+    let config = config::load()?;
+    let lines: Vec<String> = config.macros.iter().map(|m| m.to_string()).collect();
+    Ok(lines.join("\n"))
+}
+
+fn export(path: Option<&str>) -> Result<String, String> {
+    let config = config::load()?;
+    let library = config::replace_macros("", &config.macros);
+    match path {
+        Some(path) => {
+            fs::write(path, library).map_err(|err| format!("{}: {}", path, err))?;
+            Ok(format!(
+                "exported {} macros to {}",
+                config.macros.len(),
+                path
+            ))
+        }
+        None => Ok(library.trim_end().to_string()),
+    }
+}
+
+fn import(path: &str, merge: bool, force: bool) -> Result<String, String> {
+    let library = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|source| config::parse(&source))
+        .map_err(|err| format!("{}: {}", path, err))?
+        .macros;
+    let config_path = config::path()?;
+    let source = match fs::read_to_string(&config_path) {
+        Ok(source) => source,
+        Err(_) if !config_path.exists() => String::new(),
+        Err(err) => return Err(format!("{}: {}", config_path.display(), err)),
+    };
+    let current = config::parse(&source)
+        .map_err(|err| format!("{}: {}", config_path.display(), err))?
+        .macros;
+
+    let mut conflicts = Vec::new();
+    for existing in current.iter() {
+        match library.get(&existing.name) {
+            Some(imported) if imported != existing => {
+                conflicts.push(format!("{} (changed)", existing.name))
+            }
+            None if !merge => conflicts.push(format!("{} (removed)", existing.name)),
+            _ => {}
+        }
+    }
+    if !conflicts.is_empty() && !force {
+        return Err(format!(
+            "importing {} would overwrite: {}\nuse --force to import anyway{}",
+            path,
+            conflicts.join(", "),
+            if merge {
+                ""
+            } else {
+                ", or --merge to keep the others"
+            }
+        ));
+    }
+
+    let mut macros = if merge { current } else { Macros::default() };
+    let count = library.len();
+    for m in library.iter() {
+        macros.define(m.clone());
+    }
+    write_config(&config_path, &config::replace_macros(&source, &macros))?;
+    Ok(format!("imported {} macros from {}", count, path))
+}
+
+fn write_config(path: &Path, contents: &str) -> Result<(), String> {
+    state::write(path, contents).map_err(|err| format!("{}: {}", path.display(), err))
+}
@@ -0,0 +1,108 @@
+use std::fs;
+use std::path::PathBuf;
+
+use reroll::deck::Deck;
+
+use crate::cli::state;
+
+/// `roll bag`: a bag of tokens that stays filled between invocations.
+/// Drawn tokens stay out until the bag is reset.
+pub fn run(args: &[String]) -> i32 {
+    let mut name = "default".to_string();
+    let mut rest = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--name" => match args.next() {
+                Some(n) => name = n.clone(),
+                None => return usage(),
+            },
+            _ => rest.push(arg.as_str()),
+        }
+    }
+
+    let result = match rest.as_slice() {
+        ["init", contents @ ..] if !contents.is_empty() => init(&name, &contents.join(" ")),
+        ["draw"] => draw(&name, 1),
+        ["draw", count] => match count.parse::<usize>() {
+            Ok(count) if count > 0 => draw(&name, count),
+            _ => return usage(),
+        },
+        ["reset"] => reset(&name),
+        ["show"] => load(&name).map(|(_, bag)| bag.summary()),
+        _ => return usage(),
+    };
+
+    match result {
+        Ok(output) => {
+            println!("{}", output);
+            0
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn usage() -> i32 {
+    eprintln!(
+        "Usage: roll bag [--name <bag>] init \"<n> <token>, ...\"\n       \
+         roll bag [--name <bag>] draw [<n>]\n       \
+         roll bag [--name <bag>] reset\n       \
+         roll bag [--name <bag>] show\n\n\
+         Drawn tokens stay out of the bag until it is reset."
+    );
+    1
+}
+
+fn path(name: &str) -> Result<PathBuf, String> {
+    Ok(state::data_dir()?
+        .join("bags")
+        .join(format!("{}.txt", name)))
+}
+
+/// Loads a bag's original contents and the tokens still in it.
+fn load(name: &str) -> Result<(String, Deck), String> {
+    let path = path(name)?;
+    let saved = fs::read_to_string(&path)
+        .map_err(|_| format!("no bag named {}; create it with `roll bag init`", name))?;
+    let contents = saved
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("# "))
+        .ok_or_else(|| format!("{}: not a saved bag", path.display()))?;
+    Ok((contents.to_string(), Deck::parse(&saved)))
+}
+
+fn save(name: &str, contents: &str, bag: &Deck) -> Result<(), String> {
+    let path = path(name)?;
+    let text = format!("# {}\n{}", contents, bag.to_text());
+    state::write(&path, &text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn init(name: &str, contents: &str) -> Result<String, String> {
+    let bag = Deck::from_counts(contents)?;
+    if bag.is_empty() {
+        return Err("the bag has no tokens".to_string());
+    }
+    save(name, contents, &bag)?;
+    Ok(bag.summary())
+}
+
+fn draw(name: &str, count: usize) -> Result<String, String> {
+    let (contents, mut bag) = load(name)?;
+    if bag.is_empty() {
+        return Err(format!("the {} bag is empty; use `roll bag reset`", name));
+    }
+    bag.shuffle();
+    let drawn = bag.draw(count);
+    save(name, &contents, &bag)?;
+    Ok(format!("{} ({} left)", drawn.join(", "), bag.len()))
+}
+
+fn reset(name: &str) -> Result<String, String> {
+    let (contents, _) = load(name)?;
+    init(name, &contents)
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use reroll::compile::Program;
+use reroll::distribution::exact;
+use reroll::optimize::fold_all;
+use reroll::parser::parse;
+
+const DEFAULT_SAMPLES: u64 = 10_000;
+
+/// `roll calibrate`: rolls an expression many times and compares how often
+/// each total came up with its exact chance, as a check on the roller.
+pub fn run(args: &[String]) -> i32 {
+    let mut samples = DEFAULT_SAMPLES;
+    let mut expr_parts = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" | "--samples" => match args.next().map(|s| s.parse::<u64>()) {
+                Some(Ok(n)) if n > 0 => samples = n,
+                _ => {
+                    eprintln!("{} requires a positive integer", arg);
+                    return 1;
+                }
+            },
+            _ => expr_parts.push(arg.to_lowercase()),
+        }
+    }
+
+    if expr_parts.is_empty() {
+        eprintln!(
+            "Usage: roll calibrate [-n <samples>] <expr>\n\n\
+             Rolls the expression and prints, for each total, its exact chance,\n\
+             how often it came up and the difference, with a chi-square\n\
+             statistic over all of them.\n\n\
+             Options:\n\
+             \t-n, --samples <n>   Number of rolls (default {})",
+            DEFAULT_SAMPLES
+        );
+        return 1;
+    }
+
+    let mut ast = match parse(&expr_parts.join(" ")) {
+        Ok(ast) if ast.roots().len() == 1 => ast,
+        Ok(_) => {
+            eprintln!("roll calibrate takes a single expression");
+            return 1;
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    fold_all(&mut ast);
+    let root = ast.roots()[0];
+    let Some(dist) = exact(&ast, root) else {
+        eprintln!("{}: no exact distribution to compare with", ast);
+        return 1;
+    };
+
+    let program = Program::compile(&ast, root);
+    let mut counts = BTreeMap::new();
+    for _ in 0..samples {
+        *counts.entry(program.total()).or_insert(0u64) += 1;
+    }
+
+    println!("{} ({} samples)", ast, samples);
+    println!("total  expected  observed  deviation");
+    let mut chi_square = 0.0;
+    for (value, p) in dist.iter() {
+        let observed = counts.get(&value).copied().unwrap_or(0) as f64;
+        let expected = p * samples as f64;
+        chi_square += (observed - expected).powi(2) / expected;
+        let observed = observed / samples as f64;
+        println!(
+            "{:>5}  {:>7.2}%  {:>7.2}%  {:>+8.2}%",
+            value,
+            p * 100.0,
+            observed * 100.0,
+            (observed - p) * 100.0
+        );
+    }
+    println!(
+        "chi-square {:.2} with {} degrees of freedom",
+        chi_square,
+        dist.iter().count() - 1
+    );
+    0
+}
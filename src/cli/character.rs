@@ -0,0 +1,156 @@
+use std::fs;
+use std::path::PathBuf;
+
+use reroll::macros::{Macro, Macros};
+
+use crate::cli::state;
+
+/// `roll char`: character sheets of named modifiers, which `roll -c <name>`
+/// makes available in expressions, as in `roll -c fighter "1d20+str+prof"`.
+pub fn run(args: &[String]) -> i32 {
+    let mut sets = Vec::new();
+    let mut rest = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--set" => match args.next() {
+                Some(set) => sets.push(set.as_str()),
+                None => return usage(),
+            },
+            _ => rest.push(arg.as_str()),
+        }
+    }
+
+    let result = match rest.as_slice() {
+        ["add", name] => add(name, &sets),
+        ["show", name] if sets.is_empty() => load(name).map(|sheet| format_sheet(&sheet)),
+        ["remove", name] if sets.is_empty() => remove(name),
+        _ => return usage(),
+    };
+
+    match result {
+        Ok(output) => {
+            println!("{}", output);
+            0
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn usage() -> i32 {
+    eprintln!(
+        "Usage: roll char add <name> --set <stat>=<n>...\n       \
+         roll char show <name>\n       \
+         roll char remove <name>\n\n\
+         `add` creates the character or updates the stats given. Roll with\n\
+         a character's stats using `roll -c <name> \"1d20+str+prof\"`."
+    );
+    1
+}
+
+/// A character's stats in the order they were added.
+type Sheet = Vec<(String, i32)>;
+
+fn path(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("bad character name `{}`", name));
+    }
+    Ok(state::data_dir()?
+        .join("characters")
+        .join(format!("{}.toml", name)))
+}
+
+/// Loads a character's sheet, stored as `stat = n` lines.
+fn load(name: &str) -> Result<Sheet, String> {
+    let path = path(name)?;
+    let saved = fs::read_to_string(&path).map_err(|_| {
+        format!(
+            "no character named {}; create it with `roll char add`",
+            name
+        )
+    })?;
+    saved
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| parse_stat(line).map_err(|err| format!("{}: {}", path.display(), err)))
+        .collect()
+}
+
+/// Reads `str=+5` (or `str = 5`, as saved) into a stat and its modifier.
+fn parse_stat(text: &str) -> Result<(String, i32), String> {
+    let (stat, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected <stat>=<n>, got `{}`", text))?;
+    let stat = stat.trim().to_lowercase();
+    let value = value.trim();
+    let value = value
+        .strip_prefix('+')
+        .unwrap_or(value)
+        .parse::<i32>()
+        .map_err(|_| format!("`{}` is not a whole number", value))?;
+    // Stats are used like macros, so they follow the same naming rules.
+    Macro::parse(&stat, "0")?;
+    Ok((stat, value))
+}
+
+fn format_sheet(sheet: &Sheet) -> String {
+    let stats: Vec<String> = sheet
+        .iter()
+        .map(|(stat, value)| format!("{} {:+}", stat, value))
+        .collect();
+    stats.join(", ")
+}
+
+fn add(name: &str, sets: &[&str]) -> Result<String, String> {
+    if sets.is_empty() {
+        return Err("give at least one --set <stat>=<n>".to_string());
+    }
+    let mut sheet = if path(name)?.exists() {
+        load(name)?
+    } else {
+        Vec::new()
+    };
+    for set in sets {
+        let (stat, value) = parse_stat(set)?;
+        match sheet.iter_mut().find(|(s, _)| *s == stat) {
+            Some(existing) => existing.1 = value,
+            None => sheet.push((stat, value)),
+        }
+    }
+    let text: String = sheet
+        .iter()
+        .map(|(stat, value)| format!("{} = {}\n", stat, value))
+        .collect();
+    let path = path(name)?;
+    state::write(&path, &text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(format!("{}: {}", name, format_sheet(&sheet)))
+}
+
+fn remove(name: &str) -> Result<String, String> {
+    let path = path(name)?;
+    fs::remove_file(&path).map_err(|_| format!("no character named {}", name))?;
+    Ok(format!("removed {}", name))
+}
+
+/// A character's stats as named rolls, to expand in expressions.
+pub fn macros(name: &str) -> Result<Macros, String> {
+    let mut macros = Macros::default();
+    for (stat, value) in load(name)? {
+        // The grammar has no negative literals.
+        let body = if value < 0 {
+            format!("0 - {}", value.unsigned_abs())
+        } else {
+            value.to_string()
+        };
+        macros.define(Macro::parse(&stat, &body)?);
+    }
+    Ok(macros)
+}
@@ -0,0 +1,573 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use reroll::eval::TieBreak;
+use reroll::macros::{Macro, Macros};
+use reroll::style::{Color, Rules, Test};
+
+/// The user's settings, read from `config.toml`:
+///
+/// ```toml
+/// [macros]
+/// fireball = "8d6"
+/// gwf(n) = "nd6ra{1,2}"
+///
+/// [notation]
+/// w = "d"
+///
+/// [crits]
+/// bell = true
+///
+/// [colors]
+/// green = "total >= 20"
+/// bold = "die = max"
+///
+/// [profile.fate]
+/// ladder = true
+///
+/// [profile.fate.macros]
+/// fudge = "4df"
+///
+/// [profile.dnd.presets]
+/// attack = "--crit 19"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub macros: Macros,
+    pub defaults: Defaults,
+    /// Applied when printing to a terminal.
+    pub colors: Rules,
+    pub crits: Crits,
+    /// Local spellings of dice notation and the standard ones they stand
+    /// for, such as `w` for `d`.
+    pub notation: Vec<(String, String)>,
+    /// Arguments a preset starts with, such as `--crit 19` for `attack`.
+    pub presets: Vec<(String, String)>,
+    pub profiles: Vec<Profile>,
+}
+
+/// Settings used unless a flag says otherwise; unset ones keep the
+/// built-in defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Defaults {
+    pub verbose: Option<bool>,
+    pub ladder: Option<bool>,
+    pub ties: Option<TieBreak>,
+    /// Whether to keep every roll for `roll history`.
+    pub history: Option<bool>,
+    pub format: Option<Format>,
+}
+
+/// How `roll` prints its results: a line of text each, or a JSON record
+/// each, as `--json-out` writes them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+/// What to do when a die comes up critical, by default a natural 20 or 1
+/// on a d20.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Crits {
+    /// Empty until set, meaning the default.
+    pub when: Vec<Test>,
+    /// Ring the terminal bell.
+    pub bell: bool,
+    /// A shell command to run, given the die as JSON on its standard input.
+    pub command: Option<String>,
+}
+
+impl Crits {
+    pub fn is_active(&self) -> bool {
+        self.bell || self.command.is_some()
+    }
+
+    /// The first die, as `(sides, value)`, that counts as a crit.
+    pub fn find(&self, dice: &[(i32, i32)]) -> Option<(i32, i32)> {
+        let default = [
+            Test::parse("d20 = 20").unwrap(),
+            Test::parse("d20 = 1").unwrap(),
+        ];
+        let when = if self.when.is_empty() {
+            &default[..]
+        } else {
+            &self.when
+        };
+        dice.iter()
+            .copied()
+            .find(|&(sides, value)| when.iter().any(|t| t.matches_die(value, Some(sides))))
+    }
+}
+
+/// A named set of defaults, macros and preset arguments for one game,
+/// layered over the shared ones when active.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub defaults: Defaults,
+    pub macros: Macros,
+    pub presets: Vec<(String, String)>,
+}
+
+impl Config {
+    /// The config as seen with the profile `name` active: its defaults,
+    /// macros and preset arguments replace the shared ones they overlap.
+    pub fn activate(mut self, name: Option<&str>) -> Result<Config, String> {
+        let Some(name) = name else {
+            return Ok(self);
+        };
+        let profile = self
+            .profiles
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("no profile named `{}` in the config", name))?;
+        let defaults = profile.defaults;
+        self.defaults = Defaults {
+            verbose: defaults.verbose.or(self.defaults.verbose),
+            ladder: defaults.ladder.or(self.defaults.ladder),
+            ties: defaults.ties.or(self.defaults.ties),
+            history: defaults.history.or(self.defaults.history),
+            format: defaults.format.or(self.defaults.format),
+        };
+        for m in profile.macros.iter() {
+            self.macros.define(m.clone());
+        }
+        for (preset, args) in &profile.presets {
+            set_preset(&mut self.presets, preset, args);
+        }
+        Ok(self)
+    }
+
+    /// The arguments the preset `name` starts with, if the config sets any.
+    pub fn preset_args(&self, name: &str) -> Vec<String> {
+        self.presets
+            .iter()
+            .find(|(preset, _)| preset == name)
+            .map_or(Vec::new(), |(_, args)| {
+                args.split_whitespace().map(str::to_string).collect()
+            })
+    }
+}
+
+/// Sets the arguments of `preset`, replacing any it had.
+fn set_preset(presets: &mut Vec<(String, String)>, preset: &str, args: &str) {
+    match presets.iter_mut().find(|(name, _)| name == preset) {
+        Some((_, old)) => *old = args.to_string(),
+        None => presets.push((preset.to_string(), args.to_string())),
+    }
+}
+
+/// The profile chosen with `--profile`, or else by `$REROLL_PROFILE`.
+pub fn profile_name(flag: Option<&str>) -> Option<String> {
+    flag.map(str::to_string)
+        .or_else(|| env::var("REROLL_PROFILE").ok().filter(|p| !p.is_empty()))
+}
+
+/// `$REROLL_CONFIG` if set, otherwise `reroll/config.toml` in the
+/// platform's config directory.
+pub fn path() -> Result<PathBuf, String> {
+    let var = |name| {
+        env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    if let Some(path) = var("REROLL_CONFIG") {
+        return Ok(path);
+    }
+    let base = if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config")))
+    };
+    base.map(|dir| dir.join("reroll").join("config.toml"))
+        .ok_or_else(|| "cannot find a config directory; set REROLL_CONFIG".to_string())
+}
+
+/// Loads the config, or the defaults when there is no config file.
+pub fn load() -> Result<Config, String> {
+    let path = path()?;
+    match fs::read_to_string(&path) {
+        Ok(source) => parse(&source).map_err(|err| format!("{}: {}", path.display(), err)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(format!("{}: {}", path.display(), err)),
+    }
+}
+
+/// Where the settings being read belong.
+enum Section {
+    None,
+    Macros,
+    Defaults,
+    Colors,
+    Crits,
+    Notation,
+    Presets,
+    ProfileDefaults(usize),
+    ProfileMacros(usize),
+    ProfilePresets(usize),
+}
+
+/// Reads the subset of TOML the config uses: `[section]` headers, `key =
+/// value` pairs with quoted strings or bare `true` and `false`, and `#`
+/// comments.
+pub fn parse(source: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut section = Section::None;
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| format!("line {}: {}", i + 1, message);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(title) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = match title.trim() {
+                "macros" => Section::Macros,
+                "defaults" => Section::Defaults,
+                "colors" => Section::Colors,
+                "crits" => Section::Crits,
+                "notation" => Section::Notation,
+                "presets" => Section::Presets,
+                title => {
+                    let Some(profile) = title.strip_prefix("profile.") else {
+                        return Err(error(format!("unknown section `[{}]`", title)));
+                    };
+                    let (name, table) = match profile.rsplit_once('.') {
+                        Some((name, table @ ("macros" | "presets"))) => (name, Some(table)),
+                        _ => (profile, None),
+                    };
+                    let index = match config.profiles.iter().position(|p| p.name == name) {
+                        Some(index) => index,
+                        None => {
+                            config.profiles.push(Profile {
+                                name: name.to_string(),
+                                ..Profile::default()
+                            });
+                            config.profiles.len() - 1
+                        }
+                    };
+                    match table {
+                        Some("macros") => Section::ProfileMacros(index),
+                        Some(_) => Section::ProfilePresets(index),
+                        None => Section::ProfileDefaults(index),
+                    }
+                }
+            };
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `key = \"value\"`".to_string()))?;
+        let key = key.trim().trim_matches('"').to_lowercase();
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        match section {
+            Section::Macros => {
+                let m = Macro::parse(&key, &value.to_lowercase()).map_err(error)?;
+                config.macros.define(m);
+            }
+            Section::ProfileMacros(index) => {
+                let m = Macro::parse(&key, &value.to_lowercase()).map_err(error)?;
+                config.profiles[index].macros.define(m);
+            }
+            Section::Notation => {
+                if key.is_empty() || !key.chars().all(char::is_alphabetic) {
+                    return Err(error(format!("bad notation `{}`", key)));
+                }
+                config.notation.push((key, value.to_lowercase()));
+            }
+            Section::Crits => match key.as_str() {
+                "when" => {
+                    let tests: Result<Vec<Test>, String> =
+                        value.split(',').map(Test::parse).collect();
+                    config.crits.when = tests.map_err(error)?;
+                }
+                "bell" => {
+                    config.crits.bell = match value {
+                        "true" => true,
+                        "false" => false,
+                        _ => return Err(error("`bell` must be true or false".to_string())),
+                    }
+                }
+                "command" => config.crits.command = Some(value.to_string()),
+                _ => return Err(error(format!("unknown setting `{}`", key))),
+            },
+            Section::Presets => {
+                check_preset(&key).map_err(error)?;
+                set_preset(&mut config.presets, &key, value);
+            }
+            Section::ProfilePresets(index) => {
+                check_preset(&key).map_err(error)?;
+                set_preset(&mut config.profiles[index].presets, &key, value);
+            }
+            Section::Colors => {
+                let color = Color::parse(&key).map_err(error)?;
+                config.colors.add(color, value).map_err(error)?;
+            }
+            Section::Defaults => set_default(&mut config.defaults, &key, value).map_err(error)?,
+            Section::ProfileDefaults(index) => {
+                set_default(&mut config.profiles[index].defaults, &key, value).map_err(error)?
+            }
+            Section::None => {
+                return Err(error(
+                    "settings go under a section like `[macros]`".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(config)
+}
+
+fn check_preset(name: &str) -> Result<(), String> {
+    match crate::cli::preset::find(name) {
+        Some(_) => Ok(()),
+        None => Err(format!("unknown preset `{}`", name)),
+    }
+}
+
+fn set_default(defaults: &mut Defaults, key: &str, value: &str) -> Result<(), String> {
+    let flag = || match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("`{}` must be true or false", key)),
+    };
+    match key {
+        "verbose" => defaults.verbose = Some(flag()?),
+        "ladder" => defaults.ladder = Some(flag()?),
+        "history" => defaults.history = Some(flag()?),
+        "format" => {
+            defaults.format = Some(match value {
+                "text" => Format::Text,
+                "json" => Format::Json,
+                _ => return Err("`format` must be text or json".to_string()),
+            })
+        }
+        "ties" => {
+            defaults.ties = Some(match value {
+                "draw" => TieBreak::Draw,
+                "first" => TieBreak::First,
+                "second" => TieBreak::Second,
+                _ => return Err("`ties` must be draw, first or second".to_string()),
+            })
+        }
+        _ => return Err(format!("unknown setting `{}`", key)),
+    }
+    Ok(())
+}
+
+/// Rewrites the `[macros]` section of a config file to hold `macros`,
+/// keeping its comments and everything outside the section as it was.
+pub fn replace_macros(source: &str, macros: &Macros) -> String {
+    let definitions: Vec<String> = macros.iter().map(|m| m.to_string()).collect();
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_macros = false;
+    let mut written = false;
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            if in_macros {
+                insert_before_blanks(&mut lines, &definitions);
+                written = true;
+            }
+            in_macros = trimmed == "[macros]";
+        } else if in_macros && !trimmed.is_empty() && !trimmed.starts_with('#') {
+            continue;
+        }
+        lines.push(line.to_string());
+    }
+    if in_macros {
+        insert_before_blanks(&mut lines, &definitions);
+    } else if !written && !definitions.is_empty() {
+        if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push("[macros]".to_string());
+        lines.extend(definitions);
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Adds `definitions` at the end of the section being written, ahead of
+/// the blank lines that separate it from the next one.
+fn insert_before_blanks(lines: &mut Vec<String>, definitions: &[String]) {
+    let end = lines
+        .iter()
+        .rposition(|l| !l.trim().is_empty())
+        .map_or(0, |i| i + 1);
+    lines.splice(end..end, definitions.iter().cloned());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::roll::{Options, render_all};
+
+    const SOURCE: &str = r#"
+# Shared settings
+[macros]
+fireball = "8d6"
+gwf(n) = "nd6ra{1,2}"
+
+[defaults]
+verbose = true
+ties = "first"
+
+[notation]
+w = "d"
+
+[crits]
+when = "d20 = 20, d6 = 6"
+bell = true
+
+[presets]
+attack = "--crit 19 -v"
+sr = "--edge"
+
+[profile.fate]
+ladder = true
+verbose = false
+format = "json"
+
+[profile.fate.macros]
+fireball = "4df"
+
+[profile.fate.presets]
+attack = "--hitloc"
+"#;
+
+    #[test]
+    fn reads_every_section() {
+        let config = parse(SOURCE).unwrap();
+        assert_eq!(config.macros.len(), 2);
+        assert_eq!(config.macros.get("gwf").unwrap().params, ["n"]);
+        assert_eq!(config.defaults.verbose, Some(true));
+        assert_eq!(config.defaults.ties, Some(TieBreak::First));
+        assert_eq!(config.notation, [("w".to_string(), "d".to_string())]);
+        assert!(config.crits.bell);
+        assert_eq!(config.crits.find(&[(6, 5), (6, 6)]), Some((6, 6)));
+        assert_eq!(config.profiles.len(), 1);
+        assert_eq!(config.profiles[0].name, "fate");
+        assert_eq!(config.profiles[0].macros.len(), 1);
+    }
+
+    #[test]
+    fn profiles_layer_over_the_shared_settings() {
+        let config = parse(SOURCE).unwrap();
+        let fate = config.clone().activate(Some("fate")).unwrap();
+        assert_eq!(fate.defaults.verbose, Some(false));
+        assert_eq!(fate.defaults.ladder, Some(true));
+        assert_eq!(fate.defaults.ties, Some(TieBreak::First));
+        assert_eq!(fate.macros.get("fireball").unwrap().body, "4df");
+        assert!(fate.macros.get("gwf").is_some());
+        assert_eq!(fate.defaults.format, Some(Format::Json));
+        assert_eq!(fate.preset_args("attack"), ["--hitloc"]);
+        assert_eq!(fate.preset_args("sr"), ["--edge"]);
+        assert_eq!(config.defaults.format, None);
+        assert_eq!(config.preset_args("attack"), ["--crit", "19", "-v"]);
+        assert!(config.preset_args("bitd").is_empty());
+
+        assert_eq!(config.clone().activate(None), Ok(config.clone()));
+        assert_eq!(
+            config.activate(Some("dnd")),
+            Err("no profile named `dnd` in the config".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_errors_by_line() {
+        let error = |source: &str| parse(source).unwrap_err();
+        assert_eq!(error("[dice]"), "line 1: unknown section `[dice]`");
+        assert_eq!(
+            error("verbose = true"),
+            "line 1: settings go under a section like `[macros]`"
+        );
+        assert_eq!(
+            error("[defaults]\n\nverbose = yes"),
+            "line 3: `verbose` must be true or false"
+        );
+        assert_eq!(
+            error("[defaults]\nloud = true"),
+            "line 2: unknown setting `loud`"
+        );
+        assert_eq!(error("[notation]\nw2 = \"d\""), "line 2: bad notation `w2`");
+        assert_eq!(error("[crits]\nbell"), "line 2: expected `key = \"value\"`");
+        assert_eq!(
+            error("[profile.fate]\nties = \"coin\""),
+            "line 2: `ties` must be draw, first or second"
+        );
+        assert_eq!(
+            error("[profile.fate.presets]\nfate = \"+2\""),
+            "line 2: unknown preset `fate`"
+        );
+    }
+
+    #[test]
+    fn replacing_macros_keeps_the_rest_of_the_file() {
+        let mut macros = Macros::default();
+        macros.define(Macro::parse("sneak", "3d6").unwrap());
+
+        let source =
+            "# mine\n[macros]\n# spells\nfireball = \"8d6\"\n\n[defaults]\nverbose = true\n";
+        assert_eq!(
+            replace_macros(source, &macros),
+            "# mine\n[macros]\n# spells\nsneak = \"3d6\"\n\n[defaults]\nverbose = true\n"
+        );
+        assert_eq!(
+            replace_macros("[defaults]\nverbose = true", &macros),
+            "[defaults]\nverbose = true\n\n[macros]\nsneak = \"3d6\"\n"
+        );
+    }
+
+    #[test]
+    fn crits_only_see_the_dice_that_were_kept() {
+        let crits = parse("[crits]\nwhen = \"d20 = 20\"").unwrap().crits;
+        let ast = reroll::parser::parse("2d20kl1").unwrap();
+        let options = Options {
+            verbose: false,
+            ladder: false,
+            ties: TieBreak::Draw,
+        };
+        for seed in 0..200 {
+            fastrand::seed(seed);
+            let rendered = render_all(&ast, &options, true).remove(0);
+            let total: i32 = rendered.line.parse().unwrap();
+            // A dropped 20 is no crit: only the lower die counts.
+            assert_eq!(rendered.dice, [(20, total)]);
+            assert_eq!(crits.find(&rendered.dice).is_some(), total == 20);
+        }
+    }
+
+    #[test]
+    fn colors_judge_each_die_against_its_own_size() {
+        let colors = parse("[colors]\nbold = \"die = max\"").unwrap().colors;
+        let ast = reroll::parser::parse("1d2 + 1d1").unwrap();
+        let options = Options {
+            verbose: true,
+            ladder: false,
+            ties: TieBreak::Draw,
+        };
+        fastrand::seed(1);
+        let rendered = render_all(&ast, &options, true).remove(0);
+        let (sides, value) = rendered.dice[0];
+        assert_eq!(rendered.dice, [(2, value), (1, 1)]);
+        assert_eq!(rendered.total, Some(value + 1));
+        let first = if value == sides {
+            "\x1b[1m2\x1b[0m"
+        } else {
+            "1"
+        };
+        assert_eq!(
+            colors.paint(&rendered.line, rendered.total, &rendered.dice),
+            format!("[{}] + [\x1b[1m1\x1b[0m] = {}", first, value + 1)
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+
+use reroll::ast::Ast;
+use reroll::compile::Program;
+use reroll::distribution::exact;
+use reroll::optimize::fold_all;
+use reroll::parser::parse;
+
+const DEFAULT_SAMPLES: u64 = 100_000;
+
+/// `roll dist`: prints the chance of each total of an expression, or with
+/// `--plot`, draws it as an SVG chart. `--targets` adds the chance of
+/// reaching each of a list of totals.
+pub fn run(args: &[String]) -> i32 {
+    let mut samples = DEFAULT_SAMPLES;
+    let mut plot = None;
+    let mut targets = Vec::new();
+    let mut expr_parts = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" | "--samples" => match args.next().map(|s| s.parse::<u64>()) {
+                Some(Ok(n)) if n > 0 => samples = n,
+                _ => {
+                    eprintln!("{} requires a positive integer", arg);
+                    return 1;
+                }
+            },
+            "--plot" => match args.next() {
+                Some(path) => plot = Some(path),
+                None => {
+                    eprintln!("--plot requires a file");
+                    return 1;
+                }
+            },
+            "--targets" => {
+                let parsed: Option<Result<Vec<i32>, _>> = args
+                    .next()
+                    .map(|list| list.split(',').map(|t| t.trim().parse()).collect());
+                match parsed {
+                    Some(Ok(list)) => targets = list,
+                    _ => {
+                        eprintln!("--targets requires totals separated by commas, as in 20,25,30");
+                        return 1;
+                    }
+                }
+            }
+            _ => expr_parts.push(arg.to_lowercase()),
+        }
+    }
+
+    if expr_parts.is_empty() {
+        eprintln!(
+            "Usage: roll dist [-n <samples>] [--plot <file.svg>] [--targets <t,...>] <expr>\n\n\
+             Prints the chance of each total, exact for plain dice, arithmetic\n\
+             and functions, and simulated otherwise.\n\n\
+             Options:\n\
+             \t-n, --samples <n>   Rolls to simulate when there are no exact\n\
+             \t                    chances (default {})\n\
+             \t--plot <file.svg>   Draw the chances as an SVG chart instead\n\
+             \t--targets <t,...>   Also print the chance of a total of at least\n\
+             \t                    each of these",
+            DEFAULT_SAMPLES
+        );
+        return 1;
+    }
+    if let Some(path) = plot
+        && !path.ends_with(".svg")
+    {
+        eprintln!("{}: only SVG plots are supported", path);
+        return 1;
+    }
+
+    let mut ast = match parse(&expr_parts.join(" ")) {
+        Ok(ast) if ast.roots().len() == 1 => ast,
+        Ok(_) => {
+            eprintln!("roll dist takes a single expression");
+            return 1;
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    fold_all(&mut ast);
+    let (chances, exact) = chances(&ast, samples).unwrap();
+    let title = if exact {
+        ast.to_string()
+    } else {
+        format!("{} ({} samples)", ast, samples)
+    };
+
+    match plot {
+        Some(path) => {
+            if let Err(err) = fs::write(path, svg(&title, &chances)) {
+                eprintln!("{}: {}", path, err);
+                return 1;
+            }
+        }
+        None => {
+            println!("{}", title);
+            println!("{}", bar_chart(&chances, chances.len(), 40));
+        }
+    }
+    if !targets.is_empty() {
+        if plot.is_none() {
+            println!();
+        }
+        println!("at least  chance");
+        for target in targets {
+            let chance: f64 = chances
+                .iter()
+                .filter(|&&(total, _)| total >= target)
+                .map(|&(_, p)| p)
+                .sum();
+            println!("{:>8}  {:>5.1}%", target, chance * 100.0);
+        }
+    }
+    0
+}
+
+/// The chance of each total of a single expression, lowest first, and
+/// whether it is exact; otherwise it comes from `samples` rolls.
+pub fn chances(ast: &Ast, samples: u64) -> Option<(Vec<(i32, f64)>, bool)> {
+    let [root] = ast.roots() else {
+        return None;
+    };
+    if let Some(dist) = exact(ast, *root) {
+        return Some((dist.iter().collect(), true));
+    }
+    let program = Program::compile(ast, *root);
+    let mut counts = BTreeMap::new();
+    for _ in 0..samples {
+        *counts.entry(program.total()).or_insert(0u64) += 1;
+    }
+    let chances = counts
+        .into_iter()
+        .map(|(total, n)| (total, n as f64 / samples as f64))
+        .collect();
+    Some((chances, false))
+}
+
+/// Draws the chances as one bar per row, `width` characters at most,
+/// grouping neighbouring totals when there are more than `rows`.
+pub fn bar_chart(chances: &[(i32, f64)], rows: usize, width: usize) -> String {
+    let per_row = chances.len().div_ceil(rows.max(1)).max(1);
+    let groups: Vec<(String, f64)> = chances
+        .chunks(per_row)
+        .map(|chunk| {
+            let (first, last) = (chunk[0].0, chunk[chunk.len() - 1].0);
+            let label = if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            };
+            (label, chunk.iter().map(|&(_, p)| p).sum())
+        })
+        .collect();
+    let highest = groups.iter().map(|&(_, p)| p).fold(0.0, f64::max);
+    groups
+        .iter()
+        .map(|(label, p)| {
+            let bar = (p / highest * width as f64).round() as usize;
+            format!("{:>7} {:>5.1}% {}", label, p * 100.0, "█".repeat(bar))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// An SVG bar chart of the chances, with the expression as its title and
+/// labelled axes. Totals that cannot come up get no bar but keep their
+/// place on the axis.
+fn svg(title: &str, chances: &[(i32, f64)]) -> String {
+    const WIDTH: f64 = 640.0;
+    const HEIGHT: f64 = 400.0;
+    const LEFT: f64 = 60.0;
+    const RIGHT: f64 = 20.0;
+    const TOP: f64 = 40.0;
+    const BOTTOM: f64 = 50.0;
+
+    let (low, high) = (chances[0].0, chances[chances.len() - 1].0);
+    let totals = (high - low) as f64 + 1.0;
+    let highest = chances.iter().map(|&(_, p)| p).fold(0.0, f64::max);
+    let (plot_width, plot_height) = (WIDTH - LEFT - RIGHT, HEIGHT - TOP - BOTTOM);
+    let slot = plot_width / totals;
+    let x = |total: i32| LEFT + (total - low) as f64 * slot;
+    let y = |p: f64| TOP + plot_height * (1.0 - p / highest);
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\" font-size=\"12\">",
+        WIDTH, HEIGHT
+    );
+    let _ = writeln!(
+        out,
+        "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+        WIDTH, HEIGHT
+    );
+    let _ = writeln!(
+        out,
+        "<text x=\"{}\" y=\"24\" text-anchor=\"middle\" font-size=\"16\">{}</text>",
+        WIDTH / 2.0,
+        escape(title)
+    );
+
+    // Five ticks on the chance axis, with light grid lines.
+    for i in 0..=4 {
+        let p = highest * i as f64 / 4.0;
+        let _ = writeln!(
+            out,
+            "<line x1=\"{0}\" y1=\"{2:.1}\" x2=\"{1}\" y2=\"{2:.1}\" stroke=\"#ddd\"/>\n<text x=\"{3}\" y=\"{4:.1}\" text-anchor=\"end\">{5:.1}%</text>",
+            LEFT,
+            WIDTH - RIGHT,
+            y(p),
+            LEFT - 6.0,
+            y(p) + 4.0,
+            p * 100.0
+        );
+    }
+    for &(total, p) in chances {
+        let _ = writeln!(
+            out,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#4c72b0\"><title>{}: {:.2}%</title></rect>",
+            x(total) + slot * 0.1,
+            y(p),
+            slot * 0.8,
+            TOP + plot_height - y(p),
+            total,
+            p * 100.0
+        );
+    }
+    // Label every total when they fit, otherwise about twenty of them.
+    let step = (totals / 20.0).ceil().max(1.0) as i32;
+    for total in (low..=high).filter(|t| (t - low) % step == 0) {
+        let _ = writeln!(
+            out,
+            "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            x(total) + slot / 2.0,
+            TOP + plot_height + 16.0,
+            total
+        );
+    }
+    let _ = writeln!(
+        out,
+        "<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"black\"/>\n<line x1=\"{0}\" y1=\"{2}\" x2=\"{3}\" y2=\"{2}\" stroke=\"black\"/>",
+        LEFT,
+        TOP,
+        TOP + plot_height,
+        WIDTH - RIGHT
+    );
+    let _ = writeln!(
+        out,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">Total</text>",
+        LEFT + plot_width / 2.0,
+        HEIGHT - 12.0
+    );
+    let _ = writeln!(
+        out,
+        "<text x=\"16\" y=\"{0}\" text-anchor=\"middle\" transform=\"rotate(-90 16 {0})\">Chance</text>",
+        TOP + plot_height / 2.0
+    );
+    out.push_str("</svg>\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,109 @@
+use std::fs;
+
+use reroll::deck::{BUILTIN, Deck};
+use reroll::rng::label_stream;
+
+use crate::cli::state;
+
+/// `roll draw`: draws cards from a freshly shuffled deck, or with
+/// `--persist` from a deck that stays shuffled between invocations.
+pub fn run(args: &[String]) -> i32 {
+    let mut count = 1;
+    let mut deck_name = "standard52".to_string();
+    let mut persist = false;
+    let mut reset = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--deck" => match args.next() {
+                Some(name) => deck_name = name.clone(),
+                None => return usage(),
+            },
+            "--persist" => persist = true,
+            "--reset" => reset = true,
+            _ => match arg.parse::<usize>() {
+                Ok(n) if n > 0 => count = n,
+                _ => return usage(),
+            },
+        }
+    }
+
+    match draw(&deck_name, count, persist, reset) {
+        Ok(output) => {
+            println!("{}", output);
+            0
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn usage() -> i32 {
+    eprintln!(
+        "Usage: roll draw [<n>] [--deck <name|file>] [--persist] [--reset]\n\n\
+         Decks: {}, or a file with one card per line\n\n\
+         Options:\n\
+         \t--persist   Draw from a deck that stays shuffled between draws\n\
+         \t--reset     Shuffle a new persistent deck",
+        BUILTIN.join(", ")
+    );
+    1
+}
+
+fn draw(deck_name: &str, count: usize, persist: bool, reset: bool) -> Result<String, String> {
+    let fresh = || -> Result<Deck, String> {
+        let mut deck = match Deck::builtin(deck_name) {
+            Some(deck) => deck,
+            None => Deck::parse(
+                &fs::read_to_string(deck_name).map_err(|e| format!("{}: {}", deck_name, e))?,
+            ),
+        };
+        if deck.is_empty() {
+            return Err(format!("{}: the deck has no cards", deck_name));
+        }
+        deck.shuffle();
+        Ok(deck)
+    };
+
+    if !persist {
+        return Ok(fresh()?.draw(count).join(", "));
+    }
+
+    // Builtin decks are saved apart from decks from files, and those are
+    // keyed on a hash of their full path, so two `loot.txt` files in
+    // different directories keep their own decks.
+    let decks = state::data_dir()?.join("decks");
+    let (name, path) = if BUILTIN.contains(&deck_name) {
+        let name = deck_name.to_string();
+        let path = decks.join("builtin").join(format!("{}.txt", name));
+        (name, path)
+    } else {
+        let full = fs::canonicalize(deck_name).map_err(|e| format!("{}: {}", deck_name, e))?;
+        let name = full
+            .file_stem()
+            .map_or(deck_name.into(), |s| s.to_string_lossy())
+            .into_owned();
+        let hash = label_stream(&full.to_string_lossy());
+        let path = decks
+            .join("files")
+            .join(format!("{}-{:016x}.txt", name, hash));
+        (name, path)
+    };
+    let mut deck = match fs::read_to_string(&path) {
+        Ok(saved) if !reset => Deck::parse(&saved),
+        _ => fresh()?,
+    };
+
+    let drawn = deck.draw(count);
+    state::write(&path, &deck.to_text()).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if drawn.is_empty() {
+        return Err(format!(
+            "the {} deck is empty; use --reset to shuffle a new one",
+            name
+        ));
+    }
+    Ok(format!("{} ({} left)", drawn.join(", "), deck.len()))
+}
@@ -0,0 +1,41 @@
+use reroll::explain::{to_json, tree};
+use reroll::parser::parse;
+
+/// `roll explain`: prints how an expression parses, without rolling it.
+pub fn run(args: &[String]) -> i32 {
+    let mut json = false;
+    let mut expr_parts = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => expr_parts.push(arg.to_lowercase()),
+        }
+    }
+
+    if expr_parts.is_empty() {
+        eprintln!(
+            "Usage: roll explain [--json] <expr>\n\n\
+             Prints the parse tree of an expression, with each node's source\n\
+             range, without rolling it.\n\n\
+             Options:\n\
+             \t--json   Print the tree as JSON"
+        );
+        return 1;
+    }
+
+    match parse(&expr_parts.join(" ")) {
+        Ok(ast) if json => {
+            println!("{}", to_json(&ast));
+            0
+        }
+        Ok(ast) => {
+            print!("{}", tree(&ast));
+            0
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::Path;
+
+use reroll::eval::TieBreak;
+use reroll::macros::apply_notation;
+use reroll::optimize::fold_all;
+use reroll::parser::parse;
+
+use crate::cli::config::{self, Config};
+use crate::cli::roll::{Options, render};
+use crate::cli::state;
+
+/// `roll fill`: rolls every `{{expr}}` placeholder in a template, such as a
+/// stat block or a handout, and prints or writes the filled document.
+pub fn run(args: &[String]) -> i32 {
+    let mut seed = None;
+    let mut out = None;
+    let mut path = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--seed" => match iter.next().and_then(|s| s.parse::<u64>().ok()) {
+                Some(s) => seed = Some(s),
+                None => {
+                    eprintln!("--seed needs a number");
+                    return 1;
+                }
+            },
+            "-o" | "--out" => match iter.next() {
+                Some(file) => out = Some(file),
+                None => {
+                    eprintln!("{} needs a file", arg);
+                    return 1;
+                }
+            },
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("unexpected argument `{}`", arg);
+                return 1;
+            }
+        }
+    }
+
+    let Some(path) = path else {
+        eprintln!(
+            "Usage: roll fill [--seed <n>] [-o <file>] <template>\n\n\
+             Rolls each `{{{{expr}}}}` placeholder in a text file, e.g.\n\
+             `STR {{{{4d6k3}}}}`, and prints the filled document. Macros and\n\
+             notation from the config apply.\n\n\
+             Options:\n\
+             \t--seed <n>       Fill the template the same way every time\n\
+             \t-o, --out <file> Write the document to a file"
+        );
+        return 1;
+    };
+
+    if let Some(seed) = seed {
+        fastrand::seed(seed);
+    }
+    let filled = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|template| {
+            let config = config::load()?.activate(config::profile_name(None).as_deref())?;
+            let options = Options {
+                verbose: config.defaults.verbose.unwrap_or(false),
+                ladder: config.defaults.ladder.unwrap_or(false),
+                ties: config.defaults.ties.unwrap_or(TieBreak::Draw),
+            };
+            fill(&template, ("{{", "}}"), &config, &options)
+        });
+    let filled = match filled {
+        Ok(filled) => filled,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return 1;
+        }
+    };
+
+    match out {
+        Some(out) => match state::write(Path::new(out), &filled) {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("{}: {}", out, err);
+                1
+            }
+        },
+        None => {
+            print!("{}", filled);
+            0
+        }
+    }
+}
+
+/// Replaces each expression between the `(open, close)` delimiters in
+/// `template`, such as `{{3d6}}`, with its results as `roll` would print
+/// them, joined with commas. Errors name the placeholder's line.
+pub fn fill(
+    template: &str,
+    (open, close): (&str, &str),
+    config: &Config,
+    options: &Options,
+) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(open) {
+        out.push_str(&rest[..start]);
+        let offset = template.len() - rest.len() + start;
+        let line = template[..offset].matches('\n').count() + 1;
+        let error = |message: String| format!("line {}: {}", line, message);
+        let after = &rest[start + open.len()..];
+        let mut end = after
+            .find(close)
+            .ok_or_else(|| error(format!("unclosed `{}`", open)))?;
+        // `{{choose{a, b}}}` closes after its own brace.
+        while after[end + close.len()..].starts_with(&close[..1]) {
+            end += 1;
+        }
+        let input = apply_notation(&after[..end].trim().to_lowercase(), &config.notation);
+        let input = config.macros.expand(&input).map_err(error)?;
+        let mut ast = parse(&input).map_err(error)?;
+        fold_all(&mut ast);
+        out.push_str(&render(&ast, options).join(", "));
+        rest = &after[end + close.len()..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
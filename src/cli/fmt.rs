@@ -0,0 +1,45 @@
+use reroll::parser::parse;
+
+/// `roll fmt`: prints each argument in canonical form. Every argument is a
+/// single expression, so whitespace inside it is ignored.
+pub fn run(args: &[String]) -> i32 {
+    let mut check = false;
+    let mut inputs = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            _ => inputs.push(arg),
+        }
+    }
+
+    if inputs.is_empty() {
+        eprintln!(
+            "Usage: roll fmt [--check] <expr>...\n\n\
+             Options:\n\
+             \t--check   Exit with an error if any expression is not canonical"
+        );
+        return 1;
+    }
+
+    let mut status = 0;
+    for input in inputs {
+        let squeezed: String = input.to_lowercase().split_whitespace().collect();
+        let canonical = match parse(&squeezed) {
+            Ok(ast) => ast.to_string(),
+            Err(err) => {
+                eprintln!("{}", err);
+                status = 1;
+                continue;
+            }
+        };
+
+        if !check {
+            println!("{}", canonical);
+        } else if canonical != *input {
+            println!("{} -> {}", input, canonical);
+            status = 1;
+        }
+    }
+    status
+}
@@ -0,0 +1,100 @@
+use reroll::eval::TieBreak;
+use reroll::optimize::fold_all;
+use reroll::parser::parse;
+
+use crate::cli::log::Entry;
+use crate::cli::roll::{Options, render};
+
+/// `roll gen`: rolls the expressions once per seed and prints one line of
+/// JSON per seed, in the format of a session log. Seed 7 gives the same
+/// results as `roll --seed 7`, and the output can be checked again with
+/// `roll replay --verify`.
+pub fn run(args: &[String]) -> i32 {
+    let mut verbose = false;
+    let mut ladder = false;
+    let mut seeds = None;
+    let mut expr_parts = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            "--ladder" => ladder = true,
+            "--seeds" => match args.next().map(|s| parse_seeds(s)) {
+                Some(Ok(list)) => seeds = Some(list),
+                Some(Err(err)) => {
+                    eprintln!("--seeds: {}", err);
+                    return 1;
+                }
+                None => {
+                    eprintln!("--seeds requires a range such as 1..100");
+                    return 1;
+                }
+            },
+            _ => expr_parts.push(arg.to_lowercase()),
+        }
+    }
+
+    let Some(seeds) = seeds.filter(|_| !expr_parts.is_empty()) else {
+        eprintln!(
+            "Usage: roll gen --seeds <seeds> [-v] [--ladder] <expr>\n\n\
+             Rolls the expressions once for every seed and prints one JSON\n\
+             line per seed, in the format of `roll --log`.\n\n\
+             Seeds are a number, an inclusive range (1..100) or a comma\n\
+             separated list of either (1..10,42)."
+        );
+        return 1;
+    };
+
+    let mut ast = match parse(&expr_parts.join(" ")) {
+        Ok(ast) => ast,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    fold_all(&mut ast);
+
+    let options = Options {
+        verbose,
+        ladder,
+        ties: TieBreak::Draw,
+    };
+    let expression = ast.to_string();
+    let lines = seeds.into_iter().map(|seed| {
+        fastrand::seed(seed);
+        Entry {
+            seed,
+            user: None,
+            expression: expression.clone(),
+            options,
+            results: render(&ast, &options),
+        }
+        .to_json()
+    });
+    crate::cli::roll::finish(&lines.collect::<Vec<_>>())
+}
+
+/// Parses `1..100`, `7` or a comma separated list of both. Ranges are
+/// inclusive.
+fn parse_seeds(spec: &str) -> Result<Vec<u64>, String> {
+    let mut seeds = Vec::new();
+    for part in spec.split(',') {
+        let number = |s: &str| {
+            s.trim()
+                .parse::<u64>()
+                .map_err(|_| format!("bad seed `{}`", s.trim()))
+        };
+        match part.split_once("..") {
+            Some((low, high)) => {
+                let (low, high) = (number(low)?, number(high)?);
+                if low > high {
+                    return Err(format!("empty range `{}`", part));
+                }
+                seeds.extend(low..=high);
+            }
+            None => seeds.push(number(part)?),
+        }
+    }
+    Ok(seeds)
+}
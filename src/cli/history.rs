@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cli::log::{Reader, quote};
+use crate::cli::state;
+
+/// One roll in the history, stored as a line of JSON: `{"time":1760000000,
+/// "expression":"1d20 + 5","results":["17"],"dice":[[20,12]]}`, where each
+/// die is its number of sides and the face it showed. Rolls made `--as`
+/// someone add a `"user"` after the time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rolled {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub user: Option<String>,
+    pub expression: String,
+    pub results: Vec<String>,
+    pub dice: Vec<(i32, i32)>,
+}
+
+impl Rolled {
+    pub fn new(
+        user: Option<String>,
+        expression: String,
+        results: Vec<String>,
+        dice: Vec<(i32, i32)>,
+    ) -> Self {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Rolled {
+            time,
+            user,
+            expression,
+            results,
+            dice,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let results: Vec<String> = self.results.iter().map(|r| quote(r)).collect();
+        let dice: Vec<String> = self
+            .dice
+            .iter()
+            .map(|(sides, value)| format!("[{},{}]", sides, value))
+            .collect();
+        let user = match &self.user {
+            Some(user) => format!(",\"user\":{}", quote(user)),
+            None => String::new(),
+        };
+        format!(
+            "{{\"time\":{}{},\"expression\":{},\"results\":[{}],\"dice\":[{}]}}",
+            self.time,
+            user,
+            quote(&self.expression),
+            results.join(","),
+            dice.join(",")
+        )
+    }
+
+    pub fn parse(line: &str) -> Result<Rolled, String> {
+        let mut reader = Reader { rest: line.trim() };
+        let mut rolled = Rolled {
+            time: 0,
+            user: None,
+            expression: String::new(),
+            results: Vec::new(),
+            dice: Vec::new(),
+        };
+        reader.expect('{')?;
+        while !reader.eat('}') {
+            let key = reader.string()?;
+            reader.expect(':')?;
+            match key.as_str() {
+                "time" => rolled.time = reader.number()?,
+                "user" => rolled.user = Some(reader.string()?),
+                "expression" => rolled.expression = reader.string()?,
+                "results" => {
+                    reader.expect('[')?;
+                    while !reader.eat(']') {
+                        rolled.results.push(reader.string()?);
+                        reader.eat(',');
+                    }
+                }
+                "dice" => {
+                    reader.expect('[')?;
+                    while !reader.eat(']') {
+                        reader.expect('[')?;
+                        let sides = reader.number()? as i32;
+                        reader.expect(',')?;
+                        let value = reader.number()? as i32;
+                        reader.expect(']')?;
+                        rolled.dice.push((sides, value));
+                        reader.eat(',');
+                    }
+                }
+                _ => return Err(format!("unknown field `{}`", key)),
+            }
+            reader.eat(',');
+        }
+        Ok(rolled)
+    }
+}
+
+fn path() -> Result<PathBuf, String> {
+    Ok(state::data_dir()?.join("history.jsonl"))
+}
+
+/// Appends a roll to the history.
+pub fn record(rolled: &Rolled) -> Result<(), String> {
+    let path = path()?;
+    let append = || -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{}", rolled.to_json())
+    };
+    append().map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Every roll in the history, oldest first.
+pub fn load() -> Result<Vec<Rolled>, String> {
+    let path = path()?;
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("{}: {}", path.display(), err)),
+    };
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            Rolled::parse(line).map_err(|err| format!("{}:{}: {}", path.display(), i + 1, err))
+        })
+        .collect()
+}
+
+/// `roll history`: the rolls kept with `history = true` in the config.
+pub fn run(args: &[String]) -> i32 {
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => last(20),
+        ["--last", n] => match n.parse() {
+            Ok(n) => last(n),
+            Err(_) => return usage(),
+        },
+        ["--stats"] => stats(None),
+        ["--stats", die] => match die.strip_prefix('d').and_then(|s| s.parse().ok()) {
+            Some(sides) => stats(Some(sides)),
+            None => return usage(),
+        },
+        ["clear"] => path().and_then(|path| match fs::remove_file(&path) {
+            Ok(()) => Ok("history cleared".to_string()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+            Err(err) => Err(format!("{}: {}", path.display(), err)),
+        }),
+        _ => return usage(),
+    };
+    match result {
+        Ok(output) => {
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn usage() -> i32 {
+    eprintln!(
+        "Usage: roll history [--last <n>]\n       \
+         roll history --stats [d<sides>]\n       \
+         roll history clear\n\n\
+         Rolls are kept only with `history = true` under [defaults] in the\n\
+         config. --stats compares the average of each die with a fair one."
+    );
+    1
+}
+
+fn last(count: usize) -> Result<String, String> {
+    let history = load()?;
+    let lines: Vec<String> = history[history.len().saturating_sub(count)..]
+        .iter()
+        .map(|rolled| {
+            let line = format!("{}: {}", rolled.expression, rolled.results.join(", "));
+            match &rolled.user {
+                Some(user) => format!("[{}] {}", user, line),
+                None => line,
+            }
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+fn stats(only: Option<i32>) -> Result<String, String> {
+    let mut by_size: BTreeMap<i32, (u64, i64)> = BTreeMap::new();
+    for rolled in load()? {
+        for &(sides, value) in &rolled.dice {
+            if only.is_none_or(|only| only == sides) {
+                let (count, sum) = by_size.entry(sides).or_default();
+                *count += 1;
+                *sum += i64::from(value);
+            }
+        }
+    }
+    if by_size.is_empty() {
+        return Err("no dice in the history yet".to_string());
+    }
+    let lines: Vec<String> = by_size
+        .iter()
+        .map(|(sides, &(count, sum))| {
+            format!(
+                "d{}: {} rolled, average {:.2} (fair: {:.2})",
+                sides,
+                count,
+                sum as f64 / count as f64,
+                (f64::from(*sides) + 1.0) / 2.0
+            )
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolls_round_trip_through_json() {
+        let rolled = Rolled {
+            time: 1_760_000_000,
+            user: Some("sam \"the bold\"".to_string()),
+            expression: "1d20 + 5".to_string(),
+            results: vec!["17".to_string(), "a: [3, 4] = 7".to_string()],
+            dice: vec![(20, 12), (6, 3), (6, 4)],
+        };
+        let json = rolled.to_json();
+        assert_eq!(Rolled::parse(&json), Ok(rolled.clone()));
+
+        let anonymous = Rolled {
+            user: None,
+            dice: Vec::new(),
+            ..rolled
+        };
+        assert!(!anonymous.to_json().contains("user"));
+        assert_eq!(Rolled::parse(&anonymous.to_json()), Ok(anonymous));
+    }
+
+    #[test]
+    fn reads_the_documented_format() {
+        let rolled = Rolled::parse(
+            r#"{"time":1760000000, "expression":"1d20 + 5","results":["17"],"dice":[[20,12]]}"#,
+        )
+        .unwrap();
+        assert_eq!(rolled.time, 1_760_000_000);
+        assert_eq!(rolled.user, None);
+        assert_eq!(rolled.results, ["17"]);
+        assert_eq!(rolled.dice, [(20, 12)]);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(
+            Rolled::parse(r#"{"when":1}"#),
+            Err("unknown field `when`".to_string())
+        );
+        assert_eq!(
+            Rolled::parse(r#"{"dice":[[20 12]]}"#),
+            Err("expected `,`".to_string())
+        );
+        assert_eq!(Rolled::parse("[]"), Err("expected `{`".to_string()));
+        assert!(Rolled::parse(r#"{"expression":"1d20"#).is_err());
+    }
+}
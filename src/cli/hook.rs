@@ -0,0 +1,43 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Runs `command` through the shell for `--on-result`, writing `json` and a
+/// newline to its standard input. Its own output goes straight to ours.
+pub fn run(command: &str, json: &str) -> Result<(), String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let mut child = shell
+        .arg(command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("cannot run `{}`: {}", command, err))?;
+
+    // Dropped at the end of the block, closing the pipe so the command sees
+    // the end of its input.
+    if let Some(mut stdin) = child.stdin.take() {
+        match writeln!(stdin, "{}", json) {
+            // The command finished without reading its input; that is its call.
+            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+                let _ = child.wait();
+                return Err(format!("cannot write to `{}`: {}", command, err));
+            }
+            _ => {}
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|err| format!("`{}` did not finish: {}", command, err))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("`{}` failed ({})", command, status))
+    }
+}
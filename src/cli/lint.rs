@@ -0,0 +1,64 @@
+use std::fs;
+
+use reroll::lint::lint;
+
+/// `roll lint`: checks every expression in the files and reports problems
+/// as `path:line:column: severity: message`. Exits with an error if there
+/// are any.
+pub fn run(args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!(
+            "Usage: roll lint <file>...\n\n\
+             Checks one expression per line. Blank lines, `#` comments and\n\
+             script directives (`@seed`) are skipped, and in screen files\n\
+             (`name = \"expr\"`) the quoted expression is checked."
+        );
+        return 1;
+    }
+
+    let mut status = 0;
+    for path in args {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                status = 1;
+                continue;
+            }
+        };
+        for (i, line) in source.lines().enumerate() {
+            let Some((column, expression)) = expression(line) else {
+                continue;
+            };
+            for problem in lint(&expression.to_lowercase()) {
+                println!(
+                    "{}:{}:{}: {}: {}",
+                    path,
+                    i + 1,
+                    column + problem.span.start + 1,
+                    problem.severity,
+                    problem.message
+                );
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+/// Finds the expression on a line, and the offset it starts at.
+fn expression(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with(['#', '@', '[']) {
+        return None;
+    }
+    let start = line.len() - line.trim_start().len();
+    if let Some((_, value)) = trimmed.split_once('=')
+        && let Some(quoted) = value.trim().strip_prefix('"')
+        && let Some(expression) = quoted.strip_suffix('"')
+    {
+        let offset = line.find(quoted).unwrap();
+        return Some((offset, expression));
+    }
+    Some((start, trimmed))
+}
@@ -0,0 +1,383 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+use reroll::eval::TieBreak;
+
+use crate::cli::roll::Options;
+
+/// One invocation in a session log (`roll --log`), stored as a line of
+/// JSON: `{"seed":1,"expression":"1d20","verbose":false,"ladder":false,
+/// "ties":"draw","results":["17"]}`, with a `"user"` after the seed for
+/// rolls made `--as` someone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub seed: u64,
+    pub user: Option<String>,
+    /// The whole input in canonical form, labels included, as parsed
+    /// before folding so that replays can parse it again.
+    pub expression: String,
+    pub options: Options,
+    pub results: Vec<String>,
+}
+
+impl Entry {
+    pub fn to_json(&self) -> String {
+        let results: Vec<String> = self.results.iter().map(|r| quote(r)).collect();
+        let user = match &self.user {
+            Some(user) => format!(",\"user\":{}", quote(user)),
+            None => String::new(),
+        };
+        format!(
+            "{{\"seed\":{}{},\"expression\":{},\"verbose\":{},\"ladder\":{},\"ties\":{},\"results\":[{}]}}",
+            self.seed,
+            user,
+            quote(&self.expression),
+            self.options.verbose,
+            self.options.ladder,
+            quote(tie_name(self.options.ties)),
+            results.join(",")
+        )
+    }
+
+    pub fn append(&self, path: &Path) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", self.to_json())
+    }
+
+    /// Reads a line written by [`to_json`](Self::to_json), with its fields
+    /// in any order.
+    pub fn parse(line: &str) -> Result<Entry, String> {
+        let mut reader = Reader { rest: line.trim() };
+        let mut seed = None;
+        let mut user = None;
+        let mut expression = None;
+        let mut options = Options {
+            verbose: false,
+            ladder: false,
+            ties: TieBreak::Draw,
+        };
+        let mut results = Vec::new();
+
+        reader.expect('{')?;
+        while !reader.eat('}') {
+            let key = reader.string()?;
+            reader.expect(':')?;
+            match key.as_str() {
+                "seed" => seed = Some(reader.number()?),
+                "user" => user = Some(reader.string()?),
+                "expression" => expression = Some(reader.string()?),
+                "verbose" => options.verbose = reader.bool()?,
+                "ladder" => options.ladder = reader.bool()?,
+                "ties" => {
+                    options.ties = match reader.string()?.as_str() {
+                        "first" => TieBreak::First,
+                        "second" => TieBreak::Second,
+                        _ => TieBreak::Draw,
+                    }
+                }
+                "results" => {
+                    reader.expect('[')?;
+                    while !reader.eat(']') {
+                        results.push(reader.string()?);
+                        reader.eat(',');
+                    }
+                }
+                _ => return Err(format!("unknown field `{}`", key)),
+            }
+            reader.eat(',');
+        }
+
+        Ok(Entry {
+            seed: seed.ok_or("missing seed")?,
+            user,
+            expression: expression.ok_or("missing expression")?,
+            options,
+            results,
+        })
+    }
+}
+
+/// One rendered result, in the JSON that `--on-result` commands read:
+/// `{"seed":7,"user":"sam","label":"atk","comment":"vs goblin",
+/// "expression":"1d20 + 5","annotations":[],"targets":[15],"result":"17"}`.
+/// The seed, user, label and comment are `null` when there are none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub seed: Option<u64>,
+    /// Who rolled, from `--as`.
+    pub user: Option<String>,
+    pub label: Option<String>,
+    /// The text after `#` in the command line, if any.
+    pub comment: Option<String>,
+    /// This root's expression without its label, as `roll fmt` prints it.
+    pub expression: String,
+    /// The values read off the roll besides its total, as in `[sum, max]`.
+    pub annotations: Vec<String>,
+    /// The numbers the roll is checked against: a fixed opposing side
+    /// (`vs 15`) or tier thresholds (`vs 10/15/20`).
+    pub targets: Vec<i32>,
+    pub result: String,
+}
+
+impl Record {
+    pub fn to_json(&self) -> String {
+        let annotations: Vec<String> = self.annotations.iter().map(|a| quote(a)).collect();
+        let targets: Vec<String> = self.targets.iter().map(i32::to_string).collect();
+        format!(
+            "{{\"seed\":{},\"user\":{},\"label\":{},\"comment\":{},\"expression\":{},\"annotations\":[{}],\"targets\":[{}],\"result\":{}}}",
+            self.seed.map_or("null".to_string(), |s| s.to_string()),
+            self.user.as_deref().map_or("null".to_string(), quote),
+            self.label.as_deref().map_or("null".to_string(), quote),
+            self.comment.as_deref().map_or("null".to_string(), quote),
+            quote(&self.expression),
+            annotations.join(","),
+            targets.join(","),
+            quote(&self.result)
+        )
+    }
+
+    pub fn append(&self, path: &Path) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", self.to_json())
+    }
+}
+
+fn tie_name(ties: TieBreak) -> &'static str {
+    match ties {
+        TieBreak::Draw => "draw",
+        TieBreak::First => "first",
+        TieBreak::Second => "second",
+    }
+}
+
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c < ' ' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Just enough of a JSON reader for the lines [`Entry`] writes.
+pub struct Reader<'a> {
+    pub rest: &'a str,
+}
+
+impl Reader<'_> {
+    pub fn eat(&mut self, c: char) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!("expected `{}`", c))
+        }
+    }
+
+    pub fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(out);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => out.push('\n'),
+                    Some((_, 'r')) => out.push('\r'),
+                    Some((_, 't')) => out.push('\t'),
+                    Some((_, 'b')) => out.push('\u{8}'),
+                    Some((_, 'f')) => out.push('\u{c}'),
+                    Some((_, 'u')) => {
+                        let mut code = hex(&mut chars)?;
+                        // Outside the basic plane, JSON writes a surrogate pair.
+                        if (0xd800..0xdc00).contains(&code) {
+                            let low = match (chars.next(), chars.next()) {
+                                (Some((_, '\\')), Some((_, 'u'))) => hex(&mut chars)?,
+                                _ => return Err("unpaired surrogate".to_string()),
+                            };
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err("unpaired surrogate".to_string());
+                            }
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        out.push(char::from_u32(code).ok_or("unpaired surrogate")?);
+                    }
+                    Some((_, c)) => out.push(c),
+                    None => break,
+                },
+                _ => out.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    pub fn number(&mut self) -> Result<u64, String> {
+        self.rest = self.rest.trim_start();
+        let end = self
+            .rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.rest.len());
+        let (digits, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        digits.parse().map_err(|_| "expected a number".to_string())
+    }
+
+    pub fn bool(&mut self) -> Result<bool, String> {
+        self.rest = self.rest.trim_start();
+        for (word, value) in [("true", true), ("false", false)] {
+            if let Some(rest) = self.rest.strip_prefix(word) {
+                self.rest = rest;
+                return Ok(value);
+            }
+        }
+        Err("expected true or false".to_string())
+    }
+}
+
+/// Reads the four hex digits of a `\\u` escape.
+fn hex(chars: &mut std::str::CharIndices) -> Result<u32, String> {
+    let digits: String = chars.take(4).map(|(_, c)| c).collect();
+    match u32::from_str_radix(&digits, 16) {
+        Ok(code) if digits.len() == 4 => Ok(code),
+        _ => Err("expected four hex digits after `\\u`".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::roll::render;
+    use reroll::optimize::fold_all;
+    use reroll::parser::parse;
+
+    fn entry() -> Entry {
+        Entry {
+            seed: 42,
+            user: Some("sam".to_string()),
+            expression: "a: 1d20 + 5".to_string(),
+            options: Options {
+                verbose: true,
+                ladder: false,
+                ties: TieBreak::Second,
+            },
+            results: vec!["a: [12] + 5 = 17".to_string(), "say \"hi\"\n".to_string()],
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_through_json() {
+        let entry = entry();
+        assert_eq!(Entry::parse(&entry.to_json()), Ok(entry));
+
+        // Fields may come in any order, and options default when missing.
+        let parsed = Entry::parse(r#"{"results":["3"],"expression":"1d4","seed":7}"#).unwrap();
+        assert_eq!((parsed.seed, parsed.expression.as_str()), (7, "1d4"));
+        assert_eq!(parsed.options.ties, TieBreak::Draw);
+        assert!(!parsed.options.verbose);
+    }
+
+    #[test]
+    fn control_characters_round_trip_through_quote() {
+        let original = "tab\there\r\nbell\u{7} nul\u{0} esc\u{1b} \"é\" \\ 🎲";
+        let quoted = quote(original);
+        assert!(!quoted.chars().any(|c| c < ' '));
+        assert!(quoted.contains("\\u0007") && quoted.contains("\\t"));
+        assert_eq!(Reader { rest: &quoted }.string(), Ok(original.to_string()));
+
+        // Other writers may escape anything, pairing surrogates past U+FFFF.
+        let escaped = r#""\u00e9\ud83c\udfb2\/""#;
+        assert_eq!(Reader { rest: escaped }.string(), Ok("é🎲/".to_string()));
+        assert!(
+            Reader {
+                rest: r#""\ud83c""#
+            }
+            .string()
+            .is_err()
+        );
+        assert!(Reader { rest: r#""\u12""# }.string().is_err());
+    }
+
+    #[test]
+    fn rejects_incomplete_entries() {
+        assert_eq!(
+            Entry::parse(r#"{"expression":"1d4"}"#),
+            Err("missing seed".to_string())
+        );
+        assert_eq!(
+            Entry::parse(r#"{"seed":1}"#),
+            Err("missing expression".to_string())
+        );
+        assert_eq!(
+            Entry::parse(r#"{"seed":1,"verbose":yes}"#),
+            Err("expected true or false".to_string())
+        );
+        assert_eq!(
+            Entry::parse(r#"{"seed":1,"expression":"1d4"#),
+            Err("unterminated string".to_string())
+        );
+    }
+
+    #[test]
+    fn logged_entries_replay_to_their_results() {
+        // Folds to `1d20 + -4`, which would not parse back.
+        let mut ast = parse("a: 1d20+(1-5) 4d6kh3").unwrap();
+        let expression = ast.to_string();
+        fold_all(&mut ast);
+        let options = entry().options;
+        fastrand::seed(99);
+        let logged = Entry {
+            seed: 99,
+            user: None,
+            expression,
+            options,
+            results: render(&ast, &options),
+        };
+
+        let replayed = Entry::parse(&logged.to_json()).unwrap();
+        let mut ast = parse(&replayed.expression).unwrap();
+        assert_eq!(ast.to_string(), logged.expression);
+        fold_all(&mut ast);
+        fastrand::seed(replayed.seed);
+        assert_eq!(render(&ast, &replayed.options), logged.results);
+    }
+
+    #[test]
+    fn records_use_null_for_missing_fields() {
+        let record = Record {
+            seed: None,
+            user: None,
+            label: Some("atk".to_string()),
+            comment: None,
+            expression: "1d20 + 5 vs 15".to_string(),
+            annotations: Vec::new(),
+            targets: vec![15],
+            result: "17 vs 15: success".to_string(),
+        };
+        assert_eq!(
+            record.to_json(),
+            r#"{"seed":null,"user":null,"label":"atk","comment":null,"expression":"1d20 + 5 vs 15","annotations":[],"targets":[15],"result":"17 vs 15: success"}"#
+        );
+    }
+}
@@ -0,0 +1,27 @@
+pub mod aliases;
+pub mod bag;
+pub mod calibrate;
+pub mod character;
+pub mod config;
+pub mod dist;
+pub mod draw;
+pub mod explain;
+pub mod fill;
+pub mod fmt;
+pub mod generate;
+pub mod history;
+pub mod hook;
+pub mod lint;
+pub mod log;
+pub mod preset;
+pub mod progress;
+pub mod replay;
+pub mod roll;
+pub mod screen;
+pub mod script;
+pub mod sim;
+pub mod state;
+pub mod table;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod wizard;
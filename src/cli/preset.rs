@@ -0,0 +1,1083 @@
+use reroll::lint::MAX_POOL;
+use reroll::parser::parse;
+use reroll::presets::{
+    blades, burning_wheel, cortex, cthulhu, cyberpunk, dcc, dnd, genesys, gurps, hero,
+    hit_location, ironsworn, pbta, risk, savage_worlds, scatter, shadowrun, wfrp, year_zero,
+};
+
+use crate::cli::config::{self, profile_name};
+
+/// A game preset subcommand: takes its arguments and returns the text to
+/// print, or a usage error.
+type Preset = fn(&[String]) -> Result<String, String>;
+
+/// Name, arguments for the help text, and handler of every preset.
+const PRESETS: &[(&str, &str, Preset)] = &[
+    (
+        "attack",
+        "<bonus> vs <ac> [dmg <expr>] [--crit <n>] [--hitloc]",
+        attack,
+    ),
+    ("bitd", "<pool>", bitd),
+    ("bw", "<n>{b,g,w} [ob] <obstacle> [--open]", bw),
+    ("coc", "<skill> [+<n>b | +<n>p]", coc),
+    ("cortex", "<die>...", cortex),
+    ("cpr", "<modifier>", cpr),
+    ("dcc", "<die> [<±n>step] [<modifier>]", dcc),
+    (
+        "dpr",
+        "--to-hit <n> --ac <n> --damage <expr> [--crit <n>-20]",
+        dpr,
+    ),
+    ("gen-array", "[--points <n>]", gen_array),
+    ("genesys", "<n>{a,p,b,d,c,s}...", genesys),
+    ("group", "<members> <check> --dc <n>", group),
+    ("gurps", "<skill>", gurps),
+    ("hero", "<n>d6", hero),
+    ("hitloc", "[<table>]", hitloc),
+    ("hp", "d<sides> level <n> [con <±n>] [--average]", hp),
+    ("init", "\"<name>[ x<n>]: <modifier>\"...", init),
+    ("ironsworn", "<stat>", ironsworn),
+    ("pbta", "<stat> [--advanced]", pbta),
+    ("scatter", "[<distance>]", scatter),
+    ("sr", "<pool> [--edge]", sr),
+    ("stats5e", "[--min <total>] [--compare]", stats5e),
+    ("sw", "<die> [tn]", sw),
+    (
+        "validate-array",
+        "<score>... [--method pointbuy|standard] [--points <n>]",
+        validate_array,
+    ),
+    ("vs", "<n>d<sides> <n>d<sides>", vs),
+    ("wfrp", "<target> [--reverse]", wfrp),
+    ("yz", "[<n>b] [<n>s] [<n>g] [--push]", yz),
+];
+
+pub fn find(name: &str) -> Option<Preset> {
+    PRESETS
+        .iter()
+        .find(|(preset, _, _)| *preset == name)
+        .map(|(_, _, run)| *run)
+}
+
+/// The presets section of `roll --help`.
+pub fn help() -> String {
+    let mut help = String::from("Presets (add -v to show the dice):");
+    for (name, args, _) in PRESETS {
+        help.push_str(&format!("\n\troll {} {}", name, args));
+    }
+    help
+}
+
+/// Runs the preset `name` after the arguments the config gives it, from
+/// the profile chosen with `--profile` or `$REROLL_PROFILE` if any.
+pub fn run(name: &str, preset: Preset, args: &[String]) -> i32 {
+    let mut profile = None;
+    let mut given = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile" => match args.next() {
+                Some(name) => profile = Some(name.as_str()),
+                None => {
+                    eprintln!("--profile requires a profile name");
+                    return 1;
+                }
+            },
+            _ => given.push(arg.clone()),
+        }
+    }
+    let config =
+        config::load().and_then(|config| config.activate(profile_name(profile).as_deref()));
+    let mut args = match config {
+        Ok(config) => config.preset_args(name),
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    args.extend(given);
+    match preset(&args) {
+        Ok(output) => {
+            println!("{}", output);
+            0
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn format_dice(dice: &[i32]) -> String {
+    format!("{:?}", dice)
+}
+
+/// Refuses d20 bonuses too large to add to a natural 20.
+fn d20_bonus(bonus: i32) -> Result<i32, String> {
+    match bonus.checked_add(20) {
+        Some(_) => Ok(bonus),
+        None => Err(format!("a bonus of {} is too large to add to a d20", bonus)),
+    }
+}
+
+/// The most dice `roll genesys --stats` works out exact odds for.
+const MAX_STATS_POOL: u32 = 1000;
+
+/// Refuses pools of more than [`MAX_POOL`] dice, which would take too long
+/// to roll, the same limit expressions have.
+fn pool_size(count: u32) -> Result<u32, String> {
+    if count > MAX_POOL as u32 {
+        Err(format!(
+            "cannot roll {} dice, more than {}",
+            count, MAX_POOL
+        ))
+    } else {
+        Ok(count)
+    }
+}
+
+/// `roll attack <bonus> vs <ac> [dmg <expr>] [--crit <n>] [-v]`, e.g.
+/// `roll attack +7 vs 16 dmg 1d8+4`
+fn attack(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll attack <bonus> vs <ac> [dmg <expr>] [--crit <n>] [--hitloc] [-v]\n\n\
+                 --crit sets the lowest natural roll that crits (default 20)\n\
+                 --hitloc rolls where a hit lands (see `roll hitloc`)";
+    let mut bonus = None;
+    let mut ac = None;
+    let mut damage = None;
+    let mut crit_range = 20;
+    let mut hit_locations = false;
+    let mut verbose = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_lowercase().as_str() {
+            "-v" | "--verbose" => verbose = true,
+            "--hitloc" => hit_locations = true,
+            "vs" => {
+                ac = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<i32>().ok())
+                        .ok_or(usage)?,
+                )
+            }
+            "dmg" => {
+                let expr = args.next().ok_or(usage)?;
+                damage = Some(dnd::Damage::parse(&expr.to_lowercase())?);
+            }
+            "--crit" => {
+                crit_range = args
+                    .next()
+                    .and_then(|n| n.parse::<i32>().ok())
+                    .filter(|n| (2..=20).contains(n))
+                    .ok_or(usage)?
+            }
+            _ => bonus = Some(arg.parse::<i32>().map_err(|_| usage)?),
+        }
+    }
+    let (Some(bonus), Some(ac)) = (bonus, ac) else {
+        return Err(usage.to_string());
+    };
+    let attack = dnd::roll_attack(d20_bonus(bonus)?, ac, crit_range, damage.as_ref());
+    let mut output = attack.to_string();
+    if hit_locations && attack.outcome() != dnd::Outcome::Miss {
+        output.push_str(&format!(" to the {}", hit_location::humanoid().roll().1));
+    }
+    Ok(if verbose {
+        format!("{} {:+}\n{}", format_dice(&[attack.d20]), bonus, output)
+    } else {
+        output
+    })
+}
+
+/// `roll bitd <pool> [-v]`
+fn bitd(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll bitd <pool> [-v]";
+    let mut pool = None;
+    let mut verbose = false;
+    for arg in args {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            _ => pool = Some(arg.parse::<u32>().map_err(|_| usage)?),
+        }
+    }
+    let roll = blades::roll_pool(pool_size(pool.ok_or(usage)?)?);
+    Ok(if verbose {
+        format!("{}\n{}", format_dice(&roll.dice), roll)
+    } else {
+        roll.to_string()
+    })
+}
+
+/// `roll bw <n><shade> [obstacle] <ob> [--open] [-v]`, e.g.
+/// `roll bw 6B obstacle 3`
+fn bw(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll bw <n>{b,g,w} [ob] <obstacle> [--open] [-v]";
+    let mut pool = None;
+    let mut obstacle = None;
+    let mut open_ended = false;
+    let mut verbose = false;
+    for arg in args {
+        let arg = arg.to_lowercase();
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            "-o" | "--open" => open_ended = true,
+            "ob" | "obstacle" => {}
+            _ if pool.is_none() => {
+                let letter = arg.chars().last().ok_or(usage)?;
+                let shade = burning_wheel::Shade::from_letter(letter).ok_or(usage)?;
+                let count = arg[..arg.len() - letter.len_utf8()]
+                    .parse::<u32>()
+                    .map_err(|_| usage)?;
+                pool = Some((pool_size(count)?, shade));
+            }
+            _ => {
+                let ob = arg.parse::<i32>().ok().filter(|&ob| ob >= 0);
+                obstacle = Some(ob.ok_or(usage)?);
+            }
+        }
+    }
+    let (count, shade) = pool.ok_or(usage)?;
+    let test = burning_wheel::roll_test(count, shade, obstacle.ok_or(usage)?, open_ended);
+    Ok(if verbose {
+        format!("{}\n{}", format_dice(&test.dice), test)
+    } else {
+        test.to_string()
+    })
+}
+
+/// `roll coc <skill> [+Nb | +Np] [-v]`, e.g. `roll coc 65 +1b`
+fn coc(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll coc <skill> [+<n>b | +<n>p] [-v]";
+    let mut skill = None;
+    let mut bonus = 0;
+    let mut verbose = false;
+    for arg in args {
+        let arg = arg.to_lowercase();
+        let dice = |suffix| {
+            arg.trim_start_matches('+').strip_suffix(suffix).map(|n| {
+                if n.is_empty() {
+                    Ok(1)
+                } else {
+                    n.parse::<i32>()
+                }
+            })
+        };
+        if arg == "-v" || arg == "--verbose" {
+            verbose = true;
+        } else if let Some(n) = dice('b') {
+            bonus += i64::from(n.map_err(|_| usage)?);
+        } else if let Some(n) = dice('p') {
+            bonus -= i64::from(n.map_err(|_| usage)?);
+        } else {
+            skill = Some(arg.parse::<i32>().map_err(|_| usage)?);
+        }
+    }
+    if bonus.unsigned_abs() > MAX_POOL as u64 {
+        return Err(format!(
+            "cannot roll {} bonus or penalty dice, more than {}",
+            bonus.unsigned_abs(),
+            MAX_POOL
+        ));
+    }
+    let bonus = bonus as i32;
+    let roll = cthulhu::roll_against(skill.ok_or(usage)?, bonus);
+    Ok(if verbose {
+        let tens: Vec<i32> = roll.tens.iter().map(|t| t * 10).collect();
+        format!("tens {} units {}\n{}", format_dice(&tens), roll.units, roll)
+    } else {
+        roll.to_string()
+    })
+}
+
+/// `roll cortex <die>... [-v]`, e.g. `roll cortex d8 d8 d6 d10`
+fn cortex(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll cortex <die>... [-v]";
+    let mut sides = Vec::new();
+    let mut verbose = false;
+    for arg in args {
+        match arg.to_lowercase().as_str() {
+            "-v" | "--verbose" => verbose = true,
+            die => sides.push(
+                die.strip_prefix('d')
+                    .and_then(|s| s.parse::<i32>().ok())
+                    .filter(|&s| s > 1)
+                    .ok_or(usage)?,
+            ),
+        }
+    }
+    if sides.is_empty() {
+        return Err(usage.to_string());
+    }
+    let roll = cortex::roll_pool(&sides);
+    Ok(if verbose {
+        let dice: Vec<String> = roll
+            .dice
+            .iter()
+            .map(|d| format!("d{}:{}", d.sides, d.value))
+            .collect();
+        format!("{}\n{}", dice.join(" "), roll)
+    } else {
+        roll.to_string()
+    })
+}
+
+/// `roll cpr <modifier> [-v]`, e.g. `roll cpr +14`
+fn cpr(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll cpr <modifier> [-v]";
+    let mut modifier = 0;
+    let mut verbose = false;
+    for arg in args {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            _ => modifier = arg.parse::<i32>().map_err(|_| usage)?,
+        }
+    }
+    let roll = cyberpunk::roll_check(modifier);
+    Ok(if verbose {
+        let dice: Vec<i32> = std::iter::once(roll.die).chain(roll.critical).collect();
+        format!("{} {:+}\n{}", format_dice(&dice), roll.modifier, roll)
+    } else {
+        roll.to_string()
+    })
+}
+
+/// `roll dcc <die> [<±n>step] [<modifier>]`, e.g. `roll dcc d20 -1step +2`
+fn dcc(args: &[String]) -> Result<String, String> {
+    let usage = format!(
+        "Usage: roll dcc <die> [<±n>step] [<modifier>]\n\nDice chain: {}",
+        dcc::DICE_CHAIN.map(|s| format!("d{}", s)).join(", ")
+    );
+    let mut sides = None;
+    let mut steps = 0;
+    let mut modifier = 0;
+    for arg in args {
+        let arg = arg.to_lowercase();
+        if let Some(n) = arg.strip_suffix("steps").or(arg.strip_suffix("step")) {
+            steps += n.parse::<i32>().map_err(|_| usage.clone())?;
+        } else if let Some(n) = arg.strip_prefix('d') {
+            sides = Some(n.parse::<i32>().map_err(|_| usage.clone())?);
+        } else if arg != "-v" && arg != "--verbose" {
+            modifier += arg.parse::<i32>().map_err(|_| usage.clone())?;
+        }
+    }
+    let sides = sides.ok_or(usage.clone())?;
+    let (die, roll) = dcc::roll_stepped(sides, steps)
+        .ok_or_else(|| format!("d{} is not on the dice chain\n\n{}", sides, usage))?;
+    Ok(match modifier {
+        0 => format!("d{}: {}", die, roll),
+        _ => format!("d{}{:+}: {}", die, modifier, roll + modifier),
+    })
+}
+
+/// `roll dpr --to-hit <n> --ac <n> --damage <expr> [--crit <n>-20]`, e.g.
+/// `roll dpr --to-hit +7 --ac 16 --damage 1d8+4 --crit 19-20`
+fn dpr(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll dpr --to-hit <n> --ac <n> --damage <expr> [--crit <n>-20]";
+    let number = |arg: Option<&String>| arg.and_then(|n| n.parse::<i32>().ok()).ok_or(usage);
+    let mut bonus = None;
+    let mut ac = None;
+    let mut damage = None;
+    let mut crit_range = 20;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to-hit" => bonus = Some(number(args.next())?),
+            "--ac" => ac = Some(number(args.next())?),
+            "--damage" => {
+                let expr = args.next().ok_or(usage)?;
+                damage = Some(dnd::Damage::parse(&expr.to_lowercase())?);
+            }
+            "--crit" => {
+                let range = args.next().ok_or(usage)?;
+                let low = range.strip_suffix("-20").unwrap_or(range);
+                crit_range = low
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|n| (2..=20).contains(n))
+                    .ok_or(usage)?;
+            }
+            "-v" | "--verbose" => {}
+            _ => return Err(usage.to_string()),
+        }
+    }
+    let (Some(bonus), Some(ac), Some(damage)) = (bonus, ac, damage) else {
+        return Err(usage.to_string());
+    };
+    let bonus = d20_bonus(bonus)?;
+    let mut output = format!("{:<13}{:>7}{:>7}{:>8}", "", "hit", "crit", "damage");
+    for (name, mode) in [
+        ("normal", dnd::RollMode::Normal),
+        ("advantage", dnd::RollMode::Advantage),
+        ("disadvantage", dnd::RollMode::Disadvantage),
+    ] {
+        let odds = dnd::attack_odds(bonus, ac, crit_range, mode);
+        output.push_str(&format!(
+            "\n{:<13}{:>6.1}%{:>6.1}%{:>8.2}",
+            name,
+            odds.hit * 100.0,
+            odds.crit * 100.0,
+            odds.expected_damage(&damage)
+        ));
+    }
+    Ok(output)
+}
+
+/// `roll genesys <n><die>... [-v] [--stats]`, e.g. `roll genesys 2a 1p 2d 1c`
+fn genesys(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll genesys [<n>a] [<n>p] [<n>b] [<n>d] [<n>c] [<n>s] [-v] [--stats]\n\n\
+                 a: ability, p: proficiency, b: boost, d: difficulty, c: challenge, s: setback\n\
+                 --stats prints the average symbols of the pool instead of rolling it";
+    let mut pool = Vec::new();
+    let mut verbose = false;
+    let mut stats = false;
+    for arg in args {
+        let arg = arg.to_lowercase();
+        if arg == "-v" || arg == "--verbose" {
+            verbose = true;
+            continue;
+        }
+        if arg == "--stats" {
+            stats = true;
+            continue;
+        }
+        let letter = arg.chars().last().ok_or(usage)?;
+        let die = genesys::Die::from_letter(letter).ok_or(usage)?;
+        let count = match &arg[..arg.len() - letter.len_utf8()] {
+            "" => 1,
+            count => count.parse::<u32>().map_err(|_| usage)?,
+        };
+        pool.push((die, count));
+    }
+    if pool.is_empty() {
+        return Err(usage.to_string());
+    }
+    let total = pool
+        .iter()
+        .map(|&(_, count)| count)
+        .fold(0, u32::saturating_add);
+    pool_size(total)?;
+    if stats {
+        // The odds to pass are worked out die by die, in quadratic time.
+        if total > MAX_STATS_POOL {
+            return Err(format!(
+                "cannot work out the odds of {} dice, more than {}",
+                total, MAX_STATS_POOL
+            ));
+        }
+        return Ok(genesys_stats(&pool));
+    }
+    let roll = genesys::roll_pool(&pool);
+    Ok(if verbose {
+        let faces: Vec<String> = roll
+            .dice
+            .iter()
+            .map(|(die, face)| format!("{}:{}", die.name(), face))
+            .collect();
+        format!("{}\n{}", faces.join(" "), roll)
+    } else {
+        roll.to_string()
+    })
+}
+
+/// The average symbols of a narrative pool, per kind of die and overall,
+/// and its chance to pass.
+fn genesys_stats(pool: &[(genesys::Die, u32)]) -> String {
+    let (per_die, total) = genesys::Expected::of_pool(pool);
+    let row = |name: String, e: &genesys::Expected| {
+        format!(
+            "{:<15} {:>7.2} {:>9.2} {:>7.2} {:>7.2} {:>6.2} {:>7.2}",
+            name, e.success, e.advantage, e.triumph, e.failure, e.threat, e.despair
+        )
+    };
+    let mut lines = vec![format!(
+        "{:<15} {:>7} {:>9} {:>7} {:>7} {:>6} {:>7}",
+        "", "success", "advantage", "triumph", "failure", "threat", "despair"
+    )];
+    for (&(die, count), expected) in pool.iter().zip(&per_die) {
+        lines.push(row(format!("{} {}", count, die.name()), expected));
+    }
+    lines.push(row("overall".to_string(), &total));
+    lines.push(format!(
+        "net successes {:+.2}, net advantage {:+.2}, {:.1}% to pass",
+        total.success - total.failure,
+        total.advantage - total.threat,
+        genesys::chance_to_pass(pool) * 100.0
+    ));
+    lines.join("\n")
+}
+
+/// `roll group <members> <check> --dc <n>`, e.g. `roll group 5 1d20+2 --dc 15`
+fn group(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll group <members> <check> --dc <n>";
+    let mut members = None;
+    let mut check = None;
+    let mut dc = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dc" => {
+                dc = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<i32>().ok())
+                        .ok_or(usage)?,
+                )
+            }
+            "-v" | "--verbose" => {}
+            _ if members.is_none() => members = Some(arg.parse::<u32>().map_err(|_| usage)?),
+            _ => {
+                let ast = parse(&arg.to_lowercase())?;
+                if ast.roots().len() != 1 {
+                    return Err(usage.to_string());
+                }
+                check = Some(ast);
+            }
+        }
+    }
+    let (Some(members), Some(check), Some(dc)) = (members.filter(|&n| n > 0), check, dc) else {
+        return Err(usage.to_string());
+    };
+    let result = dnd::group_check(&check, pool_size(members)?, dc);
+    let totals: Vec<String> = result.totals.iter().map(|t| t.to_string()).collect();
+    Ok(format!("{}\n{}", totals.join(", "), result))
+}
+
+/// `roll gurps <skill> [-v]`
+fn gurps(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll gurps <skill> [-v]";
+    let mut skill = None;
+    let mut verbose = false;
+    for arg in args {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            _ => skill = Some(arg.parse::<i32>().map_err(|_| usage)?),
+        }
+    }
+    let roll = gurps::roll_against(skill.ok_or(usage)?);
+    Ok(if verbose {
+        format!("{}\n{}", format_dice(&roll.dice), roll)
+    } else {
+        roll.to_string()
+    })
+}
+
+/// `roll hero <n>d6 [-v]`, e.g. `roll hero 6d6`
+fn hero(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll hero <n>d6 [-v]";
+    let mut dice = None;
+    let mut verbose = false;
+    for arg in args {
+        match arg.to_lowercase().as_str() {
+            "-v" | "--verbose" => verbose = true,
+            pool => {
+                let count = pool.strip_suffix("d6").unwrap_or(pool);
+                dice = Some(count.parse::<u32>().map_err(|_| usage)?);
+            }
+        }
+    }
+    let damage = hero::roll_damage(pool_size(dice.ok_or(usage)?)?);
+    Ok(if verbose {
+        format!("{}\n{}", format_dice(&damage.dice), damage)
+    } else {
+        damage.to_string()
+    })
+}
+
+/// `roll hitloc [<table>] [-v]`: rolls on a hit-location table file, or the
+/// humanoid d20 table.
+fn hitloc(args: &[String]) -> Result<String, String> {
+    let usage = format!(
+        "Usage: roll hitloc [<table>] [-v]\n\n\
+         Without a table file (see `roll table`), rolls d20 on:\n{}",
+        hit_location::HUMANOID
+    );
+    let mut table = None;
+    let mut verbose = false;
+    for arg in args {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            _ if table.is_none() => {
+                let path = std::path::Path::new(arg);
+                table = Some(crate::cli::table::load(path).map_err(|e| format!("{}: {}", arg, e))?);
+            }
+            _ => return Err(usage),
+        }
+    }
+    let table = table.unwrap_or_else(hit_location::humanoid);
+    let (value, location) = table.roll();
+    Ok(if verbose {
+        format!("d{}: {}\n{}", table.sides(), value, location)
+    } else {
+        location.to_string()
+    })
+}
+
+/// `roll hp d<sides> level <n> [con <±n>] [--average] [-v]`, e.g.
+/// `roll hp d10 level 7 con +3`
+fn hp(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll hp d<sides> level <n> [con <±n>] [--average] [-v]";
+    let number = |arg: Option<&String>| arg.and_then(|n| n.parse::<i32>().ok()).ok_or(usage);
+    let mut sides = None;
+    let mut level = None;
+    let mut con = 0;
+    let mut average = false;
+    let mut verbose = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_lowercase().as_str() {
+            "-v" | "--verbose" => verbose = true,
+            "--average" => average = true,
+            "level" | "lvl" => level = Some(number(args.next())?),
+            "con" => con = number(args.next())?,
+            die => {
+                let die = die.strip_prefix('d').ok_or(usage)?;
+                sides = Some(die.parse::<i32>().ok().filter(|&s| s > 0).ok_or(usage)?);
+            }
+        }
+    }
+    let (Some(sides), Some(level)) = (sides, level.filter(|&l| l > 0)) else {
+        return Err(usage.to_string());
+    };
+    let hp = dnd::hit_points(sides, level as u32, con, average)?;
+    Ok(if verbose {
+        let dice: Vec<i32> = std::iter::once(sides)
+            .chain(hp.gains.iter().copied())
+            .collect();
+        format!("{} {:+} per level\n{}", format_dice(&dice), con, hp)
+    } else {
+        hp.to_string()
+    })
+}
+
+/// `roll init "<name>[ x<n>]: <modifier>"...`, e.g.
+/// `roll init "Goblin x4: +2" "Fighter: +5"`
+fn init(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll init \"<name>[ x<n>]: <modifier>\"...\n\n\
+                 e.g. roll init \"Goblin x4: +2\" \"Fighter: +5\" \"Wizard: +2\"";
+    let mut combatants = Vec::new();
+    for arg in args.iter().filter(|a| *a != "-v" && *a != "--verbose") {
+        let (name, modifier) = arg.rsplit_once(':').unwrap_or((arg, "0"));
+        let modifier = modifier.trim().parse::<i32>().map_err(|_| usage)?;
+        let name = name.trim();
+        let copies = name
+            .rsplit_once(" x")
+            .and_then(|(base, n)| Some((base.trim(), n.parse::<u32>().ok()?)));
+        match copies {
+            Some((_, 0)) => return Err(format!("`{}` has no one to roll for", name)),
+            Some((base, n)) if n > 1 => {
+                let n = pool_size(n)?;
+                combatants.extend((1..=n).map(|i| (format!("{} {}", base, i), modifier)))
+            }
+            Some((base, _)) => combatants.push((base.to_string(), modifier)),
+            None if name.is_empty() => return Err(usage.to_string()),
+            None => combatants.push((name.to_string(), modifier)),
+        }
+    }
+    if combatants.is_empty() {
+        return Err(usage.to_string());
+    }
+    pool_size(u32::try_from(combatants.len()).unwrap_or(u32::MAX))?;
+    let order = dnd::roll_initiative(&combatants);
+    let lines: Vec<String> = order
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{}. {} {} ({:+})", i + 1, c.name, c.total(), c.modifier))
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+/// `roll ironsworn <stat>`, e.g. `roll ironsworn +2`. The dice are always
+/// shown, so `-v` changes nothing.
+fn ironsworn(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll ironsworn <stat>";
+    let mut stat = 0;
+    for arg in args {
+        match arg.as_str() {
+            "-v" | "--verbose" => {}
+            _ => stat = arg.parse::<i32>().map_err(|_| usage)?,
+        }
+    }
+    Ok(ironsworn::roll_action(stat).to_string())
+}
+
+/// `roll pbta <stat> [--advanced] [-v]`, e.g. `roll pbta +2`
+fn pbta(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll pbta <stat> [--advanced] [-v]";
+    let mut stat = 0;
+    let mut advanced = false;
+    let mut verbose = false;
+    for arg in args {
+        match arg.as_str() {
+            "-a" | "--advanced" => advanced = true,
+            "-v" | "--verbose" => verbose = true,
+            _ => stat = arg.parse::<i32>().map_err(|_| usage)?,
+        }
+    }
+    let roll = pbta::roll_move(stat, advanced);
+    Ok(if verbose {
+        format!("{} {:+}\n{}", format_dice(&roll.dice), roll.stat, roll)
+    } else {
+        roll.to_string()
+    })
+}
+
+/// `roll scatter [<distance>] [-v]`, e.g. `roll scatter 3d6`. The distance
+/// defaults to 2d6.
+fn scatter(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll scatter [<distance>] [-v]";
+    let mut distance = "2d6".to_string();
+    let mut verbose = false;
+    for arg in args {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            _ => distance = arg.trim_end_matches('"').to_lowercase(),
+        }
+    }
+    let distance = parse(&distance)?;
+    if distance.roots().len() != 1 {
+        return Err(usage.to_string());
+    }
+    let scatter = scatter::roll_scatter(&distance);
+    Ok(if verbose {
+        format!("d8: {}\n{}", scatter.direction, scatter)
+    } else {
+        scatter.to_string()
+    })
+}
+
+/// `roll sr <pool> [--edge] [-v]`
+fn sr(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll sr <pool> [--edge] [-v]";
+    let mut pool = None;
+    let mut edge = false;
+    let mut verbose = false;
+    for arg in args {
+        match arg.as_str() {
+            "-e" | "--edge" => edge = true,
+            "-v" | "--verbose" => verbose = true,
+            _ => pool = Some(arg.parse::<u32>().map_err(|_| usage)?),
+        }
+    }
+    let roll = shadowrun::roll_pool(pool_size(pool.ok_or(usage)?)?, edge);
+    Ok(if verbose {
+        format!("{}\n{}", format_dice(&roll.dice), roll)
+    } else {
+        roll.to_string()
+    })
+}
+
+/// `roll stats5e [--min <total>] [--compare] [-v]`
+fn stats5e(args: &[String]) -> Result<String, String> {
+    let usage = format!(
+        "Usage: roll stats5e [--min <total>] [--compare] [-v]\n\n\
+         --min rerolls sets totalling less (at most {})\n\
+         --compare shows the difference from the standard array",
+        dnd::MAX_MIN_TOTAL
+    );
+    let mut min_total = 0;
+    let mut compare = false;
+    let mut verbose = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            "--compare" => compare = true,
+            "--min" => {
+                min_total = args
+                    .next()
+                    .and_then(|n| n.parse::<i32>().ok())
+                    .filter(|&n| n <= dnd::MAX_MIN_TOTAL)
+                    .ok_or(usage.clone())?
+            }
+            _ => return Err(usage),
+        }
+    }
+    let scores = dnd::roll_ability_scores(min_total)?;
+    let mut output = String::new();
+    if verbose {
+        for dice in &scores.dice {
+            output.push_str(&format!("{}\n", format_dice(dice)));
+        }
+        if scores.rerolls > 0 {
+            output.push_str(&format!("({} sets rerolled)\n", scores.rerolls));
+        }
+    }
+    output.push_str(&scores.to_string());
+    if compare {
+        let standard: i32 = dnd::STANDARD_ARRAY.iter().sum();
+        let differences: Vec<String> = scores
+            .scores()
+            .iter()
+            .zip(dnd::STANDARD_ARRAY)
+            .map(|(score, standard)| format!("{:+}", score - standard))
+            .collect();
+        output.push_str(&format!(
+            "\nvs standard array: {} ({:+} total)",
+            differences.join(", "),
+            scores.total() - standard
+        ));
+    }
+    Ok(output)
+}
+
+/// `roll validate-array <score>... [--method pointbuy|standard] [--points <n>]`,
+/// e.g. `roll validate-array 15 14 13 12 10 8`
+fn validate_array(args: &[String]) -> Result<String, String> {
+    let usage = format!(
+        "Usage: roll validate-array <score>... [--method pointbuy|standard] [--points <n>]\n\n\
+         pointbuy (the default) checks the cost against a budget of --points\n\
+         (default {}); standard checks for the standard array in any order",
+        dnd::POINT_BUY_BUDGET
+    );
+    let mut scores = Vec::new();
+    let mut standard = false;
+    let mut budget = dnd::POINT_BUY_BUDGET;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--method" => match args.next().map(String::as_str) {
+                Some("pointbuy") => standard = false,
+                Some("standard") => standard = true,
+                _ => return Err(usage),
+            },
+            "--points" => {
+                budget = args
+                    .next()
+                    .and_then(|n| n.parse::<i32>().ok())
+                    .filter(|&n| n >= 0)
+                    .ok_or(usage.clone())?
+            }
+            score => scores.push(
+                score
+                    .trim_end_matches(',')
+                    .parse::<i32>()
+                    .map_err(|_| usage.clone())?,
+            ),
+        }
+    }
+    if scores.is_empty() {
+        return Err(usage);
+    }
+    let listed: Vec<String> = scores.iter().map(i32::to_string).collect();
+    let listed = listed.join(", ");
+    if standard {
+        return if dnd::is_standard_array(&scores) {
+            Ok(format!("{}: the standard array", listed))
+        } else {
+            Err(format!("{}: not the standard array", listed))
+        };
+    }
+    match dnd::check_point_buy(&scores, budget) {
+        Ok(cost) => Ok(format!("{}: legal, {} of {} points", listed, cost, budget)),
+        Err(err) => Err(format!("{}: {}", listed, err)),
+    }
+}
+
+/// `roll gen-array [--points <n>]`: a random legal point-buy array.
+fn gen_array(args: &[String]) -> Result<String, String> {
+    let usage = format!(
+        "Usage: roll gen-array [--points <n>]\n\n\
+         --points sets the point-buy budget (default {})",
+        dnd::POINT_BUY_BUDGET
+    );
+    let budget = match args {
+        [] => dnd::POINT_BUY_BUDGET,
+        [flag, n] if flag == "--points" => {
+            n.parse::<i32>().ok().filter(|&n| n >= 0).ok_or(usage)?
+        }
+        _ => return Err(usage),
+    };
+    let scores = dnd::random_point_buy(budget);
+    let cost: i32 = scores.iter().filter_map(|&s| dnd::point_buy_cost(s)).sum();
+    let scores: Vec<String> = scores
+        .iter()
+        .map(|&s| format!("{} ({:+})", s, dnd::ability_modifier(s)))
+        .collect();
+    Ok(format!(
+        "{}, {} of {} points",
+        scores.join(", "),
+        cost,
+        budget
+    ))
+}
+
+/// `roll sw <die> [tn] [-v]`, e.g. `roll sw d8 6`
+fn sw(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll sw <die> [tn] [-v]";
+    let mut sides = None;
+    let mut target = savage_worlds::DEFAULT_TARGET;
+    let mut verbose = false;
+    for arg in args {
+        match arg.to_lowercase().as_str() {
+            "-v" | "--verbose" => verbose = true,
+            die if die.starts_with('d') => {
+                sides = Some(
+                    die[1..]
+                        .parse::<i32>()
+                        .ok()
+                        .filter(|&s| s > 1)
+                        .ok_or(usage)?,
+                )
+            }
+            tn => target = tn.parse().map_err(|_| usage)?,
+        }
+    }
+    let roll = savage_worlds::roll_trait(sides.ok_or(usage)?, target);
+    Ok(if verbose {
+        format!(
+            "trait {} = {}, wild {} = {}\n{}",
+            format_dice(&roll.trait_dice),
+            roll.trait_total(),
+            format_dice(&roll.wild_dice),
+            roll.wild_total(),
+            roll
+        )
+    } else {
+        roll.to_string()
+    })
+}
+
+/// `roll vs <n>d<sides> <n>d<sides> [-v]`, e.g. `roll vs 3d6 2d6`
+fn vs(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll vs <attacker>d<sides> <defender>d<sides> [-v]";
+    let mut pools = Vec::new();
+    let mut verbose = false;
+    for arg in args {
+        match arg.to_lowercase().as_str() {
+            "-v" | "--verbose" => verbose = true,
+            pool => {
+                let (count, sides) = pool.split_once('d').ok_or(usage)?;
+                let count = count.parse::<u32>().map_err(|_| usage)?;
+                let sides = sides.parse::<i32>().ok().filter(|&s| s > 0).ok_or(usage)?;
+                pools.push((pool_size(count)?, sides));
+            }
+        }
+    }
+    let [attacker, defender] = pools[..] else {
+        return Err(usage.to_string());
+    };
+    let battle = risk::roll_battle(attacker, defender);
+    Ok(if verbose {
+        format!(
+            "attacker {} vs defender {}: {}",
+            format_dice(&battle.attacker),
+            format_dice(&battle.defender),
+            battle
+        )
+    } else {
+        battle.to_string()
+    })
+}
+
+/// `roll wfrp <target> [--reverse] [-v]`
+fn wfrp(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll wfrp <target> [--reverse] [-v]";
+    let mut target = None;
+    let mut reverse = false;
+    let mut verbose = false;
+    for arg in args {
+        match arg.as_str() {
+            "-r" | "--reverse" => reverse = true,
+            "-v" | "--verbose" => verbose = true,
+            _ => target = Some(arg.parse::<i32>().map_err(|_| usage)?),
+        }
+    }
+    let roll = wfrp::roll_against(target.ok_or(usage)?, reverse);
+    Ok(if verbose && roll.rolled != roll.result {
+        format!(
+            "rolled {}, reversed to {}\n{}",
+            roll.rolled, roll.result, roll
+        )
+    } else {
+        roll.to_string()
+    })
+}
+
+/// `roll yz <n>b <n>s <n>g [--push] [-v] [--stats]`, e.g. `roll yz 5b 3s 2g`
+fn yz(args: &[String]) -> Result<String, String> {
+    let usage = "Usage: roll yz [<n>b] [<n>s] [<n>g] [--push] [-v] [--stats]\n\n\
+                 --stats prints the average successes and banes instead of rolling";
+    let (mut base, mut skill, mut gear) = (0, 0, 0);
+    let mut push = false;
+    let mut verbose = false;
+    let mut stats = false;
+    for arg in args {
+        let arg = arg.to_lowercase();
+        match arg.as_str() {
+            "-p" | "--push" => push = true,
+            "-v" | "--verbose" => verbose = true,
+            "--stats" => stats = true,
+            _ => {
+                let color = arg.chars().last().ok_or(usage)?;
+                let count = arg[..arg.len() - color.len_utf8()]
+                    .parse::<u32>()
+                    .map_err(|_| usage)?;
+                let dice = match color {
+                    'b' => &mut base,
+                    's' => &mut skill,
+                    'g' => &mut gear,
+                    _ => return Err(usage.to_string()),
+                };
+                *dice = pool_size(count.saturating_add(*dice))?;
+            }
+        }
+    }
+    if pool_size(base + skill + gear)? == 0 {
+        return Err(usage.to_string());
+    }
+    if stats {
+        let (per_color, total) = year_zero::expected(base, skill, gear);
+        let mut lines = vec![format!("{:<8} {:>9} {:>6}", "", "successes", "banes")];
+        for ((name, count), expected) in [("base", base), ("skill", skill), ("gear", gear)]
+            .into_iter()
+            .zip(per_color)
+        {
+            if count > 0 {
+                lines.push(format!(
+                    "{:<8} {:>9.2} {:>6.2}",
+                    format!("{} {}", count, name),
+                    expected.successes,
+                    expected.banes
+                ));
+            }
+        }
+        lines.push(format!(
+            "{:<8} {:>9.2} {:>6.2}",
+            "overall", total.successes, total.banes
+        ));
+        lines.push(format!(
+            "{:.1}% to get at least one success",
+            year_zero::chance_of_success(base + skill + gear) * 100.0
+        ));
+        return Ok(lines.join("\n"));
+    }
+
+    let describe = |pool: &year_zero::Pool| {
+        let mut dice = Vec::new();
+        for (name, rolled) in [
+            ("base", &pool.base),
+            ("skill", &pool.skill),
+            ("gear", &pool.gear),
+        ] {
+            if !rolled.is_empty() {
+                dice.push(format!("{} {}", name, format_dice(rolled)));
+            }
+        }
+        format!("{}\n{}", dice.join(", "), pool)
+    };
+
+    let pool = year_zero::Pool::roll(base, skill, gear);
+    if !push {
+        return Ok(if verbose {
+            describe(&pool)
+        } else {
+            pool.to_string()
+        });
+    }
+    let pushed = pool.push();
+    Ok(if verbose {
+        format!("{}\npushed:\n{}", describe(&pool), describe(&pushed))
+    } else {
+        format!("{} (before push: {})", pushed, pool)
+    })
+}
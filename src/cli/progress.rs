@@ -0,0 +1,73 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+const WIDTH: usize = 30;
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A progress bar on stderr with throughput and ETA. Draws nothing unless
+/// stderr is a terminal.
+pub struct Progress {
+    total: u64,
+    start: Instant,
+    last_draw: Option<Instant>,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(total: u64, enabled: bool) -> Self {
+        Progress {
+            total,
+            start: Instant::now(),
+            last_draw: None,
+            enabled: enabled && io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn update(&mut self, done: u64) {
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        if self
+            .last_draw
+            .is_some_and(|last| now - last < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+
+        let fraction = done as f64 / self.total as f64;
+        let filled = (fraction * WIDTH as f64) as usize;
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = done as f64 / elapsed.max(f64::EPSILON);
+        let eta = (self.total - done) as f64 / rate.max(f64::EPSILON);
+
+        eprint!(
+            "\r[{}{}] {:>3.0}%  {}/s  ETA {:.0}s ",
+            "#".repeat(filled),
+            " ".repeat(WIDTH - filled),
+            fraction * 100.0,
+            human(rate),
+            eta
+        );
+        let _ = io::stderr().flush();
+    }
+
+    /// Erases the bar so the final output starts on a clean line.
+    pub fn finish(&self) {
+        if self.enabled && self.last_draw.is_some() {
+            eprint!("\r{}\r", " ".repeat(WIDTH + 40));
+            let _ = io::stderr().flush();
+        }
+    }
+}
+
+fn human(rate: f64) -> String {
+    if rate >= 1e6 {
+        format!("{:.2}M", rate / 1e6)
+    } else if rate >= 1e3 {
+        format!("{:.1}k", rate / 1e3)
+    } else {
+        format!("{:.0}", rate)
+    }
+}
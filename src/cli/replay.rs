@@ -0,0 +1,89 @@
+use std::fs;
+
+use reroll::optimize::fold_all;
+use reroll::parser::parse;
+
+use crate::cli::log::Entry;
+use crate::cli::roll::{Options, render};
+
+/// `roll replay`: rolls every entry of a session log again from its seed
+/// and prints it in the current output format. With `--verify`, also checks
+/// that each entry still produces the results it recorded.
+pub fn run(args: &[String]) -> i32 {
+    let mut verbose = false;
+    let mut ladder = false;
+    let mut verify = false;
+    let mut path = None;
+
+    for arg in args {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            "--ladder" => ladder = true,
+            "--verify" => verify = true,
+            _ => path = Some(arg),
+        }
+    }
+
+    let Some(path) = path else {
+        eprintln!(
+            "Usage: roll replay [-v] [--ladder] [--verify] <log>\n\n\
+             Replays a session log written with `roll --log <log>`.\n\n\
+             Options:\n\
+             \t--verify   Check each roll against the results it recorded"
+        );
+        return 1;
+    };
+
+    let log = match fs::read_to_string(path) {
+        Ok(log) => log,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return 1;
+        }
+    };
+
+    let mut status = 0;
+    for (i, line) in log.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let replayed = Entry::parse(line).and_then(|entry| {
+            let mut ast = parse(&entry.expression)?;
+            fold_all(&mut ast);
+            let roll = |options: &Options| {
+                fastrand::seed(entry.seed);
+                render(&ast, options)
+            };
+            let current = Options {
+                verbose,
+                ladder,
+                ties: entry.options.ties,
+            };
+            let verified = !verify || roll(&entry.options) == entry.results;
+            let expression = match &entry.user {
+                Some(user) => format!("[{}] {}", user, entry.expression),
+                None => entry.expression.clone(),
+            };
+            Ok((expression, roll(&current), verified))
+        });
+
+        match replayed {
+            Ok((expression, results, verified)) => {
+                let mismatch = if verified {
+                    ""
+                } else {
+                    "  (does not match the log)"
+                };
+                println!("{}: {}{}", expression, results.join(", "), mismatch);
+                if !verified {
+                    status = 1;
+                }
+            }
+            Err(err) => {
+                eprintln!("{}:{}: {}", path, i + 1, err);
+                status = 1;
+            }
+        }
+    }
+    status
+}
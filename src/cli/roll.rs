@@ -0,0 +1,765 @@
+use std::cmp::Ordering;
+use std::env;
+use std::io::{self, BufWriter, IsTerminal, Read, StdoutLock, Write};
+
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use reroll::ast::{Ast, ExprKind, format_thresholds};
+use reroll::eval::{
+    EvalResult, TieBreak, counts_successes, describe_contest, describe_successes, describe_tier,
+    eval_annotation, eval_breakdown, eval_expr, eval_total, format_rolls, kept_dice, start_tally,
+    take_tally, tier_reached,
+};
+use reroll::lint::lint;
+use reroll::macros::apply_notation;
+use reroll::optimize::fold_all;
+use reroll::parser::parse;
+use reroll::presets::fate::format_ladder;
+use reroll::rng::{derive_seed, for_each_stream, label_stream, map_streams, set_threads};
+
+use crate::cli::config::{self, Format};
+use crate::cli::history::Rolled;
+use crate::cli::hook;
+use crate::cli::log::{Entry, Record};
+
+/// The default command: rolls every expression and prints the results.
+pub fn run(args: &[String]) -> i32 {
+    let mut verbose = false;
+    let mut show_help = false;
+    let mut ladder = false;
+    let mut seed = None;
+    let mut ties = None;
+    let mut profile = None;
+    let mut character = None;
+    let mut on_result = None;
+    let mut check = false;
+    let mut filter = false;
+    let mut must_pass = None;
+    let mut user = None;
+    let mut dramatic = false;
+    let mut log = None;
+    let mut json_out = None;
+    let mut file = None;
+    let mut expr_parts = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            "-h" | "--help" => show_help = true,
+            "--ladder" => ladder = true,
+            "--check" => check = true,
+            "--filter" => filter = true,
+            "--dramatic" => dramatic = true,
+            "--all-must-pass" => must_pass = Some(MustPass::All),
+            "--any-must-pass" => must_pass = Some(MustPass::Any),
+            "--seed" => match args.next().map(|s| s.parse::<u64>()) {
+                Some(Ok(n)) => seed = Some(n),
+                _ => {
+                    eprintln!("--seed requires a non-negative integer");
+                    return 1;
+                }
+            },
+            "--ties" => match args.next().map(String::as_str) {
+                Some("draw") => ties = Some(TieBreak::Draw),
+                Some("first") => ties = Some(TieBreak::First),
+                Some("second") => ties = Some(TieBreak::Second),
+                _ => {
+                    eprintln!("--ties requires draw, first or second");
+                    return 1;
+                }
+            },
+            "--log" => match args.next() {
+                Some(path) => log = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("--log requires a file");
+                    return 1;
+                }
+            },
+            "--json-out" => match args.next() {
+                Some(path) => json_out = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("--json-out requires a file");
+                    return 1;
+                }
+            },
+            "-c" | "--char" => match args.next() {
+                Some(name) => character = Some(name.as_str()),
+                None => {
+                    eprintln!("--char requires a character name");
+                    return 1;
+                }
+            },
+            "--as" => match args.next() {
+                Some(name) => user = Some(name.clone()),
+                None => {
+                    eprintln!("--as requires a name");
+                    return 1;
+                }
+            },
+            "--on-result" => match args.next() {
+                Some(command) => on_result = Some(command.as_str()),
+                None => {
+                    eprintln!("--on-result requires a command");
+                    return 1;
+                }
+            },
+            "--profile" => match args.next() {
+                Some(name) => profile = Some(name.as_str()),
+                None => {
+                    eprintln!("--profile requires a profile name");
+                    return 1;
+                }
+            },
+            "--file" => match args.next() {
+                Some(path) => file = Some(path),
+                None => {
+                    eprintln!("--file requires a script");
+                    return 1;
+                }
+            },
+            "--threads" => match args.next().map(|s| s.parse::<usize>()) {
+                Some(Ok(n)) if n > 0 => set_threads(n),
+                _ => {
+                    eprintln!("--threads requires a positive integer");
+                    return 1;
+                }
+            },
+            _ => expr_parts.push(arg.to_lowercase()),
+        }
+    }
+
+    if show_help || (expr_parts.is_empty() && file.is_none() && !filter) {
+        eprintln!(
+            "Usage: roll [options] <expr>\n       \
+             roll fmt [--check] <expr>...\n       \
+             roll explain [--json] <expr>\n       \
+             roll sim [-n <samples>] [--threads <n>] [--json] <expr>\n       \
+             roll calibrate [-n <samples>] <expr>\n       \
+             roll dist [-n <samples>] [--plot <file.svg>] <expr>\n       \
+             roll gen --seeds <first>..<last> [-v] <expr>\n       \
+             roll table [-v] <file>\n       \
+             roll fill [--seed <n>] [-o <file>] <template>\n       \
+             roll draw [<n>] [--deck <name|file>] [--persist] [--reset]\n       \
+             roll bag [--name <bag>] (init <tokens> | draw [<n>] | reset | show)\n       \
+             roll char (add <name> --set <stat>=<n>... | show <name> | remove <name>)\n       \
+             roll replay [-v] [--ladder] [--verify] <log>\n       \
+             roll lint <file>...\n       \
+             roll history [--last <n> | --stats [d<sides>] | clear]\n       \
+             roll aliases [list | export [<file>] | import <file> [--merge] [--force]]\n       \
+             roll screen <file>\n       \
+             roll wizard\n\n\
+             Options:\n\
+             \t-v, --verbose   Show individual rolls\n\
+             \t--ladder        Name totals on the Fate ladder (+3 Good)\n\
+             \t--seed <n>      Seed the random number generator\n\
+             \t--check         Check the expressions for problems without rolling\n\
+             \t--dramatic      Show the dice one at a time before the results\n\
+             \t--all-must-pass Print how many checks passed; exit with 2 unless all did\n\
+             \t--any-must-pass Print how many checks passed; exit with 2 unless one did\n\
+             \t--ties <side>   Who wins tied contests: draw, first or second\n\
+             \t--threads <n>   Worker threads for large repetitions\n\
+             \t--log <file>    Append the rolls and their seed to a session log\n\
+             \t--json-out <file> Append a JSON record per result, as well as printing it\n\
+             \t--as <name>     Tag the rolls with who made them in logs and records\n\
+             \t--on-result <cmd> Run a shell command per result, with its JSON on stdin\n\
+             \t--filter       Roll each `[[expr]]` in text from stdin and print the text\n\
+             \t--file <script> Roll each line of a script (`@seed <n>` seeds later lines)\n\
+             \t--profile <name> Use a profile from the config (or set REROLL_PROFILE)\n\
+             \t-c, --char <name> Use a character's stats (see `roll char`)\n\
+             \t-h, --help      Show this help message\n\n\
+             {}",
+            crate::cli::preset::help()
+        );
+        return if show_help { 0 } else { 1 };
+    }
+
+    let config = match config::load()
+        .and_then(|config| config.activate(config::profile_name(profile).as_deref()))
+        .and_then(|mut config| {
+            if let Some(name) = character {
+                for stat in crate::cli::character::macros(name)?.iter() {
+                    config.macros.define(stat.clone());
+                }
+            }
+            Ok(config)
+        }) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    let verbose = verbose || config.defaults.verbose.unwrap_or(false);
+    let ladder = ladder || config.defaults.ladder.unwrap_or(false);
+    let ties = ties.or(config.defaults.ties).unwrap_or(TieBreak::Draw);
+
+    if filter {
+        if file.is_some() || log.is_some() || json_out.is_some() || check || !expr_parts.is_empty()
+        {
+            eprintln!(
+                "--filter cannot be combined with --file, --log, --json-out, --check or expressions"
+            );
+            return 1;
+        }
+        if let Some(seed) = seed {
+            fastrand::seed(seed);
+        }
+        let options = Options {
+            verbose,
+            ladder,
+            ties,
+        };
+        let mut text = String::new();
+        if let Err(err) = io::stdin().read_to_string(&mut text) {
+            eprintln!("Failed to read stdin: {}", err);
+            return 1;
+        }
+        return match crate::cli::fill::fill(&text, ("[[", "]]"), &config, &options) {
+            Ok(filled) => {
+                print!("{}", filled);
+                0
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                1
+            }
+        };
+    }
+
+    if let Some(path) = file {
+        if log.is_some() || json_out.is_some() || check || !expr_parts.is_empty() {
+            eprintln!("--file cannot be combined with --log, --json-out, --check or expressions");
+            return 1;
+        }
+        if let Some(seed) = seed {
+            fastrand::seed(seed);
+        }
+        let options = Options {
+            verbose,
+            ladder,
+            ties,
+        };
+        return match crate::cli::script::run(path, &options) {
+            Ok(results) => finish(&results),
+            Err(err) => {
+                eprintln!("{}", err);
+                1
+            }
+        };
+    }
+
+    let input = expr_parts.join(" ");
+    // Everything after `#` is a comment, echoed into `--on-result` records.
+    let (input, comment) = match input.split_once('#') {
+        Some((input, comment)) => (input.to_string(), Some(comment.trim().to_string())),
+        None => (input, None),
+    };
+    let input = apply_notation(&input, &config.notation);
+    let input = match config.macros.expand(&input) {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    if check {
+        return check_input(&input);
+    }
+    let mut ast = match parse(&input) {
+        Ok(ast) => ast,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+
+    // Logs and records keep the expression as parsed: folding can leave
+    // negative numbers, which do not parse back.
+    let canonical = ast.to_string();
+    let expressions: Vec<String> = ast
+        .roots()
+        .iter()
+        .map(|&root| ast.display(root).to_string())
+        .collect();
+    fold_all(&mut ast);
+
+    // Logged rolls always have a seed, so replays can roll them again.
+    if log.is_some() && seed.is_none() {
+        seed = Some(fastrand::u64(..));
+    }
+    if let Some(seed) = seed {
+        fastrand::seed(seed);
+    }
+
+    let options = Options {
+        verbose,
+        ladder,
+        ties,
+    };
+    let history = config.defaults.history.unwrap_or(false);
+    let dramatic = dramatic && io::stdout().is_terminal();
+    let tally = history || dramatic;
+    if tally {
+        start_tally();
+    }
+    let json = config.defaults.format == Some(Format::Json);
+    let paint = !json
+        && !config.colors.is_empty()
+        && io::stdout().is_terminal()
+        && env::var_os("NO_COLOR").is_none();
+    // Crits are judged on the dice that counted, not those dropped, and
+    // only verbose lines show dice to paint.
+    let keep = config.crits.is_active() || paint && options.verbose;
+    let painted = |rendered: &Rendered| {
+        if paint {
+            config
+                .colors
+                .paint(&rendered.line, rendered.total, &rendered.dice)
+        } else {
+            rendered.line.clone()
+        }
+    };
+    let mut results = Vec::new();
+    let mut checks = Vec::new();
+    let mut kept = Vec::new();
+    let mut add = |rendered: Rendered| {
+        results.push(rendered.line);
+        checks.push(rendered.passed);
+        kept.extend(rendered.dice);
+    };
+    let (status, dice) = if dramatic {
+        // The dice are revealed before any result, so roll them all first.
+        let rendered = render_all(&ast, &options, keep);
+        let dice = take_tally();
+        reveal(&dice);
+        let painted: Vec<String> = rendered.iter().map(painted).collect();
+        rendered.into_iter().for_each(&mut add);
+        (if json { 0 } else { finish(&painted) }, dice)
+    } else {
+        // JSON records are printed once every result is in.
+        let mut output = Output::new();
+        render_each(&ast, &options, keep, |rendered| {
+            if !json {
+                output.line(&painted(&rendered));
+            }
+            add(rendered);
+        });
+        let status = output.finish();
+        (status, if tally { take_tally() } else { Vec::new() })
+    };
+    let crit = config.crits.find(&kept);
+    if history {
+        let rolled = Rolled::new(user.clone(), canonical.clone(), results.clone(), dice);
+        if let Err(err) = crate::cli::history::record(&rolled) {
+            eprintln!("Failed to write history: {}", err);
+            return 1;
+        }
+    }
+    if let Some(path) = &log {
+        let entry = Entry {
+            seed: seed.unwrap(),
+            user: user.clone(),
+            expression: canonical,
+            options,
+            results: results.clone(),
+        };
+        if let Err(err) = entry.append(path) {
+            eprintln!("Failed to write log: {}: {}", path.display(), err);
+            return 1;
+        }
+    }
+
+    let records = if json || on_result.is_some() || json_out.is_some() {
+        records(
+            &ast,
+            &expressions,
+            seed,
+            user.as_deref(),
+            comment.as_deref(),
+            &results,
+        )
+    } else {
+        Vec::new()
+    };
+    let status = if json {
+        let lines: Vec<String> = records.iter().map(Record::to_json).collect();
+        finish(&lines)
+    } else {
+        status
+    };
+    if let Some(path) = &json_out
+        && let Err(err) = records.iter().try_for_each(|record| record.append(path))
+    {
+        eprintln!("Failed to write records: {}: {}", path.display(), err);
+        return 1;
+    }
+
+    if let Some(command) = on_result {
+        for record in &records {
+            if let Err(err) = hook::run(command, &record.to_json()) {
+                eprintln!("{}", err);
+                return 1;
+            }
+        }
+    }
+
+    if let Some((sides, value)) = crit {
+        if config.crits.bell {
+            eprint!("\x07");
+        }
+        if let Some(command) = &config.crits.command {
+            let json = format!("{{\"sides\":{},\"value\":{}}}", sides, value);
+            if let Err(err) = hook::run(command, &json) {
+                eprintln!("{}", err);
+                return 1;
+            }
+        }
+    }
+
+    if let Some(must_pass) = must_pass {
+        let checked = checks.iter().flatten().count();
+        let passed = checks.iter().flatten().filter(|&&p| p).count();
+        println!("{}/{} passed", passed, checked);
+        let met = match must_pass {
+            MustPass::All => passed == checked,
+            MustPass::Any => passed > 0,
+        };
+        if status == 0 && !met {
+            return 2;
+        }
+    }
+    status
+}
+
+/// How many checked rolls must pass for `--all-must-pass` and
+/// `--any-must-pass` to exit successfully.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MustPass {
+    All,
+    Any,
+}
+
+/// `--dramatic`: shows each die as it lands, then pauses before the
+/// results. The whole reveal takes a few seconds however many dice there
+/// are.
+fn reveal(dice: &[(i32, i32)]) {
+    let pause = Duration::from_millis(400).min(Duration::from_secs(3) / dice.len().max(1) as u32);
+    let mut out = io::stdout();
+    for (sides, value) in dice {
+        let _ = write!(out, "d{} ", sides);
+        let _ = out.flush();
+        thread::sleep(pause);
+        let _ = writeln!(out, "{}", value);
+    }
+    thread::sleep(Duration::from_millis(800));
+    let _ = writeln!(out, "...");
+    thread::sleep(Duration::from_millis(600));
+}
+
+/// Pairs each rendered line with the root it came from and that root's
+/// expression as parsed.
+fn records(
+    ast: &Ast,
+    expressions: &[String],
+    seed: Option<u64>,
+    user: Option<&str>,
+    comment: Option<&str>,
+    results: &[String],
+) -> Vec<Record> {
+    ast.roots()
+        .iter()
+        .zip(expressions)
+        .zip(results)
+        .map(|((&root, expression), line)| {
+            let label = ast.label(root);
+            let result = label
+                .and_then(|label| line.strip_prefix(&format!("{}: ", label)))
+                .unwrap_or(line);
+            let targets = match ast[root].kind {
+                ExprKind::Contest(_, second) => match ast[second].kind {
+                    ExprKind::Number(n) => vec![n],
+                    _ => Vec::new(),
+                },
+                ExprKind::Tiers { ref thresholds, .. } => thresholds.clone(),
+                _ => Vec::new(),
+            };
+            Record {
+                seed,
+                user: user.map(str::to_string),
+                label: label.map(str::to_string),
+                comment: comment.filter(|c| !c.is_empty()).map(str::to_string),
+                expression: expression.clone(),
+                annotations: ast
+                    .annotations(root)
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect(),
+                targets,
+                result: result.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// `--check`: reports the problems `roll lint` would, by column, and
+/// prints the expression in canonical form if there are none.
+fn check_input(input: &str) -> i32 {
+    let problems = lint(input);
+    for problem in &problems {
+        eprintln!(
+            "{}: {}: {}",
+            problem.span.start + 1,
+            problem.severity,
+            problem.message
+        );
+    }
+    match parse(input) {
+        Ok(ast) if problems.is_empty() => {
+            println!("{}", ast);
+            0
+        }
+        _ => 1,
+    }
+}
+
+/// Prints the results and returns the exit status.
+pub fn finish(results: &[String]) -> i32 {
+    let mut output = Output::new();
+    for line in results {
+        output.line(line);
+    }
+    output.finish()
+}
+
+/// How results are rendered; logged with each roll so replays can
+/// reproduce the original output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Options {
+    pub verbose: bool,
+    pub ladder: bool,
+    pub ties: TieBreak,
+}
+
+/// Rolls every root of `ast` and renders one line per root.
+///
+/// A labeled root rolls from a stream derived from its label (and how many
+/// roots before it share the label) rather than its position, so adding or
+/// removing other rolls leaves its result unchanged for a given seed.
+pub fn render(ast: &Ast, options: &Options) -> Vec<String> {
+    render_all(ast, options, false)
+        .into_iter()
+        .map(|rendered| rendered.line)
+        .collect()
+}
+
+/// One rolled root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    pub line: String,
+    /// Whether the roll passed its check: a contest the first side wins, a
+    /// tier roll that reaches its first threshold, or a success pool with
+    /// any successes. Other rolls have nothing to pass.
+    pub passed: Option<bool>,
+    /// The total the line ends in, if it ends in one.
+    pub total: Option<i32>,
+    /// The dice that counted toward the result, as `(sides, value)`, if
+    /// they were asked for; see [`kept_dice`].
+    pub dice: Vec<(i32, i32)>,
+}
+
+/// Like [`render`], with each root's check and, if `dice` is set, the dice
+/// that counted.
+pub fn render_all(ast: &Ast, options: &Options, dice: bool) -> Vec<Rendered> {
+    let label_base = label_base(ast);
+    map_streams(ast.roots().len(), |i| {
+        render_root(ast, options, label_base, i, dice)
+    })
+}
+
+/// Like [`render_all`], but hands each result to `emit` as soon as it is
+/// rolled, in order, instead of collecting them. The results are the same.
+pub fn render_each<F>(ast: &Ast, options: &Options, dice: bool, mut emit: F)
+where
+    F: FnMut(Rendered),
+{
+    let label_base = label_base(ast);
+    for_each_stream(ast.roots().len(), |i| {
+        emit(render_root(ast, options, label_base, i, dice));
+    });
+}
+
+/// The seed labeled rolls draw their streams from, if there are any.
+fn label_base(ast: &Ast) -> Option<u64> {
+    ast.roots()
+        .iter()
+        .any(|&root| ast.label(root).is_some())
+        .then(|| fastrand::u64(..))
+}
+
+/// Rolls and renders the `i`th root, seeding labeled ones from their
+/// label's stream, and collects the dice that counted if `dice` is set.
+fn render_root(
+    ast: &Ast,
+    options: &Options,
+    label_base: Option<u64>,
+    i: usize,
+    dice: bool,
+) -> Rendered {
+    if !dice {
+        return roll_root(ast, options, label_base, i);
+    }
+    let (rendered, dice) = kept_dice(|| roll_root(ast, options, label_base, i));
+    Rendered { dice, ..rendered }
+}
+
+fn roll_root(ast: &Ast, options: &Options, label_base: Option<u64>, i: usize) -> Rendered {
+    let roots = ast.roots();
+    let format_total = |total: i32| {
+        if options.ladder {
+            format_ladder(total)
+        } else {
+            total.to_string()
+        }
+    };
+    let root = roots[i];
+    let label = ast.label(root);
+    if let (Some(base), Some(label)) = (label_base, label) {
+        let repeats = roots[..i]
+            .iter()
+            .filter(|&&earlier| ast.label(earlier) == Some(label))
+            .count();
+        let stream = derive_seed(label_stream(label), repeats as u64);
+        fastrand::seed(derive_seed(base, stream));
+    }
+    let side = |id| {
+        if options.verbose && !matches!(ast[id].kind, ExprKind::Number(_)) {
+            let (total, breakdown) = eval_breakdown(ast, id);
+            (total, format!("{} = {}", breakdown, total))
+        } else {
+            let total = eval_total(ast, id);
+            (total, total.to_string())
+        }
+    };
+    let annotations = ast.annotations(root);
+    let mut passed = None;
+    // Ladder totals are words, not numbers.
+    let mut total = None;
+    let mut format_total = |n: i32| {
+        if !options.ladder {
+            total = Some(n);
+        }
+        format_total(n)
+    };
+    let line = match ast[root].kind {
+        _ if !annotations.is_empty() => {
+            let result = eval_expr(ast, root);
+            let values: Vec<String> = annotations
+                .iter()
+                .map(|&a| format!("{} = {}", a, eval_annotation(&result, a)))
+                .collect();
+            if options.verbose {
+                format!("{} {}", format_rolls(&result), values.join(", "))
+            } else {
+                values.join(", ")
+            }
+        }
+        ExprKind::Choose(_) => format_rolls(&eval_expr(ast, root)),
+        ExprKind::Contest(first, second) => {
+            let (first, first_text) = side(first);
+            let (second, second_text) = side(second);
+            let outcome = options.ties.resolve(first, second);
+            passed = Some(outcome == Ordering::Greater);
+            let outcome = describe_contest(outcome);
+            format!("{} vs {}: {}", first_text, second_text, outcome)
+        }
+        ExprKind::Tiers {
+            expr,
+            ref thresholds,
+        } => {
+            let (total, text) = side(expr);
+            let reached = tier_reached(total, thresholds);
+            passed = Some(reached > 0);
+            let tier = describe_tier(reached, thresholds.len());
+            format!("{} vs {}: {}", text, format_thresholds(thresholds), tier)
+        }
+        ExprKind::BinaryOp(..)
+        | ExprKind::Fudge { .. }
+        | ExprKind::Call { .. }
+        | ExprKind::Read { .. }
+            if options.verbose =>
+        {
+            let (total, breakdown) = eval_breakdown(ast, root);
+            format!("{} = {}", breakdown, format_total(total))
+        }
+        _ if options.verbose || counts_successes(ast, root) => {
+            let result = eval_expr(ast, root);
+            if let EvalResult::Successes { .. } = result {
+                passed = Some(result.to_number() > 0 && !result.is_botch());
+            }
+            match result {
+                EvalResult::Successes { .. } if options.verbose => {
+                    format!("{} {}", format_rolls(&result), describe_successes(&result))
+                }
+                EvalResult::Successes { .. } => describe_successes(&result),
+                EvalResult::Number(n) => format_total(n),
+                _ => format_rolls(&result),
+            }
+        }
+        _ => format_total(eval_total(ast, root)),
+    };
+    let line = match label {
+        Some(label) => format!("{}: {}", label, line),
+        None => line,
+    };
+    Rendered {
+        line,
+        passed,
+        total,
+        dice: Vec::new(),
+    }
+}
+
+/// Writes results as they are produced through a single locked, buffered
+/// handle, so large outputs stream out without a flush per line.
+pub struct Output {
+    out: BufWriter<StdoutLock<'static>>,
+    /// The first write that failed; later lines are skipped.
+    error: Option<io::Error>,
+}
+
+impl Output {
+    pub fn new() -> Output {
+        Output {
+            out: BufWriter::new(io::stdout().lock()),
+            error: None,
+        }
+    }
+
+    pub fn line(&mut self, line: &str) {
+        if self.error.is_none()
+            && let Err(err) = writeln!(self.out, "{}", line)
+        {
+            self.error = Some(err);
+        }
+    }
+
+    /// Flushes what is left and returns the exit status.
+    pub fn finish(mut self) -> i32 {
+        let result = match self.error.take() {
+            Some(err) => Err(err),
+            None => self.out.flush(),
+        };
+        match result {
+            Ok(()) => 0,
+            // The reader went away (e.g. piped into `head`); nothing left to do.
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => 0,
+            Err(err) => {
+                eprintln!("Failed to write output: {}", err);
+                1
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use reroll::ast::Ast;
+use reroll::eval::TieBreak;
+use reroll::optimize::fold_all;
+use reroll::parser::parse;
+
+use crate::cli::roll::{Options, render};
+
+/// A named roll on the screen, e.g. `perception = "1d20+5"`.
+struct Roll {
+    name: String,
+    ast: Ast,
+}
+
+struct Section {
+    title: String,
+    rolls: Vec<Roll>,
+}
+
+/// `roll screen`: a GM screen. Loads named rolls grouped into sections and
+/// rolls whichever one is picked by number, until `q` or end of input.
+pub fn run(args: &[String]) -> i32 {
+    let [path] = args else {
+        eprintln!(
+            "Usage: roll screen <file>\n\n\
+             The file groups named rolls into sections:\n\n\
+             \t[perception]\n\
+             \tguard = \"1d20+2\"\n\
+             \tcaptain = \"1d20+6\"\n\n\
+             Pick a roll by its number; an empty line shows the menu again\n\
+             and `q` quits."
+        );
+        return 1;
+    };
+
+    let sections = match fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|source| load(&source))
+    {
+        Ok(sections) => sections,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return 1;
+        }
+    };
+    let rolls: Vec<&Roll> = sections.iter().flat_map(|s| &s.rolls).collect();
+    if rolls.is_empty() {
+        eprintln!("{}: no rolls", path);
+        return 1;
+    }
+
+    let options = Options {
+        verbose: false,
+        ladder: false,
+        ties: TieBreak::Draw,
+    };
+    print_menu(&sections);
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        let _ = io::stdout().flush();
+        let Some(Ok(line)) = lines.next() else {
+            println!();
+            return 0;
+        };
+        match line.trim() {
+            "" => print_menu(&sections),
+            "q" | "quit" => return 0,
+            choice => match choice.parse::<usize>() {
+                Ok(n) if (1..=rolls.len()).contains(&n) => {
+                    let roll = rolls[n - 1];
+                    println!("{}: {}", roll.name, render(&roll.ast, &options).join(", "));
+                }
+                _ => eprintln!("pick a roll from 1 to {}", rolls.len()),
+            },
+        }
+    }
+}
+
+fn print_menu(sections: &[Section]) {
+    let mut n = 0;
+    for section in sections {
+        if !section.title.is_empty() {
+            println!("{}", section.title);
+        }
+        for roll in &section.rolls {
+            n += 1;
+            println!("  {:>2}. {}", n, roll.name);
+        }
+    }
+}
+
+/// Reads the subset of TOML a screen needs: `[section]` headers, `name =
+/// "expression"` pairs and `#` comments. Rolls before the first header go
+/// in an untitled section.
+fn load(source: &str) -> Result<Vec<Section>, String> {
+    let mut sections = vec![Section {
+        title: String::new(),
+        rolls: Vec::new(),
+    }];
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| format!("line {}: {}", i + 1, message);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(title) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push(Section {
+                title: title.trim().replace('_', " "),
+                rolls: Vec::new(),
+            });
+            continue;
+        }
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `name = \"expression\"`".to_string()))?;
+        let expression = value
+            .trim()
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .ok_or_else(|| error("the expression must be quoted".to_string()))?;
+        let mut ast = parse(&expression.to_lowercase()).map_err(error)?;
+        fold_all(&mut ast);
+        sections.last_mut().unwrap().rolls.push(Roll {
+            name: name.trim().trim_matches('"').replace('_', " "),
+            ast,
+        });
+    }
+    sections.retain(|s| !s.rolls.is_empty());
+    Ok(sections)
+}
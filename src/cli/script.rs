@@ -0,0 +1,108 @@
+use std::fs;
+
+use reroll::ast::{Ast, ExprKind};
+use reroll::optimize::fold_all;
+use reroll::parser::parse;
+use reroll::rng::derive_seed;
+
+use crate::cli::roll::{Options, render};
+
+/// Options set by directives for the next expression in a script.
+#[derive(Default)]
+struct Pending {
+    /// The line of the first directive, for errors.
+    line: Option<usize>,
+    dc: Option<i32>,
+    label: Option<String>,
+    times: Option<usize>,
+}
+
+impl Pending {
+    /// Checks each root against the DC, names it, and renders the line as
+    /// many times as asked.
+    fn apply(self, mut ast: Ast, options: &Options) -> Result<Vec<String>, String> {
+        for root in ast.roots().to_vec() {
+            if let Some(dc) = self.dc {
+                if matches!(
+                    ast[root].kind,
+                    ExprKind::Contest(..) | ExprKind::Tiers { .. }
+                ) {
+                    return Err("`@dc` on a roll that is already a check".to_string());
+                }
+                let check = ast.tiers(root, vec![dc]);
+                ast.replace_root(root, check);
+            }
+        }
+        if let Some(label) = self.label {
+            for root in ast.roots().to_vec() {
+                ast.set_label(root, label.clone());
+            }
+        }
+        Ok((0..self.times.unwrap_or(1))
+            .flat_map(|_| render(&ast, options))
+            .collect())
+    }
+}
+
+/// Rolls a script: one expression per line, with blank lines and `#`
+/// comments skipped. Returns the results of every line in order.
+///
+/// `@seed <n>` seeds the rolls on the lines after it, so a prepared handout
+/// comes out the same every time, and `@seed random` goes back to random
+/// rolls. `@dc <n>`, `@label <name>` and `@n <count>` apply to the next
+/// expression only: it is checked against the DC, labelled, and rolled
+/// `count` times.
+pub fn roll_script(source: &str, options: &Options) -> Result<Vec<String>, String> {
+    // Drawn up front so `@seed random` does not depend on earlier seeds.
+    let unseeded = fastrand::u64(..);
+    let mut results = Vec::new();
+    let mut pending = Pending::default();
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| format!("{}: {}", i + 1, message);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(directive) = line.strip_prefix('@') {
+            let words: Vec<&str> = directive.split_whitespace().collect();
+            match words[..] {
+                ["seed", "random"] => fastrand::seed(derive_seed(unseeded, i as u64)),
+                ["seed", seed] => match seed.parse::<u64>() {
+                    Ok(seed) => fastrand::seed(seed),
+                    Err(_) => return Err(error(format!("bad seed `{}`", seed))),
+                },
+                ["dc", dc] => match dc.parse::<i32>() {
+                    Ok(dc) => pending.dc = Some(dc),
+                    Err(_) => return Err(error(format!("bad DC `{}`", dc))),
+                },
+                ["n", times] => match times.parse::<usize>() {
+                    Ok(times) if times > 0 => pending.times = Some(times),
+                    _ => return Err(error(format!("bad count `{}`", times))),
+                },
+                ["label", ..] if words.len() > 1 => pending.label = Some(words[1..].join(" ")),
+                _ => return Err(error(format!("unknown directive `{}`", line))),
+            }
+            if words[0] != "seed" {
+                pending.line.get_or_insert(i + 1);
+            }
+            continue;
+        }
+        let mut ast = parse(&line.to_lowercase()).map_err(error)?;
+        fold_all(&mut ast);
+        results.extend(
+            std::mem::take(&mut pending)
+                .apply(ast, options)
+                .map_err(error)?,
+        );
+    }
+    if let Some(line) = pending.line {
+        return Err(format!("{}: directive with no expression after it", line));
+    }
+    Ok(results)
+}
+
+/// Reads and rolls the script at `path`, for `roll --file`.
+pub fn run(path: &str, options: &Options) -> Result<Vec<String>, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    roll_script(&source, options).map_err(|err| format!("{}:{}", path, err))
+}
@@ -0,0 +1,207 @@
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use reroll::compile::Program;
+use reroll::optimize::fold_all;
+use reroll::parser::parse;
+use reroll::rng::default_threads;
+use reroll::sim::{Method, Sampling, Summary, simulate_with};
+
+use crate::cli::progress::Progress;
+
+const DEFAULT_SAMPLES: u64 = 10_000;
+
+/// Standard errors either side of the mean for a 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// `roll sim`: evaluates one expression many times and summarizes totals.
+pub fn run(args: &[String]) -> i32 {
+    let mut samples = DEFAULT_SAMPLES;
+    let mut sampling = Sampling {
+        threads: default_threads(),
+        ..Sampling::default()
+    };
+    let mut seed = None;
+    let mut json = false;
+    let mut expr_parts = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" | "--samples" => match args.next().map(|s| s.parse::<u64>()) {
+                Some(Ok(n)) if n > 0 => samples = n,
+                _ => {
+                    eprintln!("{} requires a positive integer", arg);
+                    return 1;
+                }
+            },
+            "--threads" => match args.next().map(|s| s.parse::<usize>()) {
+                Some(Ok(n)) if n > 0 => sampling.threads = n,
+                _ => {
+                    eprintln!("--threads requires a positive integer");
+                    return 1;
+                }
+            },
+            "--seed" => match args.next().map(|s| s.parse::<u64>()) {
+                Some(Ok(n)) => seed = Some(n),
+                _ => {
+                    eprintln!("--seed requires a non-negative integer");
+                    return 1;
+                }
+            },
+            "--antithetic" => sampling.method = Method::Antithetic,
+            "--at-least" => match args.next().map(|s| s.parse::<i32>()) {
+                Some(Ok(n)) => sampling.at_least = Some(n),
+                _ => {
+                    eprintln!("--at-least requires a number");
+                    return 1;
+                }
+            },
+            "--json" => json = true,
+            _ => expr_parts.push(arg.to_lowercase()),
+        }
+    }
+
+    if expr_parts.is_empty() {
+        eprintln!(
+            "Usage: roll sim [-n <samples>] [--seed <n>] [--threads <n>] [--antithetic]\n\
+             \x20               [--at-least <n>] [--json] <expr>\n\n\
+             Options:\n\
+             \t-n, --samples <n>   Number of rolls to simulate (default {})\n\
+             \t--seed <n>          Seed the run, to repeat it exactly on any machine\n\
+             \t--threads <n>       Worker threads (default: available cores)\n\
+             \t--antithetic        Roll in pairs, the second with every die flipped,\n\
+             \t                    to settle the mean and chances with fewer samples\n\
+             \t--at-least <n>      Also estimate the chance of a total of n or more\n\
+             \t--json              Print the summary as JSON",
+            DEFAULT_SAMPLES
+        );
+        return 1;
+    }
+
+    let mut ast = match parse(&expr_parts.join(" ")) {
+        Ok(ast) if ast.roots().len() == 1 => ast,
+        Ok(_) => {
+            eprintln!("roll sim takes a single expression");
+            return 1;
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    fold_all(&mut ast);
+    let program = Program::compile(&ast, ast.roots()[0]);
+
+    // On Ctrl-C, stop at the next chunk and report what we have so far.
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let handler_flag = Arc::clone(&interrupted);
+    let _ = ctrlc::set_handler(move || handler_flag.store(true, Ordering::Relaxed));
+
+    // Every run is seeded, so the one printed reproduces it whatever the
+    // thread count.
+    let seed = seed.unwrap_or_else(|| fastrand::u64(..));
+    fastrand::seed(seed);
+
+    let mut progress = Progress::new(samples, !json);
+    let summary = simulate_with(&program, samples, &sampling, |done| {
+        progress.update(done);
+        if interrupted.load(Ordering::Relaxed) {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    progress.finish();
+
+    let interrupted = interrupted.load(Ordering::Relaxed);
+    if json {
+        print_json(
+            &ast.to_string(),
+            &summary,
+            seed,
+            sampling.method,
+            interrupted,
+        );
+    } else {
+        print_text(
+            &ast.to_string(),
+            &summary,
+            seed,
+            sampling.method,
+            interrupted,
+        );
+    }
+    if interrupted { 130 } else { 0 }
+}
+
+fn print_text(expr: &str, summary: &Summary, seed: u64, method: Method, interrupted: bool) {
+    let mut note = String::new();
+    if method == Method::Antithetic {
+        note.push_str(", antithetic pairs");
+    }
+    if interrupted {
+        note.push_str(", interrupted");
+    }
+    println!("{} ({} samples{})", expr, summary.count, note);
+    println!("seed     {}", seed);
+    if summary.count == 0 {
+        println!("no samples finished");
+        return;
+    }
+    println!("mean     {:.3}", summary.mean());
+    let margin = Z_95 * summary.std_error();
+    println!(
+        "95% CI   {:.3} to {:.3}",
+        summary.mean() - margin,
+        summary.mean() + margin
+    );
+    println!("std dev  {:.3}", summary.std_dev());
+    println!("min      {}", summary.min);
+    println!("max      {}", summary.max);
+    if let Some((threshold, chance)) = summary.at_least() {
+        println!(
+            "{:<9}{:.2}% ± {:.2}%",
+            format!("{}+", threshold),
+            100.0 * chance.value(),
+            100.0 * chance.std_error()
+        );
+    }
+}
+
+fn print_json(expr: &str, summary: &Summary, seed: u64, method: Method, interrupted: bool) {
+    // With no samples there are no statistics, only nulls.
+    let value = |v: String| {
+        if summary.count == 0 {
+            "null".to_string()
+        } else {
+            v
+        }
+    };
+    let margin = Z_95 * summary.std_error();
+    let at_least = match summary.at_least() {
+        Some((threshold, chance)) => format!(
+            ",\"at_least\":{{\"total\":{},\"chance\":{},\"std_error\":{}}}",
+            threshold,
+            value(chance.value().to_string()),
+            value(chance.std_error().to_string())
+        ),
+        None => String::new(),
+    };
+    let ci95 = format!("[{},{}]", summary.mean() - margin, summary.mean() + margin);
+    println!(
+        "{{\"expression\":\"{}\",\"samples\":{},\"seed\":{},\"method\":\"{}\",\"interrupted\":{},\"mean\":{},\"ci95\":{},\"std_dev\":{},\"min\":{},\"max\":{}{}}}",
+        expr,
+        summary.count,
+        seed,
+        method.name(),
+        interrupted,
+        value(summary.mean().to_string()),
+        value(ci95),
+        value(summary.std_dev().to_string()),
+        value(summary.min.to_string()),
+        value(summary.max.to_string()),
+        at_least
+    );
+}
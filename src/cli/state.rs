@@ -0,0 +1,39 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where state that outlives one invocation (shuffled decks, bags) is kept:
+/// `$REROLL_DATA_DIR` if set, otherwise `reroll` in the platform's data
+/// directory.
+pub fn data_dir() -> Result<PathBuf, String> {
+    let var = |name| {
+        env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    if let Some(dir) = var("REROLL_DATA_DIR") {
+        return Ok(dir);
+    }
+    let base = if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_DATA_HOME").or_else(|| var("HOME").map(|home| home.join(".local/share")))
+    };
+    base.map(|dir| dir.join("reroll"))
+        .ok_or_else(|| "cannot find a data directory; set REROLL_DATA_DIR".to_string())
+}
+
+/// Replaces `path` with `contents`, creating its directory if needed. The
+/// file is written beside it and renamed into place, so an interrupted
+/// write never leaves it half-written.
+pub fn write(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
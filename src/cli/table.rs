@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use reroll::table::{Table, expand};
+
+/// `roll table`: rolls on a table file and prints the matched entry, with
+/// dice and references to other tables expanded.
+pub fn run(args: &[String]) -> i32 {
+    let mut verbose = false;
+    let mut path = None;
+
+    for arg in args {
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            _ => path = Some(arg),
+        }
+    }
+
+    let Some(path) = path else {
+        eprintln!(
+            "Usage: roll table [-v] <file>\n\n\
+             Each line is `<range>: <entry>` (e.g. `01-50: nothing`) or\n\
+             `w<weight>: <entry>`; .csv files use `,` instead of `:`.\n\
+             Entries may embed dice (`{{2d6*10}} gold`) and roll on other\n\
+             tables in the same directory (`@gems` reads gems.txt or gems.csv)."
+        );
+        return 1;
+    };
+
+    let path = Path::new(path);
+    let table = match load(path) {
+        Ok(table) => table,
+        Err(err) => {
+            eprintln!("{}: {}", path.display(), err);
+            return 1;
+        }
+    };
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut loaded: HashMap<String, Table> = HashMap::new();
+    let mut resolve = |name: &str| {
+        if let Some(table) = loaded.get(name) {
+            return Ok(table.clone());
+        }
+        let table = find(dir, name)?;
+        loaded.insert(name.to_string(), table.clone());
+        Ok(table)
+    };
+
+    let (value, entry) = table.roll();
+    if verbose {
+        println!("d{}: {}", table.sides(), value);
+    }
+    match expand(entry, &mut resolve) {
+        Ok(text) => {
+            println!("{}", text);
+            0
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+pub fn load(path: &Path) -> Result<Table, String> {
+    let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let csv = path.extension().is_some_and(|ext| ext == "csv");
+    Table::parse(&source, csv)
+}
+
+/// Loads the table `@name` refers to from `dir`.
+fn find(dir: &Path, name: &str) -> Result<Table, String> {
+    for file in [
+        format!("{}.txt", name),
+        format!("{}.csv", name),
+        name.to_string(),
+    ] {
+        let path = dir.join(file);
+        if path.is_file() {
+            return load(&path).map_err(|err| format!("{}: {}", path.display(), err));
+        }
+    }
+    Err(format!("no table named @{} in {}", name, dir.display()))
+}
@@ -0,0 +1,245 @@
+use std::io;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, List, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use reroll::ast::Ast;
+use reroll::eval::{TieBreak, start_tally, take_tally};
+use reroll::lint::{Severity, lint};
+use reroll::macros::apply_notation;
+use reroll::optimize::fold_all;
+use reroll::parser::parse;
+
+use crate::cli::config::{self, Config};
+use crate::cli::dist::{bar_chart, chances};
+use crate::cli::history::{self, Rolled};
+use crate::cli::roll::{Options, render};
+
+/// Samples used to preview expressions with no exact distribution.
+const PREVIEW_SAMPLES: u64 = 10_000;
+
+/// The dashboard's state between frames.
+struct App {
+    config: Config,
+    options: Options,
+    /// Macros without parameters, by name and body.
+    quick: Vec<(String, String)>,
+    selected: ListState,
+    /// Earlier rolls, oldest first, as `expression: results`.
+    history: Vec<String>,
+    input: String,
+    /// The last error, shown under the input until the next key.
+    error: Option<String>,
+}
+
+/// `roll tui`: a full-screen dashboard with the config's macros as quick
+/// rolls, the roll history and a preview of the chances of whatever
+/// expression is typed or picked.
+pub fn run(args: &[String]) -> i32 {
+    if !args.is_empty() {
+        eprintln!(
+            "Usage: roll tui\n\n\
+             Type an expression and press Enter to roll it, or pick a macro from\n\
+             the config with the arrow keys and press Enter on an empty line.\n\
+             Esc clears the line, or quits when it is empty."
+        );
+        return 1;
+    }
+
+    let app = config::load()
+        .and_then(|config| config.activate(config::profile_name(None).as_deref()))
+        .and_then(App::new);
+    let mut app = match app {
+        Ok(app) => app,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+impl App {
+    fn new(config: Config) -> Result<App, String> {
+        let options = Options {
+            verbose: config.defaults.verbose.unwrap_or(false),
+            ladder: config.defaults.ladder.unwrap_or(false),
+            ties: config.defaults.ties.unwrap_or(TieBreak::Draw),
+        };
+        let quick: Vec<(String, String)> = config
+            .macros
+            .iter()
+            .filter(|m| m.params.is_empty())
+            .map(|m| (m.name.clone(), m.body.clone()))
+            .collect();
+        let history = history::load()?
+            .into_iter()
+            .map(|rolled| format!("{}: {}", rolled.expression, rolled.results.join(", ")))
+            .collect();
+        let mut selected = ListState::default();
+        if !quick.is_empty() {
+            selected.select(Some(0));
+        }
+        Ok(App {
+            config,
+            options,
+            quick,
+            selected,
+            history,
+            input: String::new(),
+            error: None,
+        })
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            self.error = None;
+            match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(());
+                }
+                KeyCode::Esc if self.input.is_empty() => return Ok(()),
+                KeyCode::Esc => self.input.clear(),
+                KeyCode::Char(c) => self.input.push(c),
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Up => self.selected.select_previous(),
+                KeyCode::Down => self.selected.select_next(),
+                KeyCode::Enter => {
+                    if let Err(err) = self.roll() {
+                        self.error = Some(err);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The expression the preview and Enter act on: the typed one, or else
+    /// the picked macro.
+    fn current(&self) -> Option<&str> {
+        if !self.input.trim().is_empty() {
+            return Some(&self.input);
+        }
+        let i = self.selected.selected()?;
+        self.quick.get(i).map(|(name, _)| name.as_str())
+    }
+
+    fn parse_current(&self) -> Result<Option<Ast>, String> {
+        let Some(input) = self.current() else {
+            return Ok(None);
+        };
+        let input = apply_notation(&input.to_lowercase(), &self.config.notation);
+        let input = self.config.macros.expand(&input)?;
+        // The preview rolls as the user types, so half-typed expressions
+        // such as `4d6k` must not reach the roller.
+        if let Some(problem) = lint(&input)
+            .into_iter()
+            .find(|p| p.severity == Severity::Error)
+        {
+            return Err(problem.message);
+        }
+        let mut ast = parse(&input)?;
+        fold_all(&mut ast);
+        Ok(Some(ast))
+    }
+
+    fn roll(&mut self) -> Result<(), String> {
+        let Some(ast) = self.parse_current()? else {
+            return Ok(());
+        };
+        let keep = self.config.defaults.history.unwrap_or(false);
+        if keep {
+            start_tally();
+        }
+        let results = render(&ast, &self.options);
+        if keep {
+            history::record(&Rolled::new(
+                None,
+                ast.to_string(),
+                results.clone(),
+                take_tally(),
+            ))?;
+        }
+        self.history
+            .push(format!("{}: {}", ast, results.join(", ")));
+        self.input.clear();
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [panes, input] =
+            Layout::vertical([Constraint::Min(5), Constraint::Length(3)]).areas(frame.area());
+        let [quick, history, preview] = Layout::horizontal([
+            Constraint::Percentage(25),
+            Constraint::Percentage(40),
+            Constraint::Percentage(35),
+        ])
+        .areas(panes);
+
+        let names: Vec<String> = self
+            .quick
+            .iter()
+            .map(|(name, body)| format!("{} = {}", name, body))
+            .collect();
+        let list = List::new(names)
+            .block(Block::bordered().title(" Quick rolls "))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, quick, &mut self.selected);
+
+        let rows = history.height.saturating_sub(2) as usize;
+        let recent = &self.history[self.history.len().saturating_sub(rows)..];
+        frame.render_widget(
+            Paragraph::new(recent.join("\n")).block(Block::bordered().title(" History ")),
+            history,
+        );
+
+        let chart = match self.parse_current() {
+            Ok(Some(ast)) => {
+                let bars = chances(&ast, PREVIEW_SAMPLES).map(|(chances, _)| {
+                    let rows = preview.height.saturating_sub(2) as usize;
+                    let width = preview.width.saturating_sub(17) as usize;
+                    bar_chart(&chances, rows, width)
+                });
+                bars.unwrap_or_else(|| "one expression at a time".to_string())
+            }
+            Ok(None) => String::new(),
+            Err(_) => "not a valid expression yet".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(chart).block(Block::bordered().title(" Chances ")),
+            preview,
+        );
+
+        let status = match &self.error {
+            Some(err) => format!(" {} ", err.lines().next().unwrap_or("")),
+            None => " Enter rolls, Esc quits ".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(format!("> {}", self.input))
+                .block(Block::bordered().title(" Roll ").title_bottom(status)),
+            input,
+        );
+        frame.set_cursor_position((input.x + 3 + self.input.len() as u16, input.y + 1));
+    }
+}
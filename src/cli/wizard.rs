@@ -0,0 +1,122 @@
+use std::io::{self, BufRead, Write};
+
+use reroll::ast::{Ast, DiceModifier, DiceModifierType};
+use reroll::eval::TieBreak;
+
+use crate::cli::roll::{Options, render};
+
+/// `roll wizard`: builds an expression from a few questions, rolls it and
+/// shows the notation for next time, to teach the syntax.
+pub fn run(args: &[String]) -> i32 {
+    if !args.is_empty() {
+        eprintln!(
+            "Usage: roll wizard\n\n\
+             Asks how many dice to roll, their sides, which to keep and what\n\
+             to add, then rolls them and prints the notation to type instead."
+        );
+        return 1;
+    }
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut ask = |question: &str, default: &str| -> Option<String> {
+        print!("{} [{}] ", question, default);
+        let _ = io::stdout().flush();
+        match lines.next() {
+            Some(Ok(line)) if line.trim().is_empty() => Some(default.to_string()),
+            Some(Ok(line)) => Some(line.trim().to_lowercase()),
+            _ => {
+                println!();
+                None
+            }
+        }
+    };
+
+    let Some(ast) = build(&mut ask) else {
+        return 1;
+    };
+    let options = Options {
+        verbose: true,
+        ladder: false,
+        ties: TieBreak::Draw,
+    };
+    println!();
+    println!("Rolled: {}", render(&ast, &options).join(", "));
+    println!("Next time, type: reroll \"{}\"", ast);
+    0
+}
+
+/// Asks the questions and builds the expression from the answers; `None`
+/// if the input ends first.
+fn build<F>(ask: &mut F) -> Option<Ast>
+where
+    F: FnMut(&str, &str) -> Option<String>,
+{
+    let count = ask_number(ask, "How many dice?", "1", 1..=1000)?;
+    let sides = ask_number(ask, "How many sides?", "20", 1..=1000)?;
+    let keep = if count > 1 {
+        let kind = loop {
+            match ask("Keep the (h)ighest, the (l)owest or (a)ll?", "a")?.as_str() {
+                "a" | "all" => break None,
+                "h" | "highest" => break Some(DiceModifierType::KeepHigh),
+                "l" | "lowest" => break Some(DiceModifierType::KeepLow),
+                _ => eprintln!("answer h, l or a"),
+            }
+        };
+        match kind {
+            Some(kind) => Some((
+                kind,
+                ask_number(ask, "How many to keep?", "1", 1..=count - 1)?,
+            )),
+            None => None,
+        }
+    } else {
+        None
+    };
+    let explode = sides > 1
+        && loop {
+            match ask("Roll again and add when a die shows its highest face?", "n")?.as_str() {
+                "y" | "yes" => break true,
+                "n" | "no" => break false,
+                _ => eprintln!("answer y or n"),
+            }
+        };
+    let bonus = ask_number(ask, "Add (or subtract) how much?", "0", -1000..=1000)?;
+
+    let mut ast = Ast::new();
+    let mut modifiers = Vec::new();
+    if let Some((kind, n)) = keep {
+        modifiers.push(DiceModifier::new(kind, Some(ast.number(n))));
+    }
+    if explode {
+        modifiers.push(DiceModifier::new(DiceModifierType::Explode, None));
+    }
+    let (count, sides) = (ast.number(count), ast.number(sides));
+    let mut root = ast.dice(count, sides, modifiers);
+    if bonus != 0 {
+        let op = if bonus < 0 { '-' } else { '+' };
+        let bonus = ast.number(bonus.abs());
+        root = ast.binary(root, op, bonus);
+    }
+    ast.push_root(root);
+    Some(ast)
+}
+
+/// Asks until the answer is a whole number in `range`; `None` at the end
+/// of input.
+fn ask_number<F>(
+    ask: &mut F,
+    question: &str,
+    default: &str,
+    range: std::ops::RangeInclusive<i32>,
+) -> Option<i32>
+where
+    F: FnMut(&str, &str) -> Option<String>,
+{
+    loop {
+        match ask(question, default)?.parse::<i32>() {
+            Ok(n) if range.contains(&n) => return Some(n),
+            _ => eprintln!("answer a number from {} to {}", range.start(), range.end()),
+        }
+    }
+}
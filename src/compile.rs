@@ -0,0 +1,368 @@
+use crate::ast::{
+    Annotation, Ast, Choice, Condition, DiceModifier, DiceModifierType, ExprId, ExprKind, Function,
+};
+use crate::eval::{
+    EvalResult, Rolls, apply_function, apply_op, choose, clamp_count, eval_annotation, pick,
+    reroll_while, roll, roll_fudge, tier_reached,
+};
+use crate::rng::{map_streams, sum_streams};
+
+/// An expression flattened into a postfix list of operations, for callers
+/// that evaluate the same expression many times (e.g. `roll sim`).
+///
+/// Running a program rolls exactly the same dice, in the same order, as
+/// [`eval_total`](crate::eval::eval_total) does for the source expression.
+#[derive(Debug)]
+pub struct Program {
+    ops: Vec<Op>,
+}
+
+#[derive(Debug)]
+enum Op {
+    Const(i32),
+    /// Pops sides, then count; pushes the total of the pool, or the value
+    /// read off it.
+    Dice(Vec<Modifier>, Option<Annotation>),
+    /// Pops a count; pushes the total of a Fudge pool, or the value read
+    /// off it.
+    Fudge(Vec<Modifier>, Option<Annotation>),
+    /// Pops the right operand, then the left; pushes the result.
+    Binary(char),
+    /// Pops the function's arguments, last first; pushes its result.
+    Call(Function),
+    /// Pushes the index of a weighted pick.
+    Choose(Vec<Choice>),
+    /// Pops a total; pushes the number of thresholds it reaches.
+    Tier(Vec<i32>),
+    /// Pops a count; pushes the total of running the body that many times,
+    /// or the value read off the results.
+    Repeat(Box<Program>, Vec<Modifier>, Option<Annotation>),
+    /// Pushes the total of the dice of every member program, or the value
+    /// read off them.
+    Group(Vec<Program>, Vec<Modifier>, Option<Annotation>),
+    /// Pushes the total of running the pool program until the condition no
+    /// longer holds, at most the limit more times.
+    Reroll(Box<Program>, Condition, u32),
+}
+
+#[derive(Debug)]
+struct Modifier {
+    kind: DiceModifierType,
+    value: Value,
+    faces: Vec<i32>,
+}
+
+#[derive(Debug)]
+enum Value {
+    Implicit,
+    Const(i32),
+    Dynamic(Program),
+}
+
+impl Program {
+    pub fn compile(ast: &Ast, id: ExprId) -> Program {
+        let mut ops = Vec::new();
+        compile_into(ast, id, &mut ops);
+        Program { ops }
+    }
+
+    /// Runs the program and returns its total.
+    pub fn total(&self) -> i32 {
+        self.run(&mut Vec::new())
+    }
+
+    /// Like [`total`](Self::total), but reuses `stack` across runs to avoid
+    /// allocating in hot loops.
+    pub fn run(&self, stack: &mut Vec<i32>) -> i32 {
+        stack.clear();
+        for op in &self.ops {
+            let value = op.run(stack);
+            stack.push(value);
+        }
+        stack.pop().unwrap()
+    }
+
+    /// Runs the program like [`eval_expr`](crate::eval::eval_expr) would
+    /// evaluate it: the dice of its final pool, after that pool's
+    /// modifiers, or its total if it does not end in one.
+    fn result(&self) -> EvalResult {
+        let mut stack = Vec::new();
+        let (last, ops) = self.ops.split_last().unwrap();
+        for op in ops {
+            let value = op.run(&mut stack);
+            stack.push(value);
+        }
+        match last {
+            Op::Dice(modifiers, None) => {
+                let sides = stack.pop().unwrap();
+                let count = stack.pop().unwrap();
+                let rolls = (0..count).map(|_| roll(sides)).collect();
+                pool(rolls, modifiers, Some(sides))
+            }
+            Op::Fudge(modifiers, None) => {
+                let count = stack.pop().unwrap();
+                let rolls = (0..count).map(|_| roll_fudge()).collect();
+                pool(rolls, modifiers, None)
+            }
+            Op::Repeat(body, modifiers, None) => {
+                let count = clamp_count(stack.pop().unwrap(), usize::MAX);
+                let results = Rolls::from_vec(map_streams(count, |_| body.total()));
+                pool(results, modifiers, None)
+            }
+            Op::Group(members, modifiers, None) => pool(group_rolls(members), modifiers, None),
+            Op::Reroll(body, condition, limit) => {
+                reroll_while(*condition, *limit, || body.result())
+            }
+            Op::Choose(choices) => pick(choices),
+            op => EvalResult::Number(op.run(&mut stack)),
+        }
+    }
+}
+
+impl Op {
+    /// Pops the operation's inputs off `stack` and returns its result.
+    fn run(&self, stack: &mut Vec<i32>) -> i32 {
+        match self {
+            Op::Const(n) => *n,
+            Op::Choose(choices) => choose(choices) as i32,
+            Op::Dice(modifiers, reading) => {
+                let sides = stack.pop().unwrap();
+                let count = stack.pop().unwrap();
+                if modifiers.is_empty() && reading.is_none() {
+                    (0..count).map(|_| roll(sides)).sum()
+                } else {
+                    let rolls = (0..count).map(|_| roll(sides)).collect();
+                    apply_modifiers(rolls, modifiers, Some(sides), *reading)
+                }
+            }
+            Op::Fudge(modifiers, reading) => {
+                let count = stack.pop().unwrap();
+                if modifiers.is_empty() && reading.is_none() {
+                    (0..count).map(|_| roll_fudge()).sum()
+                } else {
+                    let rolls = (0..count).map(|_| roll_fudge()).collect();
+                    apply_modifiers(rolls, modifiers, None, *reading)
+                }
+            }
+            Op::Call(function) => {
+                let args = stack.split_off(stack.len() - function.arity());
+                apply_function(*function, &args)
+            }
+            Op::Tier(thresholds) => {
+                let total = stack.pop().unwrap();
+                tier_reached(total, thresholds) as i32
+            }
+            Op::Binary(op) => {
+                let rhs = stack.pop().unwrap();
+                let lhs = stack.pop().unwrap();
+                apply_op(lhs, *op, rhs)
+            }
+            Op::Repeat(body, modifiers, reading) => {
+                let count = clamp_count(stack.pop().unwrap(), usize::MAX);
+                if modifiers.is_empty() && reading.is_none() {
+                    sum_streams(count, || body.total())
+                } else {
+                    let results = Rolls::from_vec(map_streams(count, |_| body.total()));
+                    apply_modifiers(results, modifiers, None, *reading)
+                }
+            }
+            Op::Group(members, modifiers, reading) => {
+                apply_modifiers(group_rolls(members), modifiers, None, *reading)
+            }
+            Op::Reroll(body, condition, limit) => {
+                reroll_while(*condition, *limit, || body.result()).to_number()
+            }
+        }
+    }
+}
+
+fn group_rolls(members: &[Program]) -> Rolls {
+    let mut rolls = Rolls::new();
+    for member in members {
+        rolls.extend_from_slice(member.result().dice());
+    }
+    rolls
+}
+
+fn pool(rolls: Rolls, modifiers: &[Modifier], sides: Option<i32>) -> EvalResult {
+    let modifiers = modifiers.iter().map(|modifier| {
+        let value = match &modifier.value {
+            Value::Implicit => None,
+            Value::Const(n) => Some(*n),
+            Value::Dynamic(program) => Some(program.total()),
+        };
+        (modifier.kind, value, modifier.faces.as_slice())
+    });
+    crate::eval::apply_modifiers(rolls, modifiers, sides)
+}
+
+fn apply_modifiers(
+    rolls: Rolls,
+    modifiers: &[Modifier],
+    sides: Option<i32>,
+    reading: Option<Annotation>,
+) -> i32 {
+    let result = pool(rolls, modifiers, sides);
+    match reading {
+        Some(reading) => eval_annotation(&result, reading),
+        None => result.to_number(),
+    }
+}
+
+fn compile_into(ast: &Ast, id: ExprId, ops: &mut Vec<Op>) {
+    match &ast[id].kind {
+        ExprKind::Number(n) => ops.push(Op::Const(*n)),
+        ExprKind::Choose(choices) => ops.push(Op::Choose(choices.clone())),
+        ExprKind::Dice { .. }
+        | ExprKind::Fudge { .. }
+        | ExprKind::Group { .. }
+        | ExprKind::Repetition { .. } => compile_pool(ast, id, None, ops),
+        ExprKind::Read { pool, reading } => compile_pool(ast, *pool, Some(*reading), ops),
+        ExprKind::BinaryOp(lhs, op, rhs) => {
+            compile_into(ast, *lhs, ops);
+            compile_into(ast, *rhs, ops);
+            ops.push(Op::Binary(*op));
+        }
+        // A contest totals to the first side's margin.
+        ExprKind::Contest(first, second) => {
+            compile_into(ast, *first, ops);
+            compile_into(ast, *second, ops);
+            ops.push(Op::Binary('-'));
+        }
+        ExprKind::Call { function, args } => {
+            for &arg in args {
+                compile_into(ast, arg, ops);
+            }
+            ops.push(Op::Call(*function));
+        }
+        ExprKind::Reroll {
+            pool,
+            condition,
+            limit,
+        } => {
+            let body = Box::new(Program::compile(ast, *pool));
+            ops.push(Op::Reroll(body, *condition, *limit));
+        }
+        ExprKind::Tiers { expr, thresholds } => {
+            compile_into(ast, *expr, ops);
+            ops.push(Op::Tier(thresholds.clone()));
+        }
+    }
+}
+
+/// Compiles a pool of dice or repetitions, optionally with a value to read
+/// off it instead of its total.
+fn compile_pool(ast: &Ast, id: ExprId, reading: Option<Annotation>, ops: &mut Vec<Op>) {
+    match &ast[id].kind {
+        ExprKind::Dice {
+            count,
+            sides,
+            modifiers,
+        } => {
+            compile_into(ast, *count, ops);
+            compile_into(ast, *sides, ops);
+            ops.push(Op::Dice(compile_modifiers(ast, modifiers), reading));
+        }
+        ExprKind::Fudge { count, modifiers } => {
+            compile_into(ast, *count, ops);
+            ops.push(Op::Fudge(compile_modifiers(ast, modifiers), reading));
+        }
+        ExprKind::Repetition {
+            count,
+            expr,
+            modifiers,
+        } => {
+            compile_into(ast, *count, ops);
+            let body = Box::new(Program::compile(ast, *expr));
+            ops.push(Op::Repeat(body, compile_modifiers(ast, modifiers), reading));
+        }
+        ExprKind::Group { members, modifiers } => {
+            let members = members.iter().map(|&m| Program::compile(ast, m)).collect();
+            ops.push(Op::Group(
+                members,
+                compile_modifiers(ast, modifiers),
+                reading,
+            ));
+        }
+        _ => unreachable!("only pools can be read"),
+    }
+}
+
+fn compile_modifiers(ast: &Ast, modifiers: &[DiceModifier]) -> Vec<Modifier> {
+    modifiers
+        .iter()
+        .map(|m| Modifier {
+            kind: m.kind,
+            value: match m.value {
+                None => Value::Implicit,
+                Some(value) => match ast[value].kind {
+                    ExprKind::Number(n) => Value::Const(n),
+                    _ => Value::Dynamic(Program::compile(ast, value)),
+                },
+            },
+            faces: m.faces.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::eval_total;
+    use crate::parser::parse;
+
+    #[test]
+    fn matches_tree_evaluation() {
+        let inputs = [
+            "42",
+            "3d6 + 2 * 4",
+            "4d6kh3",
+            "10d6dl(1d4)",
+            "2d6!5",
+            "6k3 + 1",
+            "8d10wod",
+            "6d10!t7",
+            "10d10t7x",
+            "4dF + 3",
+            "5dFkh2",
+            "5(1d20 + 2)kl2",
+            "(1d4)(2d6) - 3",
+            "1d20 + 5 vs 1d20 + 3",
+            "1d20 + 7 vs 10/15/20",
+            "6d6!{1,6}",
+            "4d6ra1 + 2d6ra{5,6}",
+            "1d20r + 2d6ro2 + 4d6r{1,2}kh3",
+            "10d6kh(1d4) + 6d6!(2d3 + 2)",
+            "half(8d6) + half(3d6, up) + double(1d4)",
+            "clamp(1d20 - 5, 1, 1d20)",
+            "between(5, 25) + d[37..213] + between(2d6, 1)",
+            "6d6straight + 5d6kh3distinct + 4(1d4)straight + 4dFdistinct",
+            "6d6h - 3(1d8)l",
+            "{1d12, 2d6}kh1 + {1d20, 1d4 + 2, 3(1d6)kh2}dl1",
+            "{2d6!, 4dF, 1d8h}h + {1d10, 1d6}t5",
+            "10(1d20 + 5) >= 15 + 8d6 < 3",
+            "choose{a:3, b, c} + {1d6, choose{x, y:9}}kh1",
+            "4d6rr(sum<12) + 6(4d6kh3)rr(sum<75, 2) + {1d6, 3d4rr(max<4)}h",
+        ];
+        for input in inputs {
+            let ast = parse(input).unwrap();
+            let root = ast.roots()[0];
+            let program = Program::compile(&ast, root);
+
+            fastrand::seed(99);
+            let expected = eval_total(&ast, root);
+            fastrand::seed(99);
+            assert_eq!(program.total(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn constant_modifier_values_are_resolved() {
+        let ast = parse("4d6kh3").unwrap();
+        let program = Program::compile(&ast, ast.roots()[0]);
+        let Some(Op::Dice(modifiers, _)) = program.ops.last() else {
+            panic!("Expected dice op");
+        };
+        assert!(matches!(modifiers[0].value, Value::Const(3)));
+    }
+}
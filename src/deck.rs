@@ -0,0 +1,200 @@
+//! Decks of cards drawn without replacement.
+//!
+//! Unlike dice, a deck remembers what has been drawn, so a deck can be
+//! saved between draws with [`Deck::to_text`] and restored with
+//! [`Deck::parse`]. Both use the same format as user-defined decks: one
+//! card per line, top card first, with blank lines and `#` comments
+//! ignored.
+//!
+//! A bag of tokens is a deck too, built from counts with
+//! [`Deck::from_counts`] and reshuffled before every draw.
+
+const SUITS: [&str; 4] = ["♠", "♥", "♦", "♣"];
+const RANKS: [&str; 13] = [
+    "A", "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K",
+];
+const MAJOR_ARCANA: [&str; 22] = [
+    "The Fool",
+    "The Magician",
+    "The High Priestess",
+    "The Empress",
+    "The Emperor",
+    "The Hierophant",
+    "The Lovers",
+    "The Chariot",
+    "Strength",
+    "The Hermit",
+    "Wheel of Fortune",
+    "Justice",
+    "The Hanged Man",
+    "Death",
+    "Temperance",
+    "The Devil",
+    "The Tower",
+    "The Star",
+    "The Moon",
+    "The Sun",
+    "Judgement",
+    "The World",
+];
+const MINOR_SUITS: [&str; 4] = ["Wands", "Cups", "Swords", "Pentacles"];
+const MINOR_RANKS: [&str; 14] = [
+    "Ace", "Two", "Three", "Four", "Five", "Six", "Seven", "Eight", "Nine", "Ten", "Page",
+    "Knight", "Queen", "King",
+];
+
+/// The names accepted by [`Deck::builtin`].
+pub const BUILTIN: [&str; 3] = ["standard52", "standard54", "tarot"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deck {
+    /// The cards left, top card last so drawing pops from the end.
+    cards: Vec<String>,
+}
+
+impl Deck {
+    /// An unshuffled built-in deck: `standard52`, `standard54` (with two
+    /// jokers) or `tarot`.
+    pub fn builtin(name: &str) -> Option<Deck> {
+        let cards: Vec<String> = match name {
+            "standard52" | "standard54" => {
+                let mut cards: Vec<String> = SUITS
+                    .iter()
+                    .flat_map(|suit| RANKS.iter().map(move |rank| format!("{}{}", rank, suit)))
+                    .collect();
+                if name == "standard54" {
+                    cards.extend(["Joker".to_string(), "Joker".to_string()]);
+                }
+                cards
+            }
+            "tarot" => MAJOR_ARCANA
+                .iter()
+                .map(|card| card.to_string())
+                .chain(MINOR_SUITS.iter().flat_map(|suit| {
+                    MINOR_RANKS
+                        .iter()
+                        .map(move |rank| format!("{} of {}", rank, suit))
+                }))
+                .collect(),
+            _ => return None,
+        };
+        Some(Deck::from_top(cards))
+    }
+
+    /// Reads a deck, top card first.
+    pub fn parse(source: &str) -> Deck {
+        let cards = source
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+        Deck::from_top(cards)
+    }
+
+    /// Reads counted contents such as `3 red, 2 blue, skull`.
+    pub fn from_counts(spec: &str) -> Result<Deck, String> {
+        let mut cards = Vec::new();
+        for item in spec.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            let (count, name) = match item.split_once(' ') {
+                Some((count, name)) if count.bytes().all(|b| b.is_ascii_digit()) => {
+                    let count = count.parse::<usize>().map_err(|e| e.to_string())?;
+                    (count, name.trim())
+                }
+                _ => (1, item),
+            };
+            cards.extend(std::iter::repeat_n(name.to_string(), count));
+        }
+        Ok(Deck::from_top(cards))
+    }
+
+    fn from_top(mut cards: Vec<String>) -> Deck {
+        cards.reverse();
+        Deck { cards }
+    }
+
+    /// Writes the cards left in a form [`Deck::parse`] reads back.
+    pub fn to_text(&self) -> String {
+        self.cards
+            .iter()
+            .rev()
+            .map(|card| format!("{}\n", card))
+            .collect()
+    }
+
+    pub fn shuffle(&mut self) {
+        fastrand::shuffle(&mut self.cards);
+    }
+
+    /// Draws up to `count` cards from the top; fewer if the deck runs out.
+    pub fn draw(&mut self, count: usize) -> Vec<String> {
+        let left = self.cards.len().saturating_sub(count);
+        self.cards.drain(left..).rev().collect()
+    }
+
+    /// Counts the cards left by name, e.g. `3 red, 1 blue`.
+    pub fn summary(&self) -> String {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for card in self.cards.iter().rev() {
+            match counts.iter_mut().find(|(name, _)| name == card) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((card, 1)),
+            }
+        }
+        let counts: Vec<String> = counts
+            .iter()
+            .map(|(name, count)| format!("{} {}", count, name))
+            .collect();
+        counts.join(", ")
+    }
+
+    pub fn len(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_decks() {
+        assert_eq!(Deck::builtin("standard52").unwrap().len(), 52);
+        assert_eq!(Deck::builtin("standard54").unwrap().len(), 54);
+        assert_eq!(Deck::builtin("tarot").unwrap().len(), 78);
+        assert_eq!(Deck::builtin("uno"), None);
+
+        let mut deck = Deck::builtin("standard52").unwrap();
+        assert_eq!(deck.draw(2), vec!["A♠", "2♠"]);
+    }
+
+    #[test]
+    fn bags_from_counts() {
+        let mut bag = Deck::from_counts("3 red, 2 blue, skull").unwrap();
+        assert_eq!(bag.len(), 6);
+        assert_eq!(bag.summary(), "3 red, 2 blue, 1 skull");
+        bag.shuffle();
+        let drawn = bag.draw(6);
+        assert_eq!(drawn.iter().filter(|t| *t == "red").count(), 3);
+        assert_eq!(bag.summary(), "");
+    }
+
+    #[test]
+    fn draws_without_replacement_and_round_trips() {
+        let mut deck = Deck::parse("# the deck\nSun\n\nMoon\nStar\nComet\n");
+        deck.shuffle();
+        let drawn = deck.draw(3);
+        assert_eq!(deck.len(), 1);
+
+        let mut restored = Deck::parse(&deck.to_text());
+        assert_eq!(restored, deck);
+        let last = restored.draw(5);
+        assert_eq!(last.len(), 1);
+        assert!(!drawn.contains(&last[0]));
+        assert!(restored.is_empty());
+    }
+}
@@ -0,0 +1,647 @@
+use crate::ast::{
+    Annotation, Ast, DEFAULT_REROLL_LIMIT, DiceModifier, DiceModifierType, ExprId, ExprKind,
+    Function,
+};
+use crate::eval::{apply_function, apply_op, clamp_count};
+
+/// The most outcome pairs one step of [`exact`] will combine before giving
+/// up, so a huge pool falls back to simulation instead of hanging.
+const MAX_WORK: usize = 10_000_000;
+
+/// The widest range of totals a distribution will cover, so `1d6 * 100000000`
+/// falls back to simulation instead of allocating gigabytes.
+const MAX_SPAN: i64 = 1_000_000;
+
+/// The exact chance of each total an expression can roll.
+///
+/// Distributions combine like the rolls they describe ([`convolve`] adds
+/// two, [`max_with`] keeps the better, [`affine`] scales and shifts), for
+/// analyses the expression syntax cannot state directly.
+///
+/// [`convolve`]: Distribution::convolve
+/// [`max_with`]: Distribution::max_with
+/// [`affine`]: Distribution::affine
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    /// The lowest total.
+    min: i32,
+    /// `probs[i]` is the chance of totalling `min + i`.
+    probs: Vec<f64>,
+}
+
+impl Distribution {
+    /// Always `n`.
+    pub fn constant(n: i32) -> Distribution {
+        Distribution {
+            min: n,
+            probs: vec![1.0],
+        }
+    }
+
+    /// The distribution of the expression `id` in `ast`, such as
+    /// `4d6kh3 + 2`, if [`exact`] can work it out.
+    pub fn of(ast: &Ast, id: ExprId) -> Option<Distribution> {
+        exact(ast, id)
+    }
+
+    /// Each total from `low` to `high` equally likely, as on one die, or
+    /// `None` if they span more than [`MAX_SPAN`].
+    pub fn uniform(low: i32, high: i32) -> Option<Distribution> {
+        let (low, high) = (low.min(high), low.max(high));
+        let len = i64::from(high) - i64::from(low) + 1;
+        if len > MAX_SPAN {
+            return None;
+        }
+        Some(Distribution {
+            min: low,
+            probs: vec![1.0 / len as f64; len as usize],
+        })
+    }
+
+    pub fn min(&self) -> i32 {
+        self.min
+    }
+
+    pub fn max(&self) -> i32 {
+        self.min + self.probs.len() as i32 - 1
+    }
+
+    /// The chance of totalling exactly `value`.
+    pub fn probability(&self, value: i32) -> f64 {
+        usize::try_from(value - self.min)
+            .ok()
+            .and_then(|i| self.probs.get(i))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Each total with its chance, lowest first, skipping impossible ones.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, f64)> + '_ {
+        (self.min..)
+            .zip(self.probs.iter().copied())
+            .filter(|&(_, p)| p > 0.0)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.iter().map(|(value, p)| value as f64 * p).sum()
+    }
+
+    /// The distribution of `f(a, b)` for independent totals `a` from `self`
+    /// and `b` from `other`, or `None` if that is too much work.
+    pub fn combine(
+        &self,
+        other: &Distribution,
+        f: impl Fn(i32, i32) -> i32,
+    ) -> Option<Distribution> {
+        if self.probs.len().saturating_mul(other.probs.len()) > MAX_WORK {
+            return None;
+        }
+        let mut outcomes = Vec::new();
+        for (a, pa) in self.iter() {
+            for (b, pb) in other.iter() {
+                outcomes.push((f(a, b), pa * pb));
+            }
+        }
+        Distribution::from_outcomes(outcomes)
+    }
+
+    /// The sum of independent totals from `self` and `other`.
+    pub fn convolve(&self, other: &Distribution) -> Option<Distribution> {
+        self.combine(other, |a, b| a + b)
+    }
+
+    /// The higher of independent totals from `self` and `other`.
+    pub fn max_with(&self, other: &Distribution) -> Option<Distribution> {
+        self.combine(other, i32::max)
+    }
+
+    /// The lower of independent totals from `self` and `other`.
+    pub fn min_with(&self, other: &Distribution) -> Option<Distribution> {
+        self.combine(other, i32::min)
+    }
+
+    /// The distribution of `f(total)`, or `None` if its totals spread too
+    /// far apart.
+    pub fn map(&self, f: impl Fn(i32) -> i32) -> Option<Distribution> {
+        Distribution::from_outcomes(self.iter().map(|(value, p)| (f(value), p)).collect())
+    }
+
+    /// Each total multiplied by `scale`, plus `offset`, or `None` if that
+    /// spreads them too far apart.
+    pub fn affine(&self, scale: i32, offset: i32) -> Option<Distribution> {
+        self.map(|value| value * scale + offset)
+    }
+
+    /// Each total negated.
+    fn negate(&self) -> Distribution {
+        Distribution {
+            min: -self.max(),
+            probs: self.probs.iter().rev().copied().collect(),
+        }
+    }
+
+    /// Gathers totals and their chances, or `None` if they span more than
+    /// [`MAX_SPAN`].
+    fn from_outcomes(outcomes: Vec<(i32, f64)>) -> Option<Distribution> {
+        let min = outcomes.iter().map(|&(v, _)| v).min().unwrap_or(0);
+        let max = outcomes.iter().map(|&(v, _)| v).max().unwrap_or(0);
+        if max as i64 - min as i64 >= MAX_SPAN {
+            return None;
+        }
+        let mut probs = vec![0.0; (max - min) as usize + 1];
+        for (value, p) in outcomes {
+            probs[(value - min) as usize] += p;
+        }
+        Some(Distribution { min, probs })
+    }
+
+    /// The highest of `count` independent rolls of `self`, from the chance
+    /// that all of them are at most each total; 0 if there are none.
+    pub fn highest_of(&self, count: i32) -> Distribution {
+        if count <= 0 {
+            return Distribution::constant(0);
+        }
+        let mut below = 0.0;
+        let probs = self
+            .probs
+            .iter()
+            .map(|p| {
+                let before = below;
+                below += p;
+                below.powi(count) - f64::powi(before, count)
+            })
+            .collect();
+        Distribution {
+            min: self.min,
+            probs,
+        }
+    }
+
+    /// The lowest of `count` independent rolls of `self`; 0 if there are
+    /// none.
+    pub fn lowest_of(&self, count: i32) -> Distribution {
+        self.negate().highest_of(count).negate()
+    }
+
+    /// The sum of the highest `keep` of `count` independent rolls of
+    /// `self`, or `None` if that is too much work.
+    ///
+    /// Goes through the totals from the highest down, tracking how many
+    /// rolls have come out at or above the current one and the sum of those
+    /// kept so far: given that a roll is at most some total, it is exactly
+    /// that total with a fixed chance, so how many of the rest land on it is
+    /// binomial.
+    pub fn keep_highest(&self, count: i32, keep: i32) -> Option<Distribution> {
+        let count = clamp_count(count, usize::MAX);
+        let keep = clamp_count(keep, count) as i64;
+        let (min, max) = (self.min as i64, self.max() as i64);
+        let (low, high) = ((keep * min).min(0), (keep * max).max(0));
+        let width = high - low + 1;
+        if width > MAX_SPAN
+            || self
+                .probs
+                .len()
+                .saturating_mul((count + 1).saturating_mul(count + 1))
+                .saturating_mul(width as usize)
+                > MAX_WORK
+        {
+            return None;
+        }
+        let width = width as usize;
+
+        // binomial[n][k] is n choose k.
+        let mut binomial = vec![vec![1.0f64]];
+        for n in 1..=count {
+            let last = &binomial[n - 1];
+            let row = (0..=n)
+                .map(|k| {
+                    let left = if k > 0 { last[k - 1] } else { 0.0 };
+                    left + last.get(k).copied().unwrap_or(0.0)
+                })
+                .collect();
+            binomial.push(row);
+        }
+
+        // states[seen][sum - low]: the chance that `seen` rolls came out at
+        // or above the current total, the kept ones summing to `sum`.
+        let mut states = vec![vec![0.0; width]; count + 1];
+        states[0][(-low) as usize] = 1.0;
+        let mut rest = 1.0;
+        let totals: Vec<_> = self.iter().collect();
+        for (i, &(value, p)) in totals.iter().enumerate().rev() {
+            let q = if i == 0 { 1.0 } else { (p / rest).min(1.0) };
+            rest -= p;
+            let mut next = vec![vec![0.0; width]; count + 1];
+            for (seen, sums) in states.iter().enumerate() {
+                let left = count - seen;
+                let open = (keep as usize).saturating_sub(seen);
+                for (at, &chance) in sums.iter().enumerate().filter(|&(_, &c)| c > 0.0) {
+                    for here in 0..=left {
+                        let p = binomial[left][here]
+                            * q.powi(here as i32)
+                            * (1.0 - q).powi((left - here) as i32);
+                        let kept = here.min(open) as i64 * value as i64;
+                        next[seen + here][(at as i64 + kept) as usize] += chance * p;
+                    }
+                }
+            }
+            states = next;
+        }
+
+        let outcomes = (low..)
+            .zip(&states[count])
+            .filter(|&(_, &p)| p > 0.0)
+            .map(|(sum, &p)| (sum as i32, p))
+            .collect();
+        Distribution::from_outcomes(outcomes)
+    }
+
+    /// The sum of the lowest `keep` of `count` independent rolls of `self`,
+    /// or `None` if that is too much work.
+    pub fn keep_lowest(&self, count: i32, keep: i32) -> Option<Distribution> {
+        Some(self.negate().keep_highest(count, keep)?.negate())
+    }
+
+    /// Rolls again while `holds` is true of the total, at most `limit`
+    /// times, keeping the last roll.
+    pub fn reroll_while(&self, holds: impl Fn(i32) -> bool, limit: u32) -> Distribution {
+        let q: f64 = self.iter().filter(|&(v, _)| holds(v)).map(|(_, p)| p).sum();
+        // The chance of getting to each roll, summed over the rolls.
+        let tries = if q < 1.0 {
+            (1.0 - q.powf(limit as f64 + 1.0)) / (1.0 - q)
+        } else {
+            limit as f64 + 1.0
+        };
+        let kept = q.powf(limit as f64);
+        let probs = (self.min..)
+            .zip(&self.probs)
+            .map(|(value, p)| if holds(value) { p * kept } else { p * tries })
+            .collect();
+        Distribution {
+            min: self.min,
+            probs,
+        }
+    }
+
+    /// The sum of `count` independent rolls of `self`, or `None` if that is
+    /// too much work. Sums of a power of two rolls double up, so a huge pool
+    /// gives up after a few steps rather than one roll at a time.
+    pub fn repeat(&self, count: i32) -> Option<Distribution> {
+        let mut total = Distribution::constant(0);
+        let mut power = self.clone();
+        let mut count = clamp_count(count, usize::MAX);
+        while count > 0 {
+            if count % 2 == 1 {
+                total = total.convolve(&power)?;
+            }
+            count /= 2;
+            if count > 0 {
+                power = power.convolve(&power)?;
+            }
+        }
+        Some(total)
+    }
+}
+
+/// The exact distribution of an expression's total, for expressions built
+/// from dice, arithmetic and functions, with `r`, `ro` and `ra` rerolls, a
+/// keep or drop at the end of a pool (`4d6kh3`), whole-pool rerolls on the
+/// total (`rr(sum<8)`), and the highest or lowest die (`2d20h`). Anything
+/// else (other modifiers, dice with rolled counts, division that could be
+/// by zero) or anything too large to work out returns `None`; simulate
+/// those.
+pub fn exact(ast: &Ast, id: ExprId) -> Option<Distribution> {
+    let constant = |id: ExprId| match ast[id].kind {
+        ExprKind::Number(n) => Some(n),
+        _ => None,
+    };
+    match &ast[id].kind {
+        ExprKind::Number(n) => Some(Distribution::constant(*n)),
+        ExprKind::Dice { .. } | ExprKind::Fudge { .. } | ExprKind::Repetition { .. } => {
+            let (unit, count, keep) = pool(ast, id)?;
+            match keep {
+                None => unit.repeat(count),
+                Some((DiceModifierType::KeepHigh, n)) => unit.keep_highest(count, n),
+                Some((DiceModifierType::KeepLow, n)) => unit.keep_lowest(count, n),
+                Some((DiceModifierType::DropHigh, n)) => unit.keep_lowest(count, count - n),
+                Some((DiceModifierType::DropLow, n)) => unit.keep_highest(count, count - n),
+                Some(_) => None,
+            }
+        }
+        ExprKind::Read {
+            pool: dice,
+            reading: Annotation::Max,
+        } => {
+            let (unit, count, None) = pool(ast, *dice)? else {
+                return None;
+            };
+            Some(unit.highest_of(count))
+        }
+        ExprKind::Read {
+            pool: dice,
+            reading: Annotation::Min,
+        } => {
+            let (unit, count, None) = pool(ast, *dice)? else {
+                return None;
+            };
+            Some(unit.lowest_of(count))
+        }
+        ExprKind::Reroll {
+            pool: dice,
+            condition,
+            limit,
+        } => {
+            // The condition has to read the total itself: a sum, or the one
+            // die of a single-die pool.
+            let on_total = match condition.stat {
+                Annotation::Sum => true,
+                Annotation::Max | Annotation::Min => matches!(pool(ast, *dice)?, (_, 1, None)),
+                _ => false,
+            };
+            if !on_total {
+                return None;
+            }
+            let holds = |value| condition.compare.test(value, condition.value);
+            Some(exact(ast, *dice)?.reroll_while(holds, *limit))
+        }
+        ExprKind::BinaryOp(lhs, op, rhs) => {
+            let rhs = exact(ast, *rhs)?;
+            if *op == '/' && rhs.probability(0) > 0.0 {
+                return None;
+            }
+            exact(ast, *lhs)?.combine(&rhs, |a, b| apply_op(a, *op, b))
+        }
+        ExprKind::Call {
+            function: Function::Between,
+            args,
+        } => Distribution::uniform(constant(args[0])?, constant(args[1])?),
+        ExprKind::Call { function, args } => {
+            let function = *function;
+            let mut args = args.iter().map(|&arg| exact(ast, arg));
+            let first = args.next()??;
+            match (args.next(), args.next()) {
+                (None, _) => first.map(|n| apply_function(function, &[n])),
+                (Some(low), Some(high)) => {
+                    let (low, high) = (low?, high?);
+                    let bounded = first.combine(&low, |n, low| n.max(low))?;
+                    bounded.combine(&high, |n, high| n.min(high))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// A keep or drop modifier with its value, such as `kh3`.
+type Keep = (DiceModifierType, i32);
+
+/// A pool as the distribution of one of its dice (or repetitions), how many
+/// it rolls, and the keep or drop it ends in, for pools whose dice are
+/// independent and alike: dice and repetitions without other modifiers, and
+/// dice that reroll (`r`, `ro`) or reroll and add (`ra`).
+fn pool(ast: &Ast, id: ExprId) -> Option<(Distribution, i32, Option<Keep>)> {
+    let constant = |id: ExprId| match ast[id].kind {
+        ExprKind::Number(n) => Some(n),
+        _ => None,
+    };
+    // Only a keep at the end works out exactly; one before a reroll would
+    // reroll just the kept dice.
+    let split = |modifiers: &[DiceModifier]| match modifiers {
+        [rest @ .., last]
+            if matches!(
+                last.kind,
+                DiceModifierType::KeepHigh
+                    | DiceModifierType::KeepLow
+                    | DiceModifierType::DropHigh
+                    | DiceModifierType::DropLow
+            ) =>
+        {
+            let value = constant(last.value?)?;
+            Some((rest.len(), Some((last.kind, value))))
+        }
+        _ => Some((modifiers.len(), None)),
+    };
+    match &ast[id].kind {
+        ExprKind::Dice {
+            count,
+            sides,
+            modifiers,
+        } => {
+            let sides = constant(*sides).filter(|&s| s > 0)?;
+            let (len, keep) = split(modifiers)?;
+            let fresh = Distribution::uniform(1, sides)?;
+            let mut die = fresh.clone();
+            for modifier in &modifiers[..len] {
+                let value = match modifier.value {
+                    Some(value) => Some(constant(value)?),
+                    None => None,
+                };
+                let set = |face: i32| modifier.faces.contains(&face);
+                die = match modifier.kind {
+                    DiceModifierType::RerollAdd => {
+                        let value = value.unwrap_or(sides);
+                        let triggers =
+                            |face| set(face) || modifier.faces.is_empty() && face == value;
+                        let mut outcomes = Vec::new();
+                        for (face, p) in die.iter() {
+                            if triggers(face) {
+                                outcomes
+                                    .extend(fresh.iter().map(|(extra, q)| (face + extra, p * q)));
+                            } else {
+                                outcomes.push((face, p));
+                            }
+                        }
+                        Distribution::from_outcomes(outcomes)?
+                    }
+                    DiceModifierType::Reroll | DiceModifierType::RerollOnce => {
+                        let value = value.unwrap_or(1);
+                        let triggers =
+                            |face| set(face) || modifier.faces.is_empty() && face <= value;
+                        let limit = match modifier.kind {
+                            DiceModifierType::RerollOnce => 1,
+                            _ => DEFAULT_REROLL_LIMIT,
+                        };
+                        // The first reroll replaces the die with a fresh one,
+                        // which rerolls on its own as many more times.
+                        let again = fresh.reroll_while(triggers, limit - 1);
+                        let q: f64 = die
+                            .iter()
+                            .filter(|&(v, _)| triggers(v))
+                            .map(|(_, p)| p)
+                            .sum();
+                        let mut outcomes: Vec<_> =
+                            die.iter().filter(|&(v, _)| !triggers(v)).collect();
+                        outcomes.extend(again.iter().map(|(v, p)| (v, p * q)));
+                        Distribution::from_outcomes(outcomes)?
+                    }
+                    _ => return None,
+                };
+            }
+            Some((die, constant(*count)?, keep))
+        }
+        ExprKind::Fudge { count, modifiers } => match split(modifiers)? {
+            (0, keep) => Some((Distribution::uniform(-1, 1)?, constant(*count)?, keep)),
+            _ => None,
+        },
+        ExprKind::Repetition {
+            count,
+            expr,
+            modifiers,
+        } => match split(modifiers)? {
+            (0, keep) => Some((exact(ast, *expr)?, constant(*count)?, keep)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn of(input: &str) -> Option<Distribution> {
+        let ast = parse(input).unwrap();
+        exact(&ast, ast.roots()[0])
+    }
+
+    /// Whether two distributions agree up to rounding.
+    fn close(a: &Distribution, b: &Distribution) -> bool {
+        (a.min.min(b.min)..=a.max().max(b.max()))
+            .all(|v| (a.probability(v) - b.probability(v)).abs() < 1e-12)
+    }
+
+    #[test]
+    fn sums_of_dice() {
+        let dist = of("2d6").unwrap();
+        assert_eq!((dist.min(), dist.max()), (2, 12));
+        assert!((dist.probability(7) - 6.0 / 36.0).abs() < 1e-12);
+        assert!((dist.probability(2) - 1.0 / 36.0).abs() < 1e-12);
+        assert_eq!(dist.probability(13), 0.0);
+        assert!((dist.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn arithmetic_and_functions() {
+        assert!((of("3d6 + 4").unwrap().mean() - 14.5).abs() < 1e-12);
+        assert!((of("2 * 1d4").unwrap().probability(8) - 0.25).abs() < 1e-12);
+        assert!((of("4df").unwrap().probability(0) - 19.0 / 81.0).abs() < 1e-12);
+
+        let clamped = of("clamp(1d20 - 5, 1, 20)").unwrap();
+        assert_eq!((clamped.min(), clamped.max()), (1, 15));
+        assert!((clamped.probability(1) - 6.0 / 20.0).abs() < 1e-12);
+
+        let half = of("half(1d6, up)").unwrap();
+        assert!((half.probability(3) - 2.0 / 6.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rerolls_and_highest_dice() {
+        // A d6 that rerolls and adds on a 6: 1-5 as usual, then 7-12.
+        let ra = of("1d6ra").unwrap();
+        assert_eq!((ra.min(), ra.max()), (1, 12));
+        assert_eq!(ra.probability(6), 0.0);
+        assert!((ra.probability(9) - 1.0 / 36.0).abs() < 1e-12);
+
+        // Rerolling a 1 once: a 1 only if both rolls are.
+        let rr = of("1d6rr(sum=1, 1)").unwrap();
+        assert!((rr.probability(1) - 1.0 / 36.0).abs() < 1e-12);
+        assert!((rr.probability(6) - 7.0 / 36.0).abs() < 1e-12);
+
+        let advantage = of("2d20h").unwrap();
+        assert!((advantage.probability(20) - 39.0 / 400.0).abs() < 1e-12);
+        let disadvantage = of("2d20l").unwrap();
+        assert!((disadvantage.probability(1) - 39.0 / 400.0).abs() < 1e-12);
+        assert!((advantage.mean() + disadvantage.mean() - 21.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rerolls_of_low_dice() {
+        // Great weapon fighting: a 1 or 2 is rolled once more.
+        let gwf = of("1d6ro2").unwrap();
+        assert!((gwf.probability(1) - 2.0 / 36.0).abs() < 1e-12);
+        assert!((gwf.probability(6) - 8.0 / 36.0).abs() < 1e-12);
+        assert!((of("2d6ro2").unwrap().mean() - 25.0 / 3.0).abs() < 1e-12);
+
+        // Rerolling 1s until they go away: 2-5 evenly.
+        let r1 = of("1d20r1").unwrap();
+        assert!(r1.probability(1) < 1e-12);
+        assert!((r1.probability(20) - 1.0 / 19.0).abs() < 1e-12);
+        let set = of("1d6r{1,6}").unwrap();
+        assert!((set.mean() - 3.5).abs() < 1e-12);
+        assert!(set.probability(6) < 1e-12);
+
+        // Rerolling every face stops at the limit with a fresh die.
+        assert_eq!(of("1d4r4"), Distribution::uniform(1, 4));
+        let rr = of("1d6ro").unwrap();
+        assert_eq!(rr, of("1d6rr(sum=1, 1)").unwrap());
+    }
+
+    #[test]
+    fn kept_and_dropped_dice() {
+        let stats = of("4d6kh3").unwrap();
+        assert_eq!((stats.min(), stats.max()), (3, 18));
+        assert!((stats.probability(18) - 21.0 / 1296.0).abs() < 1e-12);
+        assert!((stats.mean() - 15869.0 / 1296.0).abs() < 1e-9);
+        assert!((stats.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(close(&of("4d6dl1").unwrap(), &stats));
+
+        let low = of("4d6kl3").unwrap();
+        assert!((low.mean() + stats.mean() - 21.0).abs() < 1e-9);
+        assert!(close(&of("4d6dh1").unwrap(), &low));
+
+        // One kept die is the highest; none kept is nothing.
+        assert!(close(&of("2d20kh1").unwrap(), &of("2d20h").unwrap()));
+        assert!(close(&of("3d6dh5").unwrap(), &Distribution::constant(0)));
+
+        // Both kept fate dice show + unless three or four show less.
+        let fate = of("4dFkh2").unwrap();
+        assert!((fate.probability(2) - 33.0 / 81.0).abs() < 1e-12);
+        assert_eq!(of("5(1d20 + 2)kl2").unwrap().max(), 44);
+        assert!(of("4d6r1kh3").unwrap().mean() > stats.mean());
+    }
+
+    #[test]
+    fn algebra() {
+        let ast = parse("1d8 + 3 3d6").unwrap();
+        let hit = Distribution::of(&ast, ast.roots()[0]).unwrap();
+        let better = hit.max_with(&hit).unwrap();
+        assert_eq!((better.min(), better.max()), (4, 11));
+        assert!((better.probability(11) - 15.0 / 64.0).abs() < 1e-12);
+        assert_eq!(hit.min_with(&hit), Some(hit.lowest_of(2)));
+
+        let crit = hit
+            .affine(2, 0)
+            .unwrap()
+            .convolve(&Distribution::uniform(1, 6).unwrap());
+        assert!((crit.unwrap().mean() - 18.5).abs() < 1e-12);
+        assert_eq!(
+            Distribution::uniform(1, 6).unwrap().repeat(3),
+            Distribution::of(&ast, ast.roots()[1])
+        );
+    }
+
+    #[test]
+    fn too_wide_or_too_much_work() {
+        assert_eq!(of("1d6 * 100000000"), None);
+        assert_eq!(of("d[1..2000000000]"), None);
+        assert_eq!(of("1d2000000000"), None);
+        assert_eq!(of("1000d6kh500"), None);
+        assert_eq!(
+            Distribution::uniform(1, 6).unwrap().affine(100_000_000, 0),
+            None
+        );
+        assert_eq!(Distribution::uniform(i32::MIN, i32::MAX), None);
+        assert_eq!(of("between(0, 2000000000)"), None);
+    }
+
+    #[test]
+    fn unsupported_expressions() {
+        assert_eq!(of("4d6kh3r1"), None);
+        assert_eq!(of("4d6kh3kl2"), None);
+        assert_eq!(of("4d6rr(max<3)"), None);
+        assert_eq!(of("(1d4)d6"), None);
+        assert_eq!(of("10 / (1d3 - 1)"), None);
+    }
+}
@@ -1,9 +1,37 @@
-use crate::parser::{DiceModifier, DiceModifierType, Expr};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+
+use smallvec::SmallVec;
+
+use crate::ast::{
+    Annotation, Ast, Choice, Condition, DEFAULT_REROLL_LIMIT, DiceModifier, DiceModifierType,
+    ExprId, ExprKind, Function, Rounding, precedence,
+};
+use crate::rng::{map_streams, sum_streams};
+
+/// Storage for a pool of rolls. Most pools are a handful of dice, which
+/// this keeps inline instead of on the heap.
+pub type Rolls = SmallVec<[i32; 8]>;
 
 #[derive(Debug)]
 pub enum EvalResult {
-    Rolls(Vec<i32>),
+    Rolls(Rolls),
     Number(i32),
+    /// A pool read by counting successes rather than summing.
+    Successes {
+        rolls: Rolls,
+        successes: i32,
+        /// Dice at or above this count as two successes.
+        double: Option<i32>,
+    },
+    /// A word picked by `choose`, which counts as its index in arithmetic.
+    Text {
+        text: String,
+        index: i32,
+    },
 }
 
 impl EvalResult {
@@ -11,36 +39,441 @@ impl EvalResult {
         match self {
             EvalResult::Number(n) => *n,
             EvalResult::Rolls(v) => v.iter().sum(),
+            EvalResult::Successes { successes, .. } => *successes,
+            EvalResult::Text { index, .. } => *index,
+        }
+    }
+
+    /// The dice of a pool, or anything else as a single value.
+    pub fn dice(&self) -> &[i32] {
+        match self {
+            EvalResult::Rolls(rolls) | EvalResult::Successes { rolls, .. } => rolls,
+            EvalResult::Number(n) | EvalResult::Text { index: n, .. } => std::slice::from_ref(n),
+        }
+    }
+
+    /// A success pool with no successes and at least one 1.
+    pub fn is_botch(&self) -> bool {
+        match self {
+            EvalResult::Successes {
+                rolls, successes, ..
+            } => *successes == 0 && rolls.contains(&1),
+            _ => false,
+        }
+    }
+}
+
+/// Reads an annotation off a result: pools die by die, anything else as
+/// its total.
+pub fn eval_annotation(result: &EvalResult, annotation: Annotation) -> i32 {
+    let rolls = result.dice();
+    match annotation {
+        Annotation::Sum => result.to_number(),
+        Annotation::Max => rolls.iter().copied().max().unwrap_or(0),
+        Annotation::Min => rolls.iter().copied().min().unwrap_or(0),
+        Annotation::Count(compare, value) => {
+            rolls.iter().filter(|&&r| compare.test(r, value)).count() as i32
+        }
+        Annotation::Distinct | Annotation::Straight => {
+            let mut faces = rolls.to_vec();
+            faces.sort_unstable();
+            faces.dedup();
+            if annotation == Annotation::Distinct {
+                return faces.len() as i32;
+            }
+            let mut longest = 0;
+            let mut run = 0;
+            for (i, &face) in faces.iter().enumerate() {
+                run = if i > 0 && faces[i - 1] + 1 == face {
+                    run + 1
+                } else {
+                    1
+                };
+                longest = longest.max(run);
+            }
+            longest
+        }
+    }
+}
+
+/// Whether a condition holds on a result, e.g. whether `sum<8` does.
+pub fn condition_holds(result: &EvalResult, condition: Condition) -> bool {
+    let value = eval_annotation(result, condition.stat);
+    condition.compare.test(value, condition.value)
+}
+
+/// Names a success count, e.g. `3 successes` or `botch`.
+pub fn describe_successes(result: &EvalResult) -> String {
+    match result.to_number() {
+        _ if result.is_botch() => "botch".to_string(),
+        1 => "1 success".to_string(),
+        n => format!("{} successes", n),
+    }
+}
+
+/// Lists the dice of a result like `{:?}` does, marking dice of a success
+/// pool that count double with `*`, e.g. `[10*, 7, 3]`. Text shows as
+/// itself.
+pub fn format_rolls(result: &EvalResult) -> String {
+    match result {
+        EvalResult::Successes {
+            rolls,
+            double: Some(double),
+            ..
+        } => {
+            let dice: Vec<String> = rolls
+                .iter()
+                .map(|&r| {
+                    if r >= *double {
+                        format!("{}*", r)
+                    } else {
+                        r.to_string()
+                    }
+                })
+                .collect();
+            format!("[{}]", dice.join(", "))
+        }
+        EvalResult::Successes { rolls, .. } | EvalResult::Rolls(rolls) => format!("{:?}", rolls),
+        EvalResult::Number(n) => n.to_string(),
+        EvalResult::Text { text, .. } => text.clone(),
+    }
+}
+
+/// Lists the dice of a pool with whether each one meets a count reading,
+/// e.g. `[17 pass, 9 fail]`.
+pub fn format_checks(result: &EvalResult, reading: Annotation) -> String {
+    let checks: Vec<String> = result
+        .dice()
+        .iter()
+        .map(|&r| {
+            let passed = eval_annotation(&EvalResult::Number(r), reading) > 0;
+            format!("{} {}", r, if passed { "pass" } else { "fail" })
+        })
+        .collect();
+    format!("[{}]", checks.join(", "))
+}
+
+/// Shows Fudge dice as their faces, e.g. `[+][−][ ][+]`.
+pub fn format_fudge(rolls: &[i32]) -> String {
+    rolls
+        .iter()
+        .map(|&r| match r {
+            1.. => "[+]",
+            0 => "[ ]",
+            _ => "[−]",
+        })
+        .collect()
+}
+
+/// Whether evaluating `id` yields a success count rather than a sum.
+pub fn counts_successes(ast: &Ast, id: ExprId) -> bool {
+    match &ast[id].kind {
+        ExprKind::Dice { modifiers, .. }
+        | ExprKind::Fudge { modifiers, .. }
+        | ExprKind::Group { modifiers, .. }
+        | ExprKind::Repetition { modifiers, .. } => {
+            modifiers.iter().any(|m| m.kind.counts_successes())
         }
+        ExprKind::Reroll { pool, .. } => counts_successes(ast, *pool),
+        _ => false,
     }
 }
 
+/// Whether [`roll`] records what it rolls; see [`start_tally`].
+static TALLYING: AtomicBool = AtomicBool::new(false);
+static TALLY: Mutex<Vec<(i32, i32)>> = Mutex::new(Vec::new());
+
 pub fn roll(sides: i32) -> i32 {
-    fastrand::i32(1..=sides)
+    let value = if PAIRING.load(AtomicOrdering::Relaxed) > 0 {
+        PAIR.with_borrow_mut(|pair| pair.roll(sides))
+    } else {
+        fastrand::i32(1..=sides)
+    };
+    if TALLYING.load(AtomicOrdering::Relaxed) {
+        TALLY.lock().unwrap().push((sides, value));
+    }
+    value
+}
+
+/// Starts recording every die [`roll`] rolls, on any thread, as `(sides,
+/// value)` pairs, for a history of real results. Off by default, so rolls
+/// cost nothing extra.
+pub fn start_tally() {
+    TALLY.lock().unwrap().clear();
+    TALLYING.store(true, AtomicOrdering::Relaxed);
+}
+
+/// Stops recording and returns the dice rolled since [`start_tally`].
+pub fn take_tally() -> Vec<(i32, i32)> {
+    TALLYING.store(false, AtomicOrdering::Relaxed);
+    std::mem::take(&mut *TALLY.lock().unwrap())
 }
 
-pub fn eval_expr(expr: &Expr) -> EvalResult {
-    match expr {
-        Expr::Number(n) => EvalResult::Number(*n),
-        Expr::Dice {
+thread_local! {
+    /// The dice that counted in each pool rolled on this thread, while
+    /// [`kept_dice`] is collecting them.
+    static KEPT: RefCell<Option<Vec<(i32, i32)>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` and returns what it returns along with the dice that counted
+/// toward it, as `(sides, value)` pairs in the order they were rolled: each
+/// pool's dice after its keeps, drops and rerolls, and only the die read off
+/// by `h` or `l`. Dice without a number of sides, such as fate dice, are
+/// left out. Unlike [`start_tally`], this only sees dice rolled on this
+/// thread, so while it runs, streams stay on this thread.
+pub fn kept_dice<T>(f: impl FnOnce() -> T) -> (T, Vec<(i32, i32)>) {
+    let outer = KEPT.with_borrow_mut(|kept| kept.replace(Vec::new()));
+    let result = f();
+    let dice = KEPT.with_borrow_mut(|kept| std::mem::replace(kept, outer));
+    (result, dice.unwrap_or_default())
+}
+
+/// Whether [`kept_dice`] is collecting on this thread.
+pub(crate) fn collecting() -> bool {
+    KEPT.with_borrow(Option::is_some)
+}
+
+/// Like [`kept_dice`], but only collects if an outer call is, so the dice
+/// can be sorted into those that still count.
+fn kept_within<T>(f: impl FnOnce() -> T) -> (T, Vec<(i32, i32)>) {
+    if collecting() {
+        kept_dice(f)
+    } else {
+        (f(), Vec::new())
+    }
+}
+
+/// Adds dice that counted to what [`kept_dice`] is collecting, if anything.
+fn keep(dice: impl IntoIterator<Item = (i32, i32)>) {
+    KEPT.with_borrow_mut(|kept| {
+        if let Some(kept) = kept {
+            kept.extend(dice);
+        }
+    });
+}
+
+/// Keeps, from `dice`, one die showing each of `values`.
+fn keep_matching(values: &[i32], mut dice: Vec<(i32, i32)>) {
+    let mut matched = Vec::new();
+    for &value in values {
+        if let Some(i) = dice.iter().position(|&(_, v)| v == value) {
+            matched.push(dice.remove(i));
+        }
+    }
+    keep(matched);
+}
+
+pub fn roll_fudge() -> i32 {
+    if PAIRING.load(AtomicOrdering::Relaxed) > 0 {
+        PAIR.with_borrow_mut(|pair| pair.roll(3)) - 2
+    } else {
+        fastrand::i32(-1..=1)
+    }
+}
+
+/// How many threads hold an [`Antithetic`], so [`roll`] only looks for one
+/// when it might exist.
+static PAIRING: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static PAIR: RefCell<Pair> = RefCell::new(Pair::default());
+}
+
+/// The dice of the first half of an antithetic pair on this thread.
+#[derive(Default)]
+struct Pair {
+    active: bool,
+    mirroring: bool,
+    dice: Vec<(i32, i32)>,
+    next: usize,
+}
+
+impl Pair {
+    fn roll(&mut self, sides: i32) -> i32 {
+        if !self.active {
+            return fastrand::i32(1..=sides);
+        }
+        if !self.mirroring {
+            let value = fastrand::i32(1..=sides);
+            self.dice.push((sides, value));
+            return value;
+        }
+        match self.dice.get(self.next) {
+            Some(&(recorded, value)) if recorded == sides => {
+                self.next += 1;
+                sides + 1 - value
+            }
+            // The runs went different ways (an explosion, a reroll), so
+            // the rest of this one is rolled fresh.
+            _ => {
+                self.next = self.dice.len();
+                fastrand::i32(1..=sides)
+            }
+        }
+    }
+}
+
+/// Whether this thread is rolling an antithetic pair, whose dice have to
+/// be rolled on it and in order.
+#[cfg(feature = "parallel")]
+pub(crate) fn pairing() -> bool {
+    PAIRING.load(AtomicOrdering::Relaxed) > 0 && PAIR.with_borrow(|pair| pair.active)
+}
+
+/// Antithetic sampling on the current thread: [`Antithetic::pair`] runs a
+/// roll twice, the second time with every die showing the opposite face
+/// (a 1 becomes the highest face), so the two pull in opposite directions
+/// and their average varies less than two independent rolls.
+pub struct Antithetic {
+    /// The pairing lives in a thread local, so the guard stays on its thread.
+    _thread: PhantomData<*const ()>,
+}
+
+impl Antithetic {
+    pub fn start() -> Antithetic {
+        PAIRING.fetch_add(1, AtomicOrdering::Relaxed);
+        Antithetic {
+            _thread: PhantomData,
+        }
+    }
+
+    /// Runs `f` for a roll and again for its mirror image. Dice the mirror
+    /// rolls beyond those of the first run, such as extra explosions, are
+    /// random.
+    pub fn pair<T>(&self, mut f: impl FnMut() -> T) -> (T, T) {
+        PAIR.with_borrow_mut(|pair| {
+            pair.active = true;
+            pair.mirroring = false;
+            pair.dice.clear();
+            pair.next = 0;
+        });
+        let first = f();
+        PAIR.with_borrow_mut(|pair| pair.mirroring = true);
+        let second = f();
+        PAIR.with_borrow_mut(|pair| pair.active = false);
+        (first, second)
+    }
+}
+
+impl Drop for Antithetic {
+    fn drop(&mut self) {
+        PAIRING.fetch_sub(1, AtomicOrdering::Relaxed);
+    }
+}
+
+/// Picks one of `choices` with a chance proportional to its weight and
+/// returns its index.
+pub fn choose(choices: &[Choice]) -> usize {
+    let mut value = roll(choices.iter().map(|c| c.weight).sum());
+    choices
+        .iter()
+        .position(|c| {
+            value -= c.weight;
+            value <= 0
+        })
+        .unwrap()
+}
+
+/// Like [`choose`], but returns the picked word.
+pub fn pick(choices: &[Choice]) -> EvalResult {
+    let index = choose(choices);
+    EvalResult::Text {
+        text: choices[index].text.clone(),
+        index: index as i32,
+    }
+}
+
+pub fn eval_expr(ast: &Ast, id: ExprId) -> EvalResult {
+    match &ast[id].kind {
+        ExprKind::Number(n) => EvalResult::Number(*n),
+        ExprKind::Dice {
+            count,
+            sides,
+            modifiers,
+        } => eval_dice(ast, *count, *sides, modifiers),
+        ExprKind::Fudge { count, modifiers } => eval_fudge(ast, *count, modifiers),
+        ExprKind::BinaryOp(exp1, op, exp2) => eval_op(ast, *exp1, *op, *exp2),
+        ExprKind::Group { members, modifiers } => eval_group(ast, members, modifiers),
+        ExprKind::Reroll {
+            pool,
+            condition,
+            limit,
+        } => {
+            // Only the last roll of the pool counts.
+            let mut last = Vec::new();
+            let result = reroll_while(*condition, *limit, || {
+                let (result, dice) = kept_within(|| eval_expr(ast, *pool));
+                last = dice;
+                result
+            });
+            keep(last);
+            result
+        }
+        ExprKind::Contest(..)
+        | ExprKind::Tiers { .. }
+        | ExprKind::Call { .. }
+        | ExprKind::Read { .. } => EvalResult::Number(eval_total(ast, id)),
+        ExprKind::Choose(choices) => pick(choices),
+        ExprKind::Repetition {
+            count,
+            expr,
+            modifiers,
+        } => eval_rep(ast, *count, *expr, modifiers),
+    }
+}
+
+/// Evaluates an expression straight to its total.
+///
+/// Dice and repetitions without modifiers are summed as they are rolled, so
+/// huge pools like `10000000d6` never materialize a vector of rolls.
+pub fn eval_total(ast: &Ast, id: ExprId) -> i32 {
+    match &ast[id].kind {
+        ExprKind::Number(n) => *n,
+        ExprKind::Choose(choices) => choose(choices) as i32,
+        ExprKind::Dice {
             count,
             sides,
             modifiers,
-        } => eval_dice(count, sides, modifiers),
-        Expr::BinaryOp(exp1, op, exp2) => eval_op(exp1, op, exp2),
-        Expr::Repetition {
+        } if modifiers.is_empty() && !collecting() => {
+            let count = eval_total(ast, *count);
+            let sides = eval_total(ast, *sides);
+            (0..count).map(|_| roll(sides)).sum()
+        }
+        ExprKind::Fudge { count, modifiers } if modifiers.is_empty() => {
+            (0..eval_total(ast, *count)).map(|_| roll_fudge()).sum()
+        }
+        ExprKind::BinaryOp(exp1, op, exp2) => {
+            apply_op(eval_total(ast, *exp1), *op, eval_total(ast, *exp2))
+        }
+        ExprKind::Contest(first, second) => eval_total(ast, *first) - eval_total(ast, *second),
+        ExprKind::Tiers { expr, thresholds } => {
+            tier_reached(eval_total(ast, *expr), thresholds) as i32
+        }
+        ExprKind::Read { pool, reading } => {
+            eval_annotation(&eval_read(ast, *pool, *reading), *reading)
+        }
+        ExprKind::Call { function, args } => {
+            let args: SmallVec<[i32; 3]> = args.iter().map(|&arg| eval_total(ast, arg)).collect();
+            apply_function(*function, &args)
+        }
+        ExprKind::Repetition {
             count,
             expr,
             modifiers,
-        } => eval_rep(count, expr, modifiers),
+        } if modifiers.is_empty() => {
+            let count = eval_total(ast, *count);
+            sum_streams(clamp_count(count, usize::MAX), || eval_total(ast, *expr))
+        }
+        _ => eval_expr(ast, id).to_number(),
     }
 }
 
-pub fn eval_op(exp1: &Expr, op: &char, exp2: &Expr) -> EvalResult {
-    let exp1 = eval_expr(exp1).to_number();
-    let exp2 = eval_expr(exp2).to_number();
+pub fn eval_op(ast: &Ast, exp1: ExprId, op: char, exp2: ExprId) -> EvalResult {
+    EvalResult::Number(apply_op(eval_total(ast, exp1), op, eval_total(ast, exp2)))
+}
 
-    let result = match op {
+pub fn apply_op(exp1: i32, op: char, exp2: i32) -> i32 {
+    match op {
         '+' => exp1 + exp2,
         '-' => exp1 - exp2,
         '*' => exp1 * exp2,
@@ -51,100 +484,456 @@ pub fn eval_op(exp1: &Expr, op: &char, exp2: &Expr) -> EvalResult {
             exp1 / exp2
         }
         _ => panic!("unsupported operation: {}", op),
+    }
+}
+
+/// Applies a function to its evaluated arguments.
+pub fn apply_function(function: Function, args: &[i32]) -> i32 {
+    match (function, args) {
+        (Function::Half(Rounding::Down), &[n]) => n.div_euclid(2),
+        (Function::Half(Rounding::Up), &[n]) => n.div_euclid(2) + n.rem_euclid(2),
+        (Function::Double, &[n]) => n * 2,
+        // Unlike `i32::clamp`, a minimum above the maximum does not panic;
+        // the maximum wins.
+        (Function::Clamp, &[n, min, max]) => n.max(min).min(max),
+        // Bounds given high first still roll between them.
+        (Function::Between, &[a, b]) => a.min(b) + roll(a.abs_diff(b) as i32 + 1) - 1,
+        _ => panic!("{} takes {} arguments", function.name(), function.arity()),
+    }
+}
+
+pub fn eval_dice(
+    ast: &Ast,
+    count: ExprId,
+    sides: ExprId,
+    modifiers: &[DiceModifier],
+) -> EvalResult {
+    let count = eval_total(ast, count);
+    let sides = eval_total(ast, sides);
+
+    let rolls = (0..count).map(|_| roll(sides)).collect();
+
+    let result = eval_modifiers(ast, rolls, modifiers, Some(sides));
+    keep(result.dice().iter().map(|&value| (sides, value)));
+    result
+}
+
+/// Rolls the pool a reading reads off. When the reading is a single die,
+/// as for `h` and `l`, only that die counts.
+fn eval_read(ast: &Ast, pool: ExprId, reading: Annotation) -> EvalResult {
+    let (result, dice) = kept_within(|| eval_expr(ast, pool));
+    match reading {
+        Annotation::Max | Annotation::Min => {
+            keep_matching(&[eval_annotation(&result, reading)], dice)
+        }
+        _ => keep(dice),
+    }
+    result
+}
+
+pub fn eval_fudge(ast: &Ast, count: ExprId, modifiers: &[DiceModifier]) -> EvalResult {
+    let count = eval_total(ast, count);
+    let rolls = (0..count).map(|_| roll_fudge()).collect();
+
+    eval_modifiers(ast, rolls, modifiers, None)
+}
+
+/// Evaluates `id` for verbose output: its total, and the expression with
+/// each pool replaced by the dice it rolled, e.g. `[6, 4, 4] + 3`.
+pub fn eval_breakdown(ast: &Ast, id: ExprId) -> (i32, String) {
+    let precedence_of = |id: ExprId| match ast[id].kind {
+        ExprKind::BinaryOp(_, op, _) => precedence(op),
+        _ => u8::MAX,
     };
-    EvalResult::Number(result)
+    match &ast[id].kind {
+        ExprKind::BinaryOp(lhs, op, rhs) => {
+            let operand = |child: ExprId, parens: bool| {
+                let (total, text) = eval_breakdown(ast, child);
+                (total, if parens { format!("({})", text) } else { text })
+            };
+            let prec = precedence(*op);
+            let (lhs, lhs_text) = operand(*lhs, precedence_of(*lhs) < prec);
+            let (rhs, rhs_text) = operand(*rhs, precedence_of(*rhs) <= prec);
+            (
+                apply_op(lhs, *op, rhs),
+                format!("{} {} {}", lhs_text, op, rhs_text),
+            )
+        }
+        ExprKind::Call { function, args } => {
+            let mut totals = SmallVec::<[i32; 3]>::new();
+            let mut texts = Vec::new();
+            for &arg in args {
+                let (total, text) = eval_breakdown(ast, arg);
+                // Name each step, unless the argument is just a number.
+                if matches!(ast[arg].kind, ExprKind::Number(_)) {
+                    texts.push(text);
+                } else {
+                    texts.push(format!("{} = {}", text, total));
+                }
+                totals.push(total);
+            }
+            (
+                apply_function(*function, &totals),
+                format!("{}({})", function.name(), texts.join(", ")),
+            )
+        }
+        ExprKind::Read { pool, reading } => {
+            let result = eval_read(ast, *pool, *reading);
+            let text = match *reading {
+                Annotation::Count(compare, value) => {
+                    format!("{} {} {}", format_checks(&result, *reading), compare, value)
+                }
+                reading => format!("{}{}", reading, format_rolls(&result)),
+            };
+            (eval_annotation(&result, *reading), text)
+        }
+        ExprKind::Fudge { .. } => match eval_expr(ast, id) {
+            EvalResult::Rolls(rolls) => (rolls.iter().sum(), format_fudge(&rolls)),
+            result => (result.to_number(), format_rolls(&result)),
+        },
+        _ => {
+            let result = eval_expr(ast, id);
+            (result.to_number(), format_rolls(&result))
+        }
+    }
 }
 
-pub fn eval_dice(count: &Expr, sides: &Expr, modifiers: &[DiceModifier]) -> EvalResult {
-    let count = eval_expr(count).to_number();
-    let sides = eval_expr(sides).to_number();
+/// Which side of a contest wins when both totals are equal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Nobody wins.
+    #[default]
+    Draw,
+    First,
+    Second,
+}
 
-    let mut rolls: Vec<i32> = Vec::new();
+impl TieBreak {
+    /// Compares the two totals of a contest: `Greater` if the first side
+    /// wins, `Less` if the second does and `Equal` for a draw.
+    pub fn resolve(self, first: i32, second: i32) -> Ordering {
+        match (first.cmp(&second), self) {
+            (Ordering::Equal, TieBreak::First) => Ordering::Greater,
+            (Ordering::Equal, TieBreak::Second) => Ordering::Less,
+            (ordering, _) => ordering,
+        }
+    }
+}
 
-    for _ in 0..count {
-        rolls.push(roll(sides));
+/// Names the winner of a contest, e.g. `first wins`.
+pub fn describe_contest(outcome: Ordering) -> &'static str {
+    match outcome {
+        Ordering::Greater => "first wins",
+        Ordering::Less => "second wins",
+        Ordering::Equal => "draw",
     }
+}
 
-    eval_modifiers(rolls, modifiers, Some(sides))
+/// How many of the ascending `thresholds` a total reaches.
+pub fn tier_reached(total: i32, thresholds: &[i32]) -> usize {
+    thresholds.iter().take_while(|&&t| total >= t).count()
 }
 
-pub fn eval_rep(count: &Expr, expr: &Expr, modifiers: &[DiceModifier]) -> EvalResult {
-    let count = eval_expr(count).to_number();
-    let mut result: Vec<i32> = Vec::new();
+/// Names a tier reached against `thresholds` thresholds: one threshold
+/// splits fail and success, two add a partial success between them, and
+/// three split success into full and critical. Beyond that tiers are
+/// numbered.
+pub fn describe_tier(tier: usize, thresholds: usize) -> String {
+    let names: &[&str] = match thresholds {
+        1 => &["fail", "success"],
+        2 => &["fail", "partial", "success"],
+        3 => &["fail", "partial", "full", "critical"],
+        _ => &["fail"],
+    };
+    match names.get(tier) {
+        Some(name) => name.to_string(),
+        None => format!("tier {}", tier),
+    }
+}
 
-    for _ in 0..count {
-        result.push(eval_expr(expr).to_number());
+pub fn eval_rep(ast: &Ast, count: ExprId, expr: ExprId, modifiers: &[DiceModifier]) -> EvalResult {
+    let count = eval_total(ast, count);
+    let count = clamp_count(count, usize::MAX);
+    if !collecting() {
+        let result = Rolls::from_vec(map_streams(count, |_| eval_total(ast, expr)));
+        return eval_modifiers(ast, result, modifiers, None);
     }
+    // The dice of each repetition count only if it is kept.
+    let (totals, mut dice): (Vec<i32>, Vec<_>) =
+        map_streams(count, |_| kept_dice(|| eval_total(ast, expr)))
+            .into_iter()
+            .unzip();
+    let result = eval_modifiers(ast, Rolls::from_vec(totals.clone()), modifiers, None);
+    let mut used = vec![false; totals.len()];
+    for &value in result.dice() {
+        if let Some(i) = (0..totals.len()).find(|&i| !used[i] && totals[i] == value) {
+            used[i] = true;
+            keep(std::mem::take(&mut dice[i]));
+        }
+    }
+    result
+}
+
+/// Pools the dice of every member of a group, then applies the group's
+/// modifiers to them.
+pub fn eval_group(ast: &Ast, members: &[ExprId], modifiers: &[DiceModifier]) -> EvalResult {
+    let (rolls, dice) = kept_within(|| {
+        let mut rolls = Rolls::new();
+        for &member in members {
+            rolls.extend_from_slice(eval_expr(ast, member).dice());
+        }
+        rolls
+    });
 
-    eval_modifiers(result, modifiers, None)
+    let result = eval_modifiers(ast, rolls, modifiers, None);
+    keep_matching(result.dice(), dice);
+    result
+}
+
+/// Rolls a pool, then rolls it again while `condition` holds, at most
+/// `limit` more times. Returns the last roll.
+pub fn reroll_while(
+    condition: Condition,
+    limit: u32,
+    mut roll: impl FnMut() -> EvalResult,
+) -> EvalResult {
+    let mut result = roll();
+    for _ in 0..limit {
+        if !condition_holds(&result, condition) {
+            break;
+        }
+        result = roll();
+    }
+    result
 }
 
 pub fn eval_modifiers(
-    mut rolls: Vec<i32>,
+    ast: &Ast,
+    rolls: Rolls,
     modifiers: &[DiceModifier],
     sides: Option<i32>,
 ) -> EvalResult {
-    for modifier in modifiers.iter() {
-        let value = match &modifier.value {
-            Some(expr_box) => eval_expr(&**expr_box).to_number(),
-            None => {
-                if modifier.kind == DiceModifierType::Explode {
-                    sides.expect("Explode requires number of sides.")
-                } else {
-                    panic!(
-                        "All dice modifiers (except explode) must be followed by a value. E.g. 4d6kh3"
-                    );
-                }
+    let modifiers = modifiers.iter().map(|m| {
+        (
+            m.kind,
+            m.value.map(|v| eval_total(ast, v)),
+            m.faces.as_slice(),
+        )
+    });
+    apply_modifiers(rolls, modifiers, sides)
+}
+
+/// Applies modifiers in order, evaluating each value only when its modifier
+/// is reached. If any modifier counts successes, the last one decides the
+/// target and the pool is read as a success count, with dice at or above a
+/// double threshold counting twice.
+pub fn apply_modifiers<'a>(
+    mut rolls: Rolls,
+    modifiers: impl IntoIterator<Item = (DiceModifierType, Option<i32>, &'a [i32])>,
+    sides: Option<i32>,
+) -> EvalResult {
+    let mut target = None;
+    let mut double = None;
+    for (kind, value, faces) in modifiers {
+        let value = modifier_value(kind, value, sides);
+        if kind.counts_successes() {
+            target = Some(value);
+        } else if kind == DiceModifierType::Double {
+            double = Some(value);
+        }
+        rolls = apply_modifier(rolls, &kind, Some(value), faces, sides);
+    }
+
+    match target {
+        Some(target) => {
+            let successes = rolls
+                .iter()
+                .map(|&r| (r >= target) as i32 + double.is_some_and(|d| r >= d) as i32)
+                .sum();
+            EvalResult::Successes {
+                rolls,
+                successes,
+                double,
             }
-        };
+        }
+        None => EvalResult::Rolls(rolls),
+    }
+}
 
-        rolls = match modifier.kind {
-            DiceModifierType::KeepHigh => keep_high(rolls, value),
-            DiceModifierType::KeepLow => keep_low(rolls, value),
-            DiceModifierType::DropHigh => drop_high(rolls, value),
-            DiceModifierType::DropLow => drop_low(rolls, value),
-            DiceModifierType::Explode => explode(rolls, sides.expect("Missing sides"), value),
-        };
+fn modifier_value(kind: DiceModifierType, value: Option<i32>, sides: Option<i32>) -> i32 {
+    match (value, kind) {
+        (Some(value), _) => value,
+        (
+            None,
+            DiceModifierType::Explode
+            | DiceModifierType::Compound
+            | DiceModifierType::RerollAdd
+            | DiceModifierType::Double,
+        ) => sides.expect("Explode requires number of sides."),
+        (None, DiceModifierType::Wod) => 8,
+        (None, DiceModifierType::Reroll | DiceModifierType::RerollOnce) => 1,
+        (None, _) => panic!(
+            "All dice modifiers (except explodes, x and wod) must be followed by a value. E.g. 4d6kh3"
+        ),
+    }
+}
+
+/// Applies one modifier whose value has already been evaluated. An explode
+/// with `faces` triggers on those faces instead of at its value. Success
+/// targets leave the rolls as they are; see [`apply_modifiers`].
+pub fn apply_modifier(
+    rolls: Rolls,
+    kind: &DiceModifierType,
+    value: Option<i32>,
+    faces: &[i32],
+    sides: Option<i32>,
+) -> Rolls {
+    let value = modifier_value(*kind, value, sides);
+    let trigger = if faces.is_empty() {
+        Faces::AtLeast(value)
+    } else {
+        Faces::Set(faces)
+    };
+
+    match kind {
+        DiceModifierType::KeepHigh => keep_high(rolls, value),
+        DiceModifierType::KeepLow => keep_low(rolls, value),
+        DiceModifierType::DropHigh => drop_high(rolls, value),
+        DiceModifierType::DropLow => drop_low(rolls, value),
+        DiceModifierType::Explode => explode(rolls, sides.expect("Missing sides"), trigger),
+        DiceModifierType::Compound => compound(rolls, sides.expect("Missing sides"), trigger),
+        DiceModifierType::RerollAdd => {
+            let trigger = match trigger {
+                Faces::AtLeast(_) => Faces::Set(std::slice::from_ref(&value)),
+                faces => faces,
+            };
+            reroll_add(rolls, sides.expect("Missing sides"), trigger)
+        }
+        DiceModifierType::Reroll | DiceModifierType::RerollOnce => {
+            let trigger = match trigger {
+                Faces::AtLeast(_) => Faces::AtMost(value),
+                faces => faces,
+            };
+            let limit = match kind {
+                DiceModifierType::RerollOnce => 1,
+                _ => DEFAULT_REROLL_LIMIT,
+            };
+            reroll(rolls, sides.expect("Missing sides"), trigger, limit)
+        }
+        DiceModifierType::Target | DiceModifierType::Double => rolls,
+        DiceModifierType::Wod => explode(rolls, sides.expect("Missing sides"), Faces::AtLeast(10)),
     }
+}
+
+/// The faces that set off an explosion or a reroll: those at or above a
+/// threshold, those at or below one, or those in a set, as in `d6!{1,6}`.
+#[derive(Debug, Clone, Copy)]
+pub enum Faces<'a> {
+    AtLeast(i32),
+    AtMost(i32),
+    Set(&'a [i32]),
+}
 
-    EvalResult::Rolls(rolls)
+impl Faces<'_> {
+    pub fn contains(self, face: i32) -> bool {
+        match self {
+            Faces::AtLeast(threshold) => face >= threshold,
+            Faces::AtMost(threshold) => face <= threshold,
+            Faces::Set(faces) => faces.contains(&face),
+        }
+    }
 }
 
-fn keep_high(mut rolls: Vec<i32>, count: i32) -> Vec<i32> {
-    rolls.sort_by(|a, b| b.cmp(a));
-    rolls.truncate(count as usize);
+/// Keeps the `count` highest rolls, highest first. Selecting them is O(n)
+/// and only the kept ones are sorted, unlike a full sort.
+fn keep_high(mut rolls: Rolls, count: i32) -> Rolls {
+    let count = clamp_count(count, rolls.len());
+    if count < rolls.len() {
+        rolls.select_nth_unstable_by(count, |a, b| b.cmp(a));
+    }
+    rolls.truncate(count);
+    rolls.sort_unstable_by(|a, b| b.cmp(a));
     rolls
 }
 
-fn keep_low(mut rolls: Vec<i32>, count: i32) -> Vec<i32> {
-    rolls.sort();
-    rolls.truncate(count as usize);
+/// Keeps the `count` lowest rolls, lowest first.
+fn keep_low(mut rolls: Rolls, count: i32) -> Rolls {
+    let count = clamp_count(count, rolls.len());
+    if count < rolls.len() {
+        rolls.select_nth_unstable(count);
+    }
+    rolls.truncate(count);
+    rolls.sort_unstable();
     rolls
 }
 
-fn drop_high(mut rolls: Vec<i32>, count: i32) -> Vec<i32> {
-    rolls.sort_by(|a, b| b.cmp(a));
-    rolls.drain(0..count as usize);
+/// Drops the `count` highest rolls and lists the rest highest first.
+fn drop_high(rolls: Rolls, count: i32) -> Rolls {
+    let keep = rolls.len() - clamp_count(count, rolls.len());
+    let mut rolls = keep_low(rolls, keep as i32);
+    rolls.reverse();
     rolls
 }
 
-fn drop_low(mut rolls: Vec<i32>, count: i32) -> Vec<i32> {
-    rolls.sort();
-    rolls.drain(0..count as usize);
+/// Drops the `count` lowest rolls and lists the rest lowest first.
+fn drop_low(rolls: Rolls, count: i32) -> Rolls {
+    let keep = rolls.len() - clamp_count(count, rolls.len());
+    let mut rolls = keep_high(rolls, keep as i32);
+    rolls.reverse();
     rolls
 }
 
-fn explode(mut rolls: Vec<i32>, sides: i32, threshold: i32) -> Vec<i32> {
+pub(crate) fn clamp_count(count: i32, len: usize) -> usize {
+    usize::try_from(count).unwrap_or(0).min(len)
+}
+
+/// Adds a die for every roll showing one of `faces`. Added dice are checked
+/// in turn, so explosions chain.
+fn explode(mut rolls: Rolls, sides: i32, faces: Faces) -> Rolls {
     let mut i = 0;
     while i < rolls.len() {
-        while rolls[i] >= threshold {
-            let new_roll = roll(sides);
-            rolls.push(new_roll);
-            if new_roll < threshold {
+        if faces.contains(rolls[i]) {
+            rolls.push(roll(sides));
+        }
+        i += 1;
+    }
+    rolls
+}
+
+/// Rolls again for every roll showing one of `faces`, adding the new roll
+/// into the same die, for as long as the new rolls keep showing one.
+fn compound(mut rolls: Rolls, sides: i32, faces: Faces) -> Rolls {
+    for die in rolls.iter_mut() {
+        let mut last = *die;
+        while faces.contains(last) {
+            last = roll(sides);
+            *die += last;
+        }
+    }
+    rolls
+}
+
+/// Rolls once more for every roll showing one of `faces` and adds the new
+/// roll into the same die. The new roll does not trigger again.
+fn reroll_add(mut rolls: Rolls, sides: i32, faces: Faces) -> Rolls {
+    for die in rolls.iter_mut() {
+        if faces.contains(*die) {
+            *die += roll(sides);
+        }
+    }
+    rolls
+}
+
+/// Rolls a die showing one of `faces` again, replacing it, until it shows
+/// another face or it has been rolled `limit` more times.
+fn reroll(mut rolls: Rolls, sides: i32, faces: Faces, limit: u32) -> Rolls {
+    for die in rolls.iter_mut() {
+        for _ in 0..limit {
+            if !faces.contains(*die) {
                 break;
             }
+            *die = roll(sides);
         }
-        i += 1;
     }
     rolls
 }
@@ -152,61 +941,56 @@ fn explode(mut rolls: Vec<i32>, sides: i32, threshold: i32) -> Vec<i32> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::{DiceModifier, DiceModifierType, Expr};
+    use crate::ast::Compare;
+    use smallvec::smallvec;
 
-    fn num(n: i32) -> Expr {
-        Expr::Number(n)
+    fn dice(ast: &mut Ast, count: i32, sides: i32, modifiers: Vec<DiceModifier>) -> ExprId {
+        let (count, sides) = (ast.number(count), ast.number(sides));
+        ast.dice(count, sides, modifiers)
     }
 
-    fn dice(count: i32, sides: i32, modifiers: Vec<DiceModifier>) -> Expr {
-        Expr::Dice {
-            count: Box::new(num(count)),
-            sides: Box::new(num(sides)),
-            modifiers,
-        }
+    fn rep(ast: &mut Ast, count: i32, expr: ExprId, modifiers: Vec<DiceModifier>) -> ExprId {
+        let count = ast.number(count);
+        ast.repetition(count, expr, modifiers)
     }
 
-    fn rep(count: i32, expr: Expr, modifiers: Vec<DiceModifier>) -> Expr {
-        Expr::Repetition {
-            count: Box::new(num(count)),
-            expr: Box::new(expr),
-            modifiers,
-        }
+    fn binop(ast: &mut Ast, lhs: i32, op: char, rhs: i32) -> ExprId {
+        let (lhs, rhs) = (ast.number(lhs), ast.number(rhs));
+        ast.binary(lhs, op, rhs)
     }
 
-    fn binop(lhs: Expr, op: char, rhs: Expr) -> Expr {
-        Expr::BinaryOp(Box::new(lhs), op, Box::new(rhs))
-    }
-
-    fn modifier(kind: DiceModifierType, val: Option<i32>) -> DiceModifier {
-        DiceModifier {
-            kind,
-            value: val.map(|v| Box::new(num(v))),
-        }
+    fn modifier(ast: &mut Ast, kind: DiceModifierType, val: Option<i32>) -> DiceModifier {
+        DiceModifier::new(kind, val.map(|v| ast.number(v)))
     }
 
     #[test]
     fn eval_number() {
-        let expr = num(42);
-        assert_eq!(eval_expr(&expr).to_number(), 42);
+        let mut ast = Ast::new();
+        let expr = ast.number(42);
+        assert_eq!(eval_expr(&ast, expr).to_number(), 42);
     }
 
     #[test]
     fn eval_addition() {
-        let expr = binop(num(2), '+', num(3));
-        assert_eq!(eval_expr(&expr).to_number(), 5);
+        let mut ast = Ast::new();
+        let expr = binop(&mut ast, 2, '+', 3);
+        assert_eq!(eval_expr(&ast, expr).to_number(), 5);
     }
 
     #[test]
     fn eval_multiplication_precedence() {
-        let expr = binop(num(2), '+', binop(num(3), '*', num(4)));
-        assert_eq!(eval_expr(&expr).to_number(), 14);
+        let mut ast = Ast::new();
+        let two = ast.number(2);
+        let product = binop(&mut ast, 3, '*', 4);
+        let expr = ast.binary(two, '+', product);
+        assert_eq!(eval_expr(&ast, expr).to_number(), 14);
     }
 
     #[test]
     fn eval_simple_dice_roll() {
-        let expr = dice(2, 6, vec![]);
-        match eval_expr(&expr) {
+        let mut ast = Ast::new();
+        let expr = dice(&mut ast, 2, 6, vec![]);
+        match eval_expr(&ast, expr) {
             EvalResult::Rolls(rolls) => {
                 assert_eq!(rolls.len(), 2);
                 assert!(rolls.iter().all(|&r| (1..=6).contains(&r)));
@@ -217,9 +1001,10 @@ mod tests {
 
     #[test]
     fn eval_repetition_roll() {
-        let inner_dice = dice(1, 6, vec![]);
-        let expr = rep(3, inner_dice, vec![]);
-        match eval_expr(&expr) {
+        let mut ast = Ast::new();
+        let inner_dice = dice(&mut ast, 1, 6, vec![]);
+        let expr = rep(&mut ast, 3, inner_dice, vec![]);
+        match eval_expr(&ast, expr) {
             EvalResult::Rolls(rolls) => {
                 assert_eq!(rolls.len(), 3);
                 assert!(rolls.iter().all(|&r| (1..=6).contains(&r)));
@@ -230,13 +1015,11 @@ mod tests {
 
     #[test]
     fn keep_high_modifier_works() {
-        let expr = Expr::Dice {
-            count: Box::new(num(5)),
-            sides: Box::new(num(6)),
-            modifiers: vec![modifier(DiceModifierType::KeepHigh, Some(3))],
-        };
+        let mut ast = Ast::new();
+        let keep = modifier(&mut ast, DiceModifierType::KeepHigh, Some(3));
+        let expr = dice(&mut ast, 5, 6, vec![keep]);
 
-        let EvalResult::Rolls(rolls) = eval_expr(&expr) else {
+        let EvalResult::Rolls(rolls) = eval_expr(&ast, expr) else {
             panic!("Expected rolls");
         };
 
@@ -245,13 +1028,11 @@ mod tests {
 
     #[test]
     fn drop_low_modifier_works() {
-        let expr = Expr::Dice {
-            count: Box::new(num(4)),
-            sides: Box::new(num(6)),
-            modifiers: vec![modifier(DiceModifierType::DropLow, Some(2))],
-        };
+        let mut ast = Ast::new();
+        let drop = modifier(&mut ast, DiceModifierType::DropLow, Some(2));
+        let expr = dice(&mut ast, 4, 6, vec![drop]);
 
-        let EvalResult::Rolls(rolls) = eval_expr(&expr) else {
+        let EvalResult::Rolls(rolls) = eval_expr(&ast, expr) else {
             panic!("Expected rolls");
         };
 
@@ -260,13 +1041,11 @@ mod tests {
 
     #[test]
     fn explode_modifier_works() {
-        let expr = Expr::Dice {
-            count: Box::new(num(2)),
-            sides: Box::new(num(3)),
-            modifiers: vec![modifier(DiceModifierType::Explode, None)],
-        };
+        let mut ast = Ast::new();
+        let explode = modifier(&mut ast, DiceModifierType::Explode, None);
+        let expr = dice(&mut ast, 2, 3, vec![explode]);
 
-        let EvalResult::Rolls(rolls) = eval_expr(&expr) else {
+        let EvalResult::Rolls(rolls) = eval_expr(&ast, expr) else {
             panic!("Expected rolls");
         };
 
@@ -274,12 +1053,512 @@ mod tests {
         assert!(rolls.iter().all(|&r| (1..=3).contains(&r)));
     }
 
+    fn sorted(mut rolls: Rolls) -> Vec<i32> {
+        rolls.sort();
+        rolls.into_vec()
+    }
+
+    #[test]
+    fn keep_and_drop_select_the_right_dice_in_order() {
+        let rolls: Rolls = smallvec![3, 6, 1, 4, 6, 2];
+        assert_eq!(keep_high(rolls.clone(), 3).into_vec(), vec![6, 6, 4]);
+        assert_eq!(keep_low(rolls.clone(), 2).into_vec(), vec![1, 2]);
+        assert_eq!(drop_high(rolls.clone(), 2).into_vec(), vec![4, 3, 2, 1]);
+        assert_eq!(drop_low(rolls.clone(), 1).into_vec(), vec![2, 3, 4, 6, 6]);
+    }
+
+    #[test]
+    fn keep_and_drop_clamp_counts() {
+        let rolls: Rolls = smallvec![5, 2, 4];
+        assert_eq!(sorted(keep_high(rolls.clone(), 10)), vec![2, 4, 5]);
+        assert!(keep_low(rolls.clone(), -1).is_empty());
+        assert!(drop_low(rolls.clone(), 5).is_empty());
+        assert_eq!(sorted(drop_high(rolls.clone(), 0)), vec![2, 4, 5]);
+    }
+
+    #[test]
+    fn eval_total_streams_large_pools() {
+        let mut ast = Ast::new();
+        let pool = dice(&mut ast, 100_000, 6, vec![]);
+        let total = eval_total(&ast, pool);
+        assert!((100_000..=600_000).contains(&total));
+
+        let inner = dice(&mut ast, 2, 6, vec![]);
+        let repeated = rep(&mut ast, 3, inner, vec![]);
+        let one = ast.number(1);
+        let expr = ast.binary(repeated, '+', one);
+        assert!((7..=37).contains(&eval_total(&ast, expr)));
+    }
+
+    #[test]
+    fn eval_total_applies_modifiers() {
+        let mut ast = Ast::new();
+        let keep = modifier(&mut ast, DiceModifierType::KeepHigh, Some(1));
+        let expr = dice(&mut ast, 4, 6, vec![keep]);
+        assert!((1..=6).contains(&eval_total(&ast, expr)));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(apply_function(Function::Half(Rounding::Down), &[7]), 3);
+        assert_eq!(apply_function(Function::Half(Rounding::Up), &[7]), 4);
+        assert_eq!(apply_function(Function::Half(Rounding::Up), &[8]), 4);
+        assert_eq!(apply_function(Function::Double, &[7]), 14);
+        assert_eq!(apply_function(Function::Clamp, &[-3, 1, 20]), 1);
+        assert_eq!(apply_function(Function::Clamp, &[12, 1, 20]), 12);
+        assert_eq!(apply_function(Function::Clamp, &[12, 5, 3]), 3);
+        fastrand::seed(1);
+        let mut seen = [false; 3];
+        for _ in 0..100 {
+            let n = apply_function(Function::Between, &[-1, 1]);
+            seen[(n + 1) as usize] = true;
+            assert!((37..=213).contains(&apply_function(Function::Between, &[213, 37])));
+        }
+        assert_eq!(seen, [true; 3]);
+
+        let mut ast = Ast::new();
+        let d6 = dice(&mut ast, 3, 6, vec![]);
+        let half = ast.call(Function::Half(Rounding::Down), vec![d6]);
+        fastrand::seed(2);
+        let (total, text) = eval_breakdown(&ast, half);
+        let inner = text
+            .strip_prefix("half([")
+            .and_then(|t| t.split_once("] = "))
+            .and_then(|(_, t)| t.strip_suffix(')'))
+            .unwrap();
+        assert_eq!(total, inner.parse::<i32>().unwrap() / 2);
+    }
+
+    #[test]
+    fn annotations_read_pools_die_by_die() {
+        let pool = EvalResult::Rolls(smallvec![5, 1, 6, 2, 5]);
+        let count = Annotation::Count(Compare::GreaterOrEqual, 5);
+        assert_eq!(eval_annotation(&pool, Annotation::Sum), 19);
+        assert_eq!(eval_annotation(&pool, count), 3);
+        assert_eq!(eval_annotation(&pool, Annotation::Max), 6);
+        assert_eq!(eval_annotation(&pool, Annotation::Min), 1);
+
+        let pool = EvalResult::Rolls(smallvec![4, 2, 6, 3, 2, 6]);
+        assert_eq!(eval_annotation(&pool, Annotation::Distinct), 4);
+        assert_eq!(eval_annotation(&pool, Annotation::Straight), 3);
+        let pool = EvalResult::Rolls(smallvec![1, 3, 5]);
+        assert_eq!(eval_annotation(&pool, Annotation::Straight), 1);
+
+        let total = EvalResult::Number(9);
+        assert_eq!(eval_annotation(&total, Annotation::Max), 9);
+        assert_eq!(eval_annotation(&total, count), 1);
+    }
+
+    #[test]
+    fn count_readings_mark_each_check() {
+        let reading = Annotation::Count(Compare::GreaterOrEqual, 15);
+        let result = EvalResult::Rolls(smallvec![17, 9, 15]);
+        assert_eq!(eval_annotation(&result, reading), 2);
+        assert_eq!(
+            format_checks(&result, reading),
+            "[17 pass, 9 fail, 15 pass]"
+        );
+
+        let mut ast = Ast::new();
+        let d20 = dice(&mut ast, 1, 20, vec![]);
+        let attacks = rep(&mut ast, 10, d20, vec![]);
+        let hits = ast.read(attacks, reading);
+        let (total, text) = eval_breakdown(&ast, hits);
+        assert_eq!(text.matches(" pass").count() as i32, total);
+        assert_eq!(text.matches(", ").count(), 9);
+        assert!(text.ends_with("] >= 15"), "{}", text);
+    }
+
+    #[test]
+    fn choices_follow_their_weights() {
+        let choice = |text: &str, weight| Choice {
+            text: text.to_string(),
+            weight,
+        };
+        let choices = [choice("sword", 3), choice("bow", 1)];
+        let mut counts = [0; 2];
+        fastrand::seed(12);
+        for _ in 0..4000 {
+            counts[choose(&choices)] += 1;
+        }
+        assert!((2800..3200).contains(&counts[0]), "{:?}", counts);
+        assert_eq!(choose(&[choice("staff", 5)]), 0);
+
+        let result = pick(&choices);
+        assert_eq!(
+            format_rolls(&result),
+            choices[result.to_number() as usize].text
+        );
+        assert_eq!(result.dice(), [result.to_number()]);
+    }
+
+    #[test]
+    fn modifier_values_can_roll_dice() {
+        let mut ast = Ast::new();
+        let count = dice(&mut ast, 1, 4, vec![]);
+        let keep = DiceModifier::new(DiceModifierType::KeepHigh, Some(count));
+        let expr = dice(&mut ast, 10, 6, vec![keep]);
+
+        let mut seen = [false; 4];
+        fastrand::seed(8);
+        for _ in 0..100 {
+            let EvalResult::Rolls(rolls) = eval_expr(&ast, expr) else {
+                panic!("Expected rolls");
+            };
+            seen[rolls.len() - 1] = true;
+        }
+        assert_eq!(seen, [true; 4]);
+    }
+
+    #[test]
+    fn repetition_modifiers_apply_inside_and_across_groups() {
+        let mut ast = Ast::new();
+        let keep = modifier(&mut ast, DiceModifierType::KeepHigh, Some(3));
+        let group = dice(&mut ast, 4, 6, vec![keep]);
+        let drop = modifier(&mut ast, DiceModifierType::DropLow, Some(1));
+        let expr = rep(&mut ast, 6, group, vec![drop]);
+
+        for _ in 0..20 {
+            let EvalResult::Rolls(groups) = eval_expr(&ast, expr) else {
+                panic!("Expected rolls");
+            };
+            assert_eq!(groups.len(), 5);
+            assert!(groups.iter().all(|g| (3..=18).contains(g)));
+        }
+
+        let EvalResult::Rolls(kept) = eval_expr(&ast, group) else {
+            panic!("Expected rolls");
+        };
+        assert_eq!(kept.len(), 3);
+    }
+
+    #[test]
+    fn groups_keep_single_dice_across_pools() {
+        let mut ast = Ast::new();
+        let d12 = dice(&mut ast, 1, 12, vec![]);
+        let d6 = dice(&mut ast, 2, 6, vec![]);
+        let five = ast.number(5);
+        let keep = modifier(&mut ast, DiceModifierType::KeepHigh, Some(1));
+        let group = ast.group(vec![d12, d6], vec![keep]);
+        let all = ast.group(vec![d12, d6, five], vec![]);
+
+        fastrand::seed(6);
+        for _ in 0..50 {
+            let EvalResult::Rolls(kept) = eval_expr(&ast, group) else {
+                panic!("Expected rolls");
+            };
+            assert_eq!(kept.len(), 1);
+            assert!((1..=12).contains(&kept[0]));
+
+            let EvalResult::Rolls(rolls) = eval_expr(&ast, all) else {
+                panic!("Expected rolls");
+            };
+            assert_eq!(rolls.len(), 4);
+            assert_eq!(rolls[3], 5);
+        }
+    }
+
+    #[test]
+    fn pool_rerolls_stop_when_the_condition_fails() {
+        let condition = Condition {
+            stat: Annotation::Sum,
+            compare: crate::ast::Compare::Less,
+            value: 8,
+        };
+        let mut rolls = [3, 12, 5].into_iter();
+        let mut next = || EvalResult::Rolls(smallvec![rolls.next().unwrap()]);
+        assert_eq!(reroll_while(condition, 100, &mut next).to_number(), 12);
+        assert_eq!(reroll_while(condition, 0, &mut next).to_number(), 5);
+
+        let mut ast = Ast::new();
+        let pool = dice(&mut ast, 4, 6, vec![]);
+        let expr = ast.reroll(pool, condition, 100);
+        fastrand::seed(10);
+        for _ in 0..100 {
+            assert!(eval_total(&ast, expr) >= 8);
+        }
+        let low = ast.reroll(pool, condition, 1);
+        assert!((4..=24).contains(&eval_total(&ast, low)));
+    }
+
+    #[test]
+    fn seeded_repetitions_are_reproducible() {
+        let mut ast = Ast::new();
+        let inner = dice(&mut ast, 3, 6, vec![]);
+        let expr = rep(&mut ast, 200, inner, vec![]);
+
+        fastrand::seed(7);
+        let EvalResult::Rolls(first) = eval_expr(&ast, expr) else {
+            panic!("Expected rolls");
+        };
+        fastrand::seed(7);
+        let EvalResult::Rolls(second) = eval_expr(&ast, expr) else {
+            panic!("Expected rolls");
+        };
+        assert_eq!(first, second);
+
+        fastrand::seed(7);
+        assert_eq!(eval_total(&ast, expr), first.iter().sum::<i32>());
+    }
+
+    #[test]
+    fn explosions_chain_once_per_die() {
+        fastrand::seed(11);
+        let rolls = explode(smallvec![6, 2], 6, Faces::AtLeast(6));
+        let sixes = rolls.iter().filter(|&&r| r == 6).count();
+        assert_eq!(rolls.len(), 2 + sixes);
+    }
+
+    #[test]
+    fn explosions_trigger_on_a_set_of_faces() {
+        fastrand::seed(3);
+        let rolls = explode(smallvec![1, 3, 6], 6, Faces::Set(&[1, 6]));
+        let triggers = rolls.iter().filter(|&&r| r == 1 || r == 6).count();
+        assert_eq!(rolls.len(), 3 + triggers);
+
+        assert!(Faces::Set(&[1, 6]).contains(1));
+        assert!(!Faces::Set(&[1, 6]).contains(5));
+        assert!(Faces::AtLeast(5).contains(6));
+    }
+
+    #[test]
+    fn reroll_and_add_triggers_once_on_the_value() {
+        fastrand::seed(9);
+        for _ in 0..50 {
+            let rolls = apply_modifier(
+                smallvec![4, 6, 2],
+                &DiceModifierType::RerollAdd,
+                Some(4),
+                &[],
+                Some(6),
+            );
+            assert!((5..=10).contains(&rolls[0]));
+            assert_eq!(rolls[1..], [6, 2]);
+        }
+        let rolls = apply_modifier(
+            smallvec![1, 6],
+            &DiceModifierType::RerollAdd,
+            None,
+            &[],
+            Some(6),
+        );
+        assert_eq!(rolls[0], 1);
+        assert!((7..=12).contains(&rolls[1]));
+    }
+
+    #[test]
+    fn rerolls_replace_low_dice() {
+        fastrand::seed(5);
+        for _ in 0..50 {
+            let rolls = apply_modifier(
+                smallvec![1, 2, 5],
+                &DiceModifierType::Reroll,
+                Some(2),
+                &[],
+                Some(6),
+            );
+            assert!(rolls[..2].iter().all(|r| (3..=6).contains(r)));
+            assert_eq!(rolls[2], 5);
+
+            let rolls = apply_modifier(
+                smallvec![1, 3],
+                &DiceModifierType::RerollOnce,
+                None,
+                &[],
+                Some(6),
+            );
+            assert!((1..=6).contains(&rolls[0]));
+            assert_eq!(rolls[1], 3);
+        }
+        // Rerolling every face gives up after the limit instead of hanging.
+        let rolls = reroll(smallvec![1], 1, Faces::AtMost(1), DEFAULT_REROLL_LIMIT);
+        assert_eq!(rolls[..], [1]);
+    }
+
+    #[test]
+    fn kept_dice_leave_out_dropped_dice() {
+        fastrand::seed(12);
+        for input in [
+            "2d20kl1",
+            "{1d20, 1d20}kh1",
+            "3(1d20)kl1",
+            "2d20h",
+            "1d20rr(sum<15)",
+        ] {
+            let ast = crate::parser::parse(input).unwrap();
+            for _ in 0..20 {
+                let (total, dice) = kept_dice(|| eval_total(&ast, ast.roots()[0]));
+                assert_eq!(dice, [(20, total)], "{}", input);
+            }
+        }
+
+        let ast = crate::parser::parse("4d6kh3 + 4dF + 1d8").unwrap();
+        let (total, dice) = kept_dice(|| eval_breakdown(&ast, ast.roots()[0]).0);
+        let sizes: Vec<i32> = dice.iter().map(|&(sides, _)| sides).collect();
+        assert_eq!(sizes, [6, 6, 6, 8]);
+        assert!((total - dice.iter().map(|&(_, v)| v).sum::<i32>()).abs() <= 4);
+        assert!(!collecting());
+    }
+
+    #[test]
+    fn compounding_adds_into_the_die() {
+        fastrand::seed(4);
+        for _ in 0..100 {
+            let rolls = compound(smallvec![10, 3], 10, Faces::AtLeast(10));
+            assert_eq!(rolls.len(), 2);
+            assert!(rolls[0] > 10 && rolls[0] % 10 != 0);
+            assert_eq!(rolls[1], 3);
+        }
+    }
+
+    #[test]
+    fn success_pools_count_and_botch() {
+        let rolls: Rolls = smallvec![8, 3, 10, 1];
+        let modifiers = [(DiceModifierType::Target, Some(8), &[][..])];
+        let result = apply_modifiers(rolls, modifiers, Some(10));
+        assert_eq!(result.to_number(), 2);
+        assert_eq!(describe_successes(&result), "2 successes");
+
+        let result = apply_modifiers(
+            smallvec![1, 4],
+            [(DiceModifierType::Wod, None, &[][..])],
+            Some(10),
+        );
+        assert!(result.is_botch());
+        assert_eq!(describe_successes(&result), "botch");
+    }
+
+    #[test]
+    fn doubles_count_twice() {
+        let rolls: Rolls = smallvec![10, 7, 3, 10, 9];
+        let modifiers = [
+            (DiceModifierType::Target, Some(7), &[][..]),
+            (DiceModifierType::Double, None, &[][..]),
+        ];
+        let result = apply_modifiers(rolls, modifiers, Some(10));
+        assert_eq!(result.to_number(), 6);
+        assert_eq!(format_rolls(&result), "[10*, 7, 3, 10*, 9]");
+    }
+
+    #[test]
+    fn wod_rerolls_tens() {
+        fastrand::seed(5);
+        for _ in 0..50 {
+            let rolls: Rolls = (0..8).map(|_| roll(10)).collect();
+            let result = apply_modifiers(rolls, [(DiceModifierType::Wod, None, &[][..])], Some(10));
+            let EvalResult::Successes {
+                rolls, successes, ..
+            } = result
+            else {
+                panic!("Expected successes");
+            };
+            let tens = rolls.iter().filter(|&&r| r == 10).count();
+            assert_eq!(rolls.len(), 8 + tens);
+            assert_eq!(
+                successes as usize,
+                rolls.iter().filter(|&&r| r >= 8).count()
+            );
+        }
+    }
+
+    #[test]
+    fn fudge_dice_and_breakdown() {
+        assert_eq!(format_fudge(&[1, -1, 0, 1]), "[+][−][ ][+]");
+
+        let mut ast = Ast::new();
+        let four = ast.number(4);
+        let fudge = ast.fudge(four, vec![]);
+        let three = ast.number(3);
+        let expr = ast.binary(fudge, '+', three);
+
+        let (total, text) = eval_breakdown(&ast, expr);
+        assert!((-1..=7).contains(&total));
+        assert!(text.starts_with('[') && text.ends_with("] + 3"), "{}", text);
+    }
+
+    #[test]
+    fn contests_total_the_margin_and_break_ties() {
+        let mut ast = Ast::new();
+        let (first, second) = (ast.number(12), ast.number(9));
+        let contest = ast.contest(first, second);
+        assert_eq!(eval_total(&ast, contest), 3);
+
+        assert_eq!(TieBreak::Draw.resolve(12, 9), Ordering::Greater);
+        assert_eq!(TieBreak::Draw.resolve(9, 9), Ordering::Equal);
+        assert_eq!(TieBreak::First.resolve(9, 9), Ordering::Greater);
+        assert_eq!(TieBreak::Second.resolve(9, 9), Ordering::Less);
+        assert_eq!(describe_contest(Ordering::Less), "second wins");
+    }
+
+    #[test]
+    fn tiers_count_the_thresholds_reached() {
+        let mut ast = Ast::new();
+        let total = ast.number(17);
+        let tiers = ast.tiers(total, vec![10, 15, 20]);
+        assert_eq!(eval_total(&ast, tiers), 2);
+
+        assert_eq!(tier_reached(9, &[10, 15, 20]), 0);
+        assert_eq!(tier_reached(20, &[10, 15, 20]), 3);
+        assert_eq!(describe_tier(2, 3), "full");
+        assert_eq!(describe_tier(1, 2), "partial");
+        assert_eq!(describe_tier(4, 5), "tier 4");
+    }
+
     #[test]
     fn test_division_by_zero_panics() {
-        let expr = binop(num(4), '/', num(0));
+        let mut ast = Ast::new();
+        let expr = binop(&mut ast, 4, '/', 0);
         let result = std::panic::catch_unwind(|| {
-            eval_expr(&expr);
+            eval_expr(&ast, expr);
         });
         assert!(result.is_err());
     }
+
+    #[test]
+    fn tallies_dice_only_while_asked() {
+        // Other tests roll at the same time, so look only at an odd size.
+        roll(7919);
+        start_tally();
+        let rolled: Vec<i32> = (0..5).map(|_| roll(7919)).collect();
+        let tally = take_tally();
+        roll(7919);
+        let tallied: Vec<i32> = tally
+            .iter()
+            .filter(|(sides, _)| *sides == 7919)
+            .map(|&(_, value)| value)
+            .collect();
+        assert_eq!(tallied, rolled);
+        assert!(take_tally().is_empty());
+    }
+
+    #[test]
+    fn antithetic_pairs_mirror_large_repetitions() {
+        // Enough repetitions to be spread across threads when that is on.
+        let ast = crate::parser::parse("100(1d6)").unwrap();
+        let (first, second) = Antithetic::start().pair(|| eval_total(&ast, ast.roots()[0]));
+        assert_eq!(first + second, 700);
+    }
+
+    #[test]
+    fn antithetic_pairs_mirror_every_die() {
+        let antithetic = Antithetic::start();
+        let (first, second) = antithetic.pair(|| {
+            let mut rolls: Vec<i32> = (0..20).map(|_| roll(6)).collect();
+            rolls.push(roll_fudge());
+            rolls
+        });
+        for (a, b) in first.iter().zip(&second).take(20) {
+            assert_eq!(a + b, 7);
+        }
+        assert_eq!(first[20], -second[20]);
+
+        // Dice past the end of the first run roll fresh.
+        let mut runs = 0;
+        let (_, extra) = antithetic.pair(|| {
+            runs += 1;
+            (0..=runs).map(|_| roll(6)).collect::<Vec<_>>()
+        });
+        assert_eq!(extra.len(), 3);
+        assert!(extra.iter().all(|v| (1..=6).contains(v)));
+    }
 }
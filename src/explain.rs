@@ -0,0 +1,316 @@
+//! Describes a parsed expression without rolling it, for `roll explain`:
+//! an indented tree for people debugging precedence, and JSON for tools.
+//! Nodes are shown as parsed, before constant folding.
+
+use crate::ast::{Ast, DiceModifier, ExprId, ExprKind, Function, Rounding};
+
+/// A scalar field of a node.
+enum Field {
+    Number(i32),
+    Text(String),
+    Numbers(Vec<i32>),
+    Texts(Vec<String>),
+}
+
+/// A node's children, by the role they play in it.
+enum Child {
+    One(&'static str, ExprId),
+    Many(&'static str, Vec<ExprId>),
+}
+
+struct Node {
+    kind: &'static str,
+    fields: Vec<(&'static str, Field)>,
+    children: Vec<Child>,
+}
+
+fn modifiers(ast: &Ast, modifiers: &[DiceModifier]) -> Vec<(&'static str, Field)> {
+    if modifiers.is_empty() {
+        return Vec::new();
+    }
+    let texts = modifiers
+        .iter()
+        .map(|m| {
+            let mut text = m.kind.to_string();
+            if let Some(value) = m.value {
+                match ast[value].kind {
+                    ExprKind::Number(n) => text.push_str(&n.to_string()),
+                    _ => text.push_str(&format!("({})", ast.display(value))),
+                }
+            }
+            if !m.faces.is_empty() {
+                let faces: Vec<String> = m.faces.iter().map(i32::to_string).collect();
+                text.push_str(&format!("{{{}}}", faces.join(",")));
+            }
+            text
+        })
+        .collect();
+    vec![("modifiers", Field::Texts(texts))]
+}
+
+fn describe(ast: &Ast, id: ExprId) -> Node {
+    let node = |kind, fields, children| Node {
+        kind,
+        fields,
+        children,
+    };
+    match &ast[id].kind {
+        ExprKind::Number(n) => node("number", vec![("value", Field::Number(*n))], vec![]),
+        ExprKind::Dice {
+            count,
+            sides,
+            modifiers: m,
+        } => node(
+            "dice",
+            modifiers(ast, m),
+            vec![Child::One("count", *count), Child::One("sides", *sides)],
+        ),
+        ExprKind::Fudge {
+            count,
+            modifiers: m,
+        } => node(
+            "fudge",
+            modifiers(ast, m),
+            vec![Child::One("count", *count)],
+        ),
+        ExprKind::BinaryOp(lhs, op, rhs) => node(
+            "binary",
+            vec![("op", Field::Text(op.to_string()))],
+            vec![Child::One("lhs", *lhs), Child::One("rhs", *rhs)],
+        ),
+        ExprKind::Call { function, args } => {
+            let mut fields = vec![("function", Field::Text(function.name().to_string()))];
+            if let Function::Half(Rounding::Up) = function {
+                fields.push(("rounding", Field::Text("up".to_string())));
+            }
+            node("call", fields, vec![Child::Many("args", args.clone())])
+        }
+        ExprKind::Read { pool, reading } => node(
+            "read",
+            vec![("reading", Field::Text(reading.to_string()))],
+            vec![Child::One("pool", *pool)],
+        ),
+        ExprKind::Repetition {
+            count,
+            expr,
+            modifiers: m,
+        } => node(
+            "repetition",
+            modifiers(ast, m),
+            vec![Child::One("count", *count), Child::One("expr", *expr)],
+        ),
+        ExprKind::Group {
+            members,
+            modifiers: m,
+        } => node(
+            "group",
+            modifiers(ast, m),
+            vec![Child::Many("members", members.clone())],
+        ),
+        ExprKind::Reroll {
+            pool,
+            condition,
+            limit,
+        } => node(
+            "reroll",
+            vec![
+                ("condition", Field::Text(condition.to_string())),
+                ("limit", Field::Number(*limit as i32)),
+            ],
+            vec![Child::One("pool", *pool)],
+        ),
+        ExprKind::Choose(choices) => {
+            let choices = choices
+                .iter()
+                .map(|c| format!("{}:{}", c.text, c.weight))
+                .collect();
+            node("choose", vec![("choices", Field::Texts(choices))], vec![])
+        }
+        ExprKind::Contest(first, second) => node(
+            "contest",
+            vec![],
+            vec![Child::One("first", *first), Child::One("second", *second)],
+        ),
+        ExprKind::Tiers { expr, thresholds } => node(
+            "tiers",
+            vec![("thresholds", Field::Numbers(thresholds.clone()))],
+            vec![Child::One("expr", *expr)],
+        ),
+    }
+}
+
+/// Formats every root as an indented tree, one node per line with its
+/// fields and source range:
+///
+/// ```text
+/// binary op=+ @0..8
+///   lhs: dice modifiers=[kh3] @0..6
+///     count: number value=4 @0..1
+/// ```
+pub fn tree(ast: &Ast) -> String {
+    let mut out = String::new();
+    for &root in ast.roots() {
+        let mut heading = vec!["root".to_string()];
+        if let Some(label) = ast.label(root) {
+            heading.push(format!("label={}", label));
+        }
+        let annotations: Vec<String> = ast
+            .annotations(root)
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        if !annotations.is_empty() {
+            heading.push(format!("annotations=[{}]", annotations.join(", ")));
+        }
+        out.push_str(&heading.join(" "));
+        out.push('\n');
+        write_tree(ast, root, "", 1, &mut out);
+    }
+    out
+}
+
+fn write_tree(ast: &Ast, id: ExprId, role: &str, depth: usize, out: &mut String) {
+    let node = describe(ast, id);
+    out.push_str(&"  ".repeat(depth));
+    out.push_str(role);
+    out.push_str(node.kind);
+    for (name, field) in &node.fields {
+        let value = match field {
+            Field::Number(n) => n.to_string(),
+            Field::Text(text) => text.clone(),
+            Field::Numbers(numbers) => format!("{:?}", numbers),
+            Field::Texts(texts) => format!("[{}]", texts.join(", ")),
+        };
+        out.push_str(&format!(" {}={}", name, value));
+    }
+    let span = ast[id].span;
+    out.push_str(&format!(" @{}..{}\n", span.start, span.end));
+    for child in node.children {
+        match child {
+            Child::One(role, child) => {
+                write_tree(ast, child, &format!("{}: ", role), depth + 1, out)
+            }
+            Child::Many(role, children) => {
+                for (i, child) in children.into_iter().enumerate() {
+                    write_tree(ast, child, &format!("{}[{}]: ", role, i), depth + 1, out);
+                }
+            }
+        }
+    }
+}
+
+/// Formats the roots as JSON: `{"roots":[{"label":null,"annotations":[],
+/// "expr":{"kind":"number","span":[0,1],"value":4}}]}`. Children appear
+/// under their role, and lists of them as arrays.
+pub fn to_json(ast: &Ast) -> String {
+    let roots: Vec<String> = ast
+        .roots()
+        .iter()
+        .map(|&root| {
+            let annotations: Vec<String> = ast
+                .annotations(root)
+                .iter()
+                .map(|a| quote(&a.to_string()))
+                .collect();
+            format!(
+                "{{\"label\":{},\"annotations\":[{}],\"expr\":{}}}",
+                ast.label(root).map_or("null".to_string(), quote),
+                annotations.join(","),
+                node_json(ast, root)
+            )
+        })
+        .collect();
+    format!("{{\"roots\":[{}]}}", roots.join(","))
+}
+
+fn node_json(ast: &Ast, id: ExprId) -> String {
+    let node = describe(ast, id);
+    let span = ast[id].span;
+    let mut members = vec![
+        format!("\"kind\":{}", quote(node.kind)),
+        format!("\"span\":[{},{}]", span.start, span.end),
+    ];
+    for (name, field) in &node.fields {
+        let value = match field {
+            Field::Number(n) => n.to_string(),
+            Field::Text(text) => quote(text),
+            Field::Numbers(numbers) => format!("{:?}", numbers).replace(' ', ""),
+            Field::Texts(texts) => {
+                let quoted: Vec<String> = texts.iter().map(|t| quote(t)).collect();
+                format!("[{}]", quoted.join(","))
+            }
+        };
+        members.push(format!("\"{}\":{}", name, value));
+    }
+    for child in node.children {
+        match child {
+            Child::One(role, child) => {
+                members.push(format!("\"{}\":{}", role, node_json(ast, child)))
+            }
+            Child::Many(role, children) => {
+                let children: Vec<String> =
+                    children.into_iter().map(|c| node_json(ast, c)).collect();
+                members.push(format!("\"{}\":[{}]", role, children.join(",")));
+            }
+        }
+    }
+    format!("{{{}}}", members.join(","))
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn prints_a_tree() {
+        let ast = parse("4d6kh3+2").unwrap();
+        assert_eq!(
+            tree(&ast),
+            "root\n\
+             \x20 binary op=+ @0..8\n\
+             \x20   lhs: dice modifiers=[kh3] @0..6\n\
+             \x20     count: number value=4 @0..1\n\
+             \x20     sides: number value=6 @2..3\n\
+             \x20   rhs: number value=2 @7..8\n"
+        );
+    }
+
+    #[test]
+    fn shows_labels_and_lists() {
+        let ast = parse("atk: clamp(1d20, 2, 19) [sum, max]").unwrap();
+        let tree = tree(&ast);
+        assert!(
+            tree.starts_with("root label=atk annotations=[sum, max]\n"),
+            "{}",
+            tree
+        );
+        assert!(tree.contains("args[2]: number value=19"), "{}", tree);
+    }
+
+    #[test]
+    fn prints_json() {
+        let ast = parse("2d6 vs 10/15").unwrap();
+        assert_eq!(
+            to_json(&ast),
+            "{\"roots\":[{\"label\":null,\"annotations\":[],\"expr\":\
+             {\"kind\":\"tiers\",\"span\":[0,12],\"thresholds\":[10,15],\"expr\":\
+             {\"kind\":\"dice\",\"span\":[0,3],\
+             \"count\":{\"kind\":\"number\",\"span\":[0,1],\"value\":2},\
+             \"sides\":{\"kind\":\"number\",\"span\":[2,3],\"value\":6}}}}]}"
+        );
+    }
+}
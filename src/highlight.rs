@@ -0,0 +1,242 @@
+use pest::Parser;
+use pest::iterators::Pair;
+
+use crate::ast::Span;
+use crate::parser::{DiceParser, Rule};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Number,
+    Dice,
+    Modifier,
+    Operator,
+    Paren,
+    /// A function name, such as `half`.
+    Function,
+    /// A root's label, with its colon.
+    Label,
+    /// Words to pick from, as in `choose{sword, bow}`.
+    Text,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Splits `input` into classified tokens for syntax highlighting.
+///
+/// Unlike [`parse`](crate::parser::parse) this never fails: text past the
+/// longest valid prefix is reported as [`TokenKind::Unknown`], so partially
+/// typed expressions still highlight. Whitespace is not reported.
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let mut leaves = Vec::new();
+    let mut parsed = 0;
+
+    if let Ok(pairs) = DiceParser::parse(Rule::dice_expr, input) {
+        for pair in pairs {
+            parsed = pair.as_span().end();
+            collect_leaves(pair, &mut leaves);
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    for leaf in leaves {
+        fill_gap(input, pos..leaf.span.start, parsed, &mut tokens);
+        pos = leaf.span.end;
+        tokens.push(leaf);
+    }
+    fill_gap(input, pos..input.len(), parsed, &mut tokens);
+
+    tokens
+}
+
+fn collect_leaves(pair: Pair<Rule>, tokens: &mut Vec<Token>) {
+    let kind = match pair.as_rule() {
+        Rule::number => TokenKind::Number,
+        Rule::roll | Rule::fudge | Rule::rk => TokenKind::Dice,
+        Rule::compound
+        | Rule::explode
+        | Rule::keep_high
+        | Rule::keep_low
+        | Rule::drop_high
+        | Rule::drop_low
+        | Rule::target
+        | Rule::wod
+        | Rule::double
+        | Rule::reroll_add
+        | Rule::reroll_once
+        | Rule::reroll_die
+        | Rule::rr
+        | Rule::count
+        | Rule::sum
+        | Rule::max
+        | Rule::min
+        | Rule::reading
+        | Rule::pool_stat => TokenKind::Modifier,
+        Rule::comparison => TokenKind::Operator,
+        Rule::fn_half | Rule::fn_double | Rule::fn_clamp | Rule::fn_choose | Rule::fn_between => {
+            TokenKind::Function
+        }
+        Rule::choice_text => TokenKind::Text,
+        Rule::rounding => TokenKind::Modifier,
+        Rule::label => TokenKind::Label,
+        Rule::add
+        | Rule::subtract
+        | Rule::multiply
+        | Rule::divide
+        | Rule::vs
+        | Rule::tier_sep
+        | Rule::weight_sep
+        | Rule::range_sep
+        | Rule::comma => TokenKind::Operator,
+        Rule::weight => TokenKind::Number,
+        _ => {
+            for child in pair.into_inner() {
+                collect_leaves(child, tokens);
+            }
+            return;
+        }
+    };
+    tokens.push(Token {
+        kind,
+        span: pair.as_span().into(),
+    });
+}
+
+/// Classifies the text between two leaf tokens: brackets inside the parsed
+/// prefix are parens, any other run of non-whitespace is unknown.
+fn fill_gap(input: &str, range: std::ops::Range<usize>, parsed: usize, tokens: &mut Vec<Token>) {
+    let mut unknown_start = None;
+
+    for (offset, c) in input[range.clone()].char_indices() {
+        let at = range.start + offset;
+        let is_paren = at < parsed && matches!(c, '(' | ')' | '[' | ']' | '{' | '}');
+
+        if c.is_whitespace() || is_paren {
+            if let Some(start) = unknown_start.take() {
+                tokens.push(Token {
+                    kind: TokenKind::Unknown,
+                    span: Span::new(start, at),
+                });
+            }
+            if is_paren {
+                tokens.push(Token {
+                    kind: TokenKind::Paren,
+                    span: Span::new(at, at + 1),
+                });
+            }
+        } else if unknown_start.is_none() {
+            unknown_start = Some(at);
+        }
+    }
+
+    if let Some(start) = unknown_start {
+        tokens.push(Token {
+            kind: TokenKind::Unknown,
+            span: Span::new(start, range.end),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(input)
+            .into_iter()
+            .map(|t| (t.kind, &input[t.span.start..t.span.end]))
+            .collect()
+    }
+
+    #[test]
+    fn test_dice_with_modifier() {
+        assert_eq!(
+            kinds("4d6kh3 + 2"),
+            vec![
+                (TokenKind::Number, "4"),
+                (TokenKind::Dice, "d"),
+                (TokenKind::Number, "6"),
+                (TokenKind::Modifier, "kh"),
+                (TokenKind::Number, "3"),
+                (TokenKind::Operator, "+"),
+                (TokenKind::Number, "2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_drop_high_is_a_modifier() {
+        assert_eq!(
+            kinds("(2)d6d1"),
+            vec![
+                (TokenKind::Paren, "("),
+                (TokenKind::Number, "2"),
+                (TokenKind::Paren, ")"),
+                (TokenKind::Dice, "d"),
+                (TokenKind::Number, "6"),
+                (TokenKind::Modifier, "d"),
+                (TokenKind::Number, "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_contest_keyword_is_an_operator() {
+        assert_eq!(
+            kinds("3 vs 4"),
+            vec![
+                (TokenKind::Number, "3"),
+                (TokenKind::Operator, "vs"),
+                (TokenKind::Number, "4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_partial_input() {
+        assert_eq!(
+            kinds("2d6 + foo"),
+            vec![
+                (TokenKind::Number, "2"),
+                (TokenKind::Dice, "d"),
+                (TokenKind::Number, "6"),
+                (TokenKind::Unknown, "+"),
+                (TokenKind::Unknown, "foo"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(
+            kinds("atk: d20"),
+            vec![
+                (TokenKind::Label, "atk:"),
+                (TokenKind::Dice, "d"),
+                (TokenKind::Number, "20"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_choose() {
+        assert_eq!(
+            kinds("choose{long bow:2, axe}"),
+            vec![
+                (TokenKind::Function, "choose"),
+                (TokenKind::Paren, "{"),
+                (TokenKind::Text, "long bow"),
+                (TokenKind::Operator, ":"),
+                (TokenKind::Number, "2"),
+                (TokenKind::Operator, ","),
+                (TokenKind::Text, "axe"),
+                (TokenKind::Paren, "}"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,17 @@
+pub mod ast;
+pub mod compile;
+pub mod deck;
+pub mod distribution;
+pub mod eval;
+pub mod explain;
+pub mod highlight;
+pub mod lint;
+pub mod macros;
+pub mod optimize;
+pub mod parser;
+pub mod presets;
+pub mod rng;
+pub mod sim;
+pub mod style;
+pub mod symbols;
+pub mod table;
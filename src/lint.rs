@@ -0,0 +1,481 @@
+//! Checks expressions for mistakes that parse but are unlikely to be what
+//! the writer meant, such as keeping more dice than a pool rolls or an
+//! explosion that never stops.
+
+use std::fmt;
+
+use pest::Parser;
+use pest::error::InputLocation;
+
+use crate::ast::{Ast, DiceModifier, DiceModifierType, ExprId, ExprKind, Function, Span};
+use crate::parser::{DiceParser, Rule, parse_unchecked};
+
+/// Pools and repetitions larger than this are flagged; they take a long
+/// time to roll and are more likely a typo than a real roll.
+pub const MAX_POOL: i32 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The expression does not parse, or cannot finish rolling.
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    /// Where in the input the problem is.
+    pub span: Span,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(severity: Severity, span: Span, message: String) -> Self {
+        Problem {
+            severity,
+            span,
+            message,
+        }
+    }
+}
+
+/// Parses `input` and reports every problem found, in source order.
+pub fn lint(input: &str) -> Vec<Problem> {
+    if let Err(err) = DiceParser::parse(Rule::input, input) {
+        let at = match err.location {
+            InputLocation::Pos(at) | InputLocation::Span((at, _)) => at,
+        };
+        let message = err.variant.message().to_string();
+        return vec![Problem::new(Severity::Error, Span::new(at, at), message)];
+    }
+    match parse_unchecked(input) {
+        Ok(ast) => problems(&ast),
+        Err(message) => {
+            let span = Span::new(0, input.len());
+            vec![Problem::new(Severity::Error, span, message)]
+        }
+    }
+}
+
+/// Every problem in a parsed expression, in source order.
+pub fn problems(ast: &Ast) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for &root in ast.roots() {
+        check(ast, root, &mut problems);
+    }
+    problems.sort_by_key(|p| p.span.start);
+    problems
+}
+
+fn check(ast: &Ast, id: ExprId, problems: &mut Vec<Problem>) {
+    for child in ast.children(id) {
+        check(ast, child, problems);
+    }
+    let constant = |id: ExprId| match ast[id].kind {
+        ExprKind::Number(n) => Some(n),
+        _ => None,
+    };
+    let check_size = |count: ExprId, noun: &str, problems: &mut Vec<Problem>| {
+        if let Some(count) = constant(count).filter(|&c| c > MAX_POOL) {
+            problems.push(Problem::new(
+                Severity::Warning,
+                ast[id].span,
+                format!("rolls {} {}, more than {}", count, noun, MAX_POOL),
+            ));
+        }
+    };
+    match &ast[id].kind {
+        ExprKind::Dice {
+            count,
+            sides,
+            modifiers,
+        } => {
+            check_size(*count, "dice", problems);
+            let sides = constant(*sides);
+            if sides.is_some_and(|s| s < 1) {
+                problems.push(Problem::new(
+                    Severity::Error,
+                    ast[id].span,
+                    "needs at least 1 side".to_string(),
+                ));
+            }
+            check_pool(ast, constant(*count), "dice", modifiers, problems);
+            check_values(modifiers, problems);
+            for modifier in modifiers {
+                check_explode(ast, modifier, sides, problems);
+            }
+        }
+        ExprKind::Fudge { count, modifiers } => {
+            check_size(*count, "dice", problems);
+            check_pool(ast, constant(*count), "dice", modifiers, problems);
+            check_values(modifiers, problems);
+            check_sides(modifiers, "fate dice", problems);
+        }
+        ExprKind::Repetition {
+            count, modifiers, ..
+        } => {
+            check_size(*count, "times", problems);
+            check_pool(ast, constant(*count), "rolls", modifiers, problems);
+            check_values(modifiers, problems);
+            check_sides(modifiers, "repeated rolls", problems);
+        }
+        ExprKind::Group { modifiers, .. } => {
+            check_values(modifiers, problems);
+            check_sides(modifiers, "a group", problems);
+        }
+        ExprKind::Choose(choices) => {
+            let total: i64 = choices.iter().map(|c| c.weight as i64).sum();
+            if total > i32::MAX as i64 {
+                problems.push(Problem::new(
+                    Severity::Error,
+                    ast[id].span,
+                    format!("has weights adding up to more than {}", i32::MAX),
+                ));
+            }
+        }
+        ExprKind::BinaryOp(_, '/', divisor) => match bounds(ast, *divisor) {
+            Some((0, 0)) => problems.push(Problem::new(
+                Severity::Error,
+                ast[id].span,
+                "divides by zero".to_string(),
+            )),
+            Some((low, high)) if low <= 0 && 0 <= high => problems.push(Problem::new(
+                Severity::Warning,
+                ast[*divisor].span,
+                format!("divides by `{}`, which can be 0", ast.display(*divisor)),
+            )),
+            _ => {}
+        },
+        _ => {}
+    }
+}
+
+/// Warns about keeping or dropping more of a pool than it has.
+fn check_pool(
+    ast: &Ast,
+    count: Option<i32>,
+    noun: &str,
+    modifiers: &[DiceModifier],
+    problems: &mut Vec<Problem>,
+) {
+    let Some(count) = count else {
+        return;
+    };
+    for modifier in modifiers {
+        // Explosions add dice, so the pool may well grow large enough.
+        if matches!(
+            modifier.kind,
+            DiceModifierType::Explode | DiceModifierType::Wod
+        ) {
+            return;
+        }
+        let verb = match modifier.kind {
+            DiceModifierType::KeepHigh | DiceModifierType::KeepLow => "keeps",
+            DiceModifierType::DropHigh | DiceModifierType::DropLow => "drops",
+            _ => continue,
+        };
+        let value = modifier.value.and_then(|v| match ast[v].kind {
+            ExprKind::Number(n) => Some(n),
+            _ => None,
+        });
+        if let Some(value) = value.filter(|&v| v > count) {
+            problems.push(Problem::new(
+                Severity::Warning,
+                modifier.span,
+                format!("{} {} of only {} {}", verb, value, count, noun),
+            ));
+        }
+    }
+}
+
+/// Flags modifiers with no default that are missing their number, such as
+/// the `kh` in `4d6kh` or the `t` in `8d10t`, which cannot roll.
+fn check_values(modifiers: &[DiceModifier], problems: &mut Vec<Problem>) {
+    for modifier in modifiers {
+        let needs_value = matches!(
+            modifier.kind,
+            DiceModifierType::KeepHigh
+                | DiceModifierType::KeepLow
+                | DiceModifierType::DropHigh
+                | DiceModifierType::DropLow
+                | DiceModifierType::Target
+        );
+        if needs_value && modifier.value.is_none() {
+            problems.push(Problem::new(
+                Severity::Error,
+                modifier.span,
+                "needs a number, as in `4d6kh3` or `8d10t7`".to_string(),
+            ));
+        }
+    }
+}
+
+/// Flags modifiers that roll dice again, or default to the highest face,
+/// on pools whose dice have no number of sides, such as the `!` in
+/// `{1d6, 1d8}!`.
+fn check_sides(modifiers: &[DiceModifier], noun: &str, problems: &mut Vec<Problem>) {
+    for modifier in modifiers {
+        let needs_sides = match modifier.kind {
+            DiceModifierType::Explode
+            | DiceModifierType::Compound
+            | DiceModifierType::RerollAdd
+            | DiceModifierType::Reroll
+            | DiceModifierType::RerollOnce
+            | DiceModifierType::Wod => true,
+            DiceModifierType::Double => modifier.value.is_none(),
+            _ => false,
+        };
+        if needs_sides {
+            problems.push(Problem::new(
+                Severity::Error,
+                modifier.span,
+                format!("needs dice with a number of sides, not {}", noun),
+            ));
+        }
+    }
+}
+
+/// Flags explosions that trigger on every face and so never stop.
+fn check_explode(
+    ast: &Ast,
+    modifier: &DiceModifier,
+    sides: Option<i32>,
+    problems: &mut Vec<Problem>,
+) {
+    if !matches!(
+        modifier.kind,
+        DiceModifierType::Explode | DiceModifierType::Compound
+    ) {
+        return;
+    }
+    let endless = if !modifier.faces.is_empty() {
+        sides.is_some_and(|sides| (1..=sides).all(|face| modifier.faces.contains(&face)))
+    } else {
+        let threshold = match modifier.value {
+            Some(value) => match ast[value].kind {
+                ExprKind::Number(n) => Some(n),
+                _ => None,
+            },
+            None => sides,
+        };
+        threshold.is_some_and(|t| t <= 1)
+    };
+    if endless {
+        problems.push(Problem::new(
+            Severity::Error,
+            modifier.span,
+            "explodes on every face, so it never stops".to_string(),
+        ));
+    }
+}
+
+/// The lowest and highest total an expression can have, when that is easy
+/// to tell.
+fn bounds(ast: &Ast, id: ExprId) -> Option<(i64, i64)> {
+    let constant = |id: ExprId| match ast[id].kind {
+        ExprKind::Number(n) => Some(n as i64),
+        _ => None,
+    };
+    match &ast[id].kind {
+        ExprKind::Number(n) => Some((*n as i64, *n as i64)),
+        ExprKind::Dice {
+            count,
+            sides,
+            modifiers,
+        } if modifiers.is_empty() => {
+            let (count, sides) = (constant(*count)?, constant(*sides)?);
+            (count >= 0 && sides > 0).then_some((count, count * sides))
+        }
+        ExprKind::Fudge { count, modifiers } if modifiers.is_empty() => {
+            let count = constant(*count)?;
+            Some((-count, count))
+        }
+        ExprKind::Call {
+            function: Function::Between,
+            args,
+        } => {
+            let (a, b) = (constant(args[0])?, constant(args[1])?);
+            Some((a.min(b), a.max(b)))
+        }
+        ExprKind::BinaryOp(lhs, op, rhs) => {
+            let (a, b) = bounds(ast, *lhs)?;
+            let (c, d) = bounds(ast, *rhs)?;
+            match op {
+                '+' => Some((a + c, b + d)),
+                '-' => Some((a - d, b - c)),
+                '*' => {
+                    let products = [a * c, a * d, b * c, b * d];
+                    Some((*products.iter().min()?, *products.iter().max()?))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn messages(input: &str) -> Vec<(Severity, String)> {
+        lint(input)
+            .into_iter()
+            .map(|p| (p.severity, p.message))
+            .collect()
+    }
+
+    #[test]
+    fn reports_parse_errors_with_a_position() {
+        let problems = lint("2d6 + foo");
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].severity, Severity::Error);
+        assert_eq!(problems[0].span.start, 6);
+
+        assert_eq!(
+            messages("1d20 vs 15/10"),
+            [(
+                Severity::Error,
+                "tier thresholds must increase: 15/10".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn checks_die_sizes_and_pool_limits() {
+        assert_eq!(
+            messages("1d0 + 2000000d6 + 5000000(1d6)"),
+            [
+                (Severity::Error, "needs at least 1 side".to_string()),
+                (
+                    Severity::Warning,
+                    "rolls 2000000 dice, more than 1000000".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    "rolls 5000000 times, more than 1000000".to_string()
+                ),
+            ]
+        );
+        assert!(lint("1000000d6").is_empty());
+    }
+
+    #[test]
+    fn warns_about_keeping_more_than_the_pool() {
+        let problems = lint("4d6kh5 + 3(1d6)dl4");
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].message, "keeps 5 of only 4 dice");
+        assert_eq!(problems[0].span, Span::new(3, 6));
+        assert_eq!(problems[1].message, "drops 4 of only 3 rolls");
+        assert!(lint("4d6kh3 + 2d6!kh3").is_empty());
+
+        let problems = lint("4d6k + 2");
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].severity, Severity::Error);
+        assert_eq!(problems[0].span, Span::new(3, 4));
+        assert_eq!(lint("{1d6, 1d8}kh")[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn flags_endless_explosions() {
+        for input in ["2d6!1", "1d1!!", "1d2!{1,2}"] {
+            assert_eq!(
+                messages(input),
+                [(
+                    Severity::Error,
+                    "explodes on every face, so it never stops".to_string()
+                )],
+                "{}",
+                input
+            );
+        }
+        assert!(lint("1d6!{1,6} + 1d6!2").is_empty());
+    }
+
+    #[test]
+    fn flags_rerolls_on_pools_without_sides() {
+        assert_eq!(
+            messages("{1d6, 1d8}!"),
+            [(
+                Severity::Error,
+                "needs dice with a number of sides, not a group".to_string()
+            )]
+        );
+        assert_eq!(
+            parse("{1d6, 1d8}r1").map(|_| ()),
+            Err("`r1` needs dice with a number of sides, not a group".to_string())
+        );
+        for input in ["3(1d6)!", "{1d6, 1d8}ra", "{1d10, 1d10}t7x", "2(1d10)wod"] {
+            assert_eq!(lint(input).len(), 1, "{}", input);
+        }
+        assert!(lint("{1d6, 1d8}kh1 + {1d10, 1d10}t7x9 + {1d6!, 1d8r1}").is_empty());
+    }
+
+    #[test]
+    fn flags_rerolls_on_fate_dice() {
+        for input in ["4dF!", "4dFx"] {
+            assert_eq!(
+                parse(input).map(|_| ()),
+                Err(format!(
+                    "`{}` needs dice with a number of sides, not fate dice",
+                    &input[3..]
+                ))
+            );
+        }
+        assert!(lint("4dFkh2 + 4dFt1x1").is_empty());
+    }
+
+    #[test]
+    fn flags_numbers_that_do_not_fit() {
+        assert_eq!(
+            messages("choose{a:99999999999}"),
+            [(Severity::Error, "number too large: 99999999999".to_string())]
+        );
+        assert_eq!(
+            messages("choose{a:2000000000, b:2000000000}"),
+            [(
+                Severity::Error,
+                "has weights adding up to more than 2147483647".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn errors_refuse_to_parse() {
+        assert_eq!(
+            parse("2 + 1d6!1").map(|_| ()),
+            Err("`!1` explodes on every face, so it never stops".to_string())
+        );
+        assert_eq!(
+            parse("1d20 / (2 - 2)").map(|_| ()),
+            Err("`1d20 / (2 - 2)` divides by zero".to_string())
+        );
+        assert!(parse("1d0").is_err());
+        // Warnings still roll.
+        assert!(parse("4d6kh5 + 10 / 1d3").is_ok());
+    }
+
+    #[test]
+    fn warns_about_division_that_can_hit_zero() {
+        assert_eq!(
+            messages("10 / (1d3 - 2)"),
+            [(
+                Severity::Warning,
+                "divides by `1d3 - 2`, which can be 0".to_string()
+            )]
+        );
+        assert_eq!(messages("1d6 / 0")[0].1, "divides by zero");
+        assert_eq!(messages("10 / 4dF")[0].0, Severity::Warning);
+        assert!(lint("10 / 1d4 + 1d6 / (1d6 - 1d6 * 0 + 1)").is_empty());
+    }
+}
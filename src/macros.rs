@@ -0,0 +1,337 @@
+//! Named rolls and macros with parameters, expanded as text before
+//! parsing. With `fireball = "8d6"` and `gwf(n) = "nd6ra{1,2}"` defined,
+//! `fireball + gwf(2) + 4` reads as `(8d6) + (2d6ra{1,2}) + 4`.
+//!
+//! A name is only replaced where it stands alone, so a macro named `h`
+//! leaves `6d6h` alone, and a name followed by `:` is a label. Inside a
+//! body, a parameter may run straight into the `d` of a dice roll, as in
+//! `nd6`. Arguments other than plain numbers are put in parentheses.
+//!
+//! Local spellings of dice notation, like German `3w6`, are replaced here
+//! too, by [`apply_notation`].
+
+use std::fmt;
+
+/// How deeply macros may expand into other macros, so one that uses itself
+/// fails instead of expanding forever.
+pub const MAX_DEPTH: usize = 16;
+
+/// Names the expression syntax already uses for functions.
+const RESERVED: &[&str] = &["half", "double", "clamp", "between", "choose", "vs"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Macro {
+    pub name: String,
+    /// Empty for a named roll without parameters.
+    pub params: Vec<String>,
+    pub body: String,
+}
+
+impl Macro {
+    /// Reads a definition from its key, `name` or `name(a, b)`, and body.
+    pub fn parse(key: &str, body: &str) -> Result<Macro, String> {
+        let key = key.trim();
+        let (name, params) = match key.strip_suffix(')').and_then(|k| k.split_once('(')) {
+            Some((name, params)) => {
+                let params: Vec<String> = params.split(',').map(|p| p.trim().to_string()).collect();
+                (name.trim(), params)
+            }
+            None => (key, Vec::new()),
+        };
+        let is_identifier = |s: &str| {
+            s.starts_with(|c: char| c.is_ascii_alphabetic())
+                && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        if !is_identifier(name) {
+            return Err(format!("bad macro name `{}`", name));
+        }
+        if RESERVED.contains(&name) {
+            return Err(format!("`{}` is a built-in function", name));
+        }
+        if let Some(param) = params.iter().find(|p| !is_identifier(p)) {
+            return Err(format!("bad parameter `{}` in `{}`", param, key));
+        }
+        Ok(Macro {
+            name: name.to_string(),
+            params,
+            body: body.trim().to_string(),
+        })
+    }
+}
+
+/// Formats a macro the way config files define it: `gwf(n) = "nd6"`.
+impl fmt::Display for Macro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.params.is_empty() {
+            write!(f, "({})", self.params.join(", "))?;
+        }
+        write!(f, " = \"{}\"", self.body)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Macros {
+    macros: Vec<Macro>,
+}
+
+impl Macros {
+    /// Adds a macro, replacing any with the same name.
+    pub fn define(&mut self, new: Macro) {
+        match self.macros.iter_mut().find(|m| m.name == new.name) {
+            Some(existing) => *existing = new,
+            None => self.macros.push(new),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Macro> {
+        self.macros.iter().find(|m| m.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Macro> {
+        self.macros.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.macros.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.macros.is_empty()
+    }
+
+    /// Replaces every use of a macro in `input` with its body.
+    pub fn expand(&self, input: &str) -> Result<String, String> {
+        self.expand_at(input, 0)
+    }
+
+    fn expand_at(&self, input: &str, depth: usize) -> Result<String, String> {
+        let mut out = String::new();
+        let mut i = 0;
+        while i < input.len() {
+            let rest = &input[i..];
+            let c = rest.chars().next().unwrap();
+            let starts_word = c.is_ascii_alphabetic() && !input[..i].ends_with(is_word_char);
+            if !starts_word {
+                out.push(c);
+                i += c.len_utf8();
+                continue;
+            }
+            let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+            let (name, after) = rest.split_at(len);
+            let call = match self.get(name) {
+                Some(m) if m.params.is_empty() && !after.starts_with([':', '(']) => {
+                    Some((m, m.body.clone(), len))
+                }
+                Some(m) if !m.params.is_empty() && after.starts_with('(') => {
+                    let (args, args_len) = split_args(&after[1..])
+                        .ok_or_else(|| format!("unclosed `(` after `{}`", name))?;
+                    if args.len() != m.params.len() {
+                        return Err(format!(
+                            "{} takes {} arguments, got {}",
+                            name,
+                            m.params.len(),
+                            args.len()
+                        ));
+                    }
+                    Some((m, substitute(&m.body, &m.params, &args), len + 1 + args_len))
+                }
+                _ => None,
+            };
+            match call {
+                Some((m, body, used)) => {
+                    if depth == MAX_DEPTH {
+                        return Err(format!(
+                            "macros nest more than {} deep at {}",
+                            MAX_DEPTH, m.name
+                        ));
+                    }
+                    out.push('(');
+                    out.push_str(&self.expand_at(&body, depth + 1)?);
+                    out.push(')');
+                    i += used;
+                }
+                None => {
+                    out.push_str(name);
+                    i += len;
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Replaces local spellings of dice notation with the standard ones, given
+/// as `(local, standard)` pairs: with `("w", "d")`, German `3w6` reads as
+/// `3d6`. A spelling is only replaced where a roll could start, after a
+/// count or anything but a letter, and before the number of sides.
+pub fn apply_notation(input: &str, notation: &[(String, String)]) -> String {
+    let mut by_length: Vec<&(String, String)> = notation.iter().collect();
+    by_length.sort_by_key(|(local, _)| std::cmp::Reverse(local.len()));
+    let mut out = String::new();
+    let mut i = 0;
+    while i < input.len() {
+        let rest = &input[i..];
+        let after_word = input[..i].ends_with(|c: char| c.is_alphabetic() || c == '_');
+        let spelling = by_length.iter().find(|(local, _)| {
+            rest.strip_prefix(local.as_str())
+                .is_some_and(|after| after.starts_with(|c: char| c.is_ascii_digit() || c == '%'))
+        });
+        match spelling {
+            Some((local, standard)) if !after_word => {
+                out.push_str(standard);
+                i += local.len();
+            }
+            _ => {
+                let c = rest.chars().next().unwrap();
+                out.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    out
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Splits the arguments of a call at top-level commas, given the text after
+/// its `(`. Returns them with the length up to and including the `)`.
+fn split_args(text: &str) -> Option<(Vec<&str>, usize)> {
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' if depth == 0 => {
+                args.push(text[start..i].trim());
+                return Some((args, i + 1));
+            }
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Puts the arguments in place of the parameters in a macro's body.
+fn substitute(body: &str, params: &[String], args: &[&str]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < body.len() {
+        let rest = &body[i..];
+        let c = rest.chars().next().unwrap();
+        if c.is_ascii_alphabetic() && !body[..i].ends_with(is_word_char) {
+            // The longest parameter that stands alone or runs into a `d`.
+            let param = params
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| {
+                    rest.strip_prefix(p.as_str()).is_some_and(|after| {
+                        after.starts_with('d') || !after.starts_with(is_word_char)
+                    })
+                })
+                .max_by_key(|(_, p)| p.len());
+            if let Some((n, param)) = param {
+                let arg = args[n];
+                if !arg.is_empty() && arg.bytes().all(|b| b.is_ascii_digit()) {
+                    out.push_str(arg);
+                } else {
+                    out.push_str(&format!("({})", arg));
+                }
+                i += param.len();
+                continue;
+            }
+            let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+            out.push_str(&rest[..len]);
+            i += len;
+            continue;
+        }
+        out.push(c);
+        i += c.len_utf8();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn macros(definitions: &[(&str, &str)]) -> Macros {
+        let mut macros = Macros::default();
+        for (key, body) in definitions {
+            macros.define(Macro::parse(key, body).unwrap());
+        }
+        macros
+    }
+
+    #[test]
+    fn parses_definitions() {
+        let gwf = Macro::parse("gwf( n )", " nd6ra{1,2} ").unwrap();
+        assert_eq!(gwf.params, ["n"]);
+        assert_eq!(gwf.to_string(), "gwf(n) = \"nd6ra{1,2}\"");
+        assert_eq!(Macro::parse("fireball", "8d6").unwrap().params.len(), 0);
+        assert!(Macro::parse("2x", "1d6").is_err());
+        assert!(Macro::parse("half(n)", "n").is_err());
+        assert!(Macro::parse("f(a b)", "a").is_err());
+    }
+
+    #[test]
+    fn expands_names_and_arguments() {
+        let macros = macros(&[
+            ("fireball", "8d6"),
+            ("gwf(n)", "nd6ra{1,2}"),
+            ("dmg(count, bonus)", "countd8 + bonus"),
+            ("adv", "2d20kh1"),
+        ]);
+        assert_eq!(macros.expand("gwf(2)+4").unwrap(), "(2d6ra{1,2})+4");
+        assert_eq!(macros.expand("fireball * 2").unwrap(), "(8d6) * 2");
+        assert_eq!(macros.expand("dmg(1d4, 3)").unwrap(), "((1d4)d8 + 3)");
+        assert_eq!(
+            macros.expand("dmg(gwf(1), clamp(1d4, 2, 3))").unwrap(),
+            "(((1d6ra{1,2}))d8 + (clamp(1d4, 2, 3)))"
+        );
+        // Names inside other words, and labels, are left alone.
+        assert_eq!(
+            macros.expand("adv: adv advx 6d6distinct").unwrap(),
+            "adv: (2d20kh1) advx 6d6distinct"
+        );
+    }
+
+    #[test]
+    fn applies_local_notation() {
+        let notation = [
+            ("w".to_string(), "d".to_string()),
+            ("dé".to_string(), "d".to_string()),
+        ];
+        assert_eq!(apply_notation("3w6 + 1w20", &notation), "3d6 + 1d20");
+        assert_eq!(apply_notation("3dé6 + w%", &notation), "3d6 + d%");
+        // Only where a roll could start.
+        assert_eq!(
+            apply_notation("choose{w6, kw6}", &notation),
+            "choose{d6, kw6}"
+        );
+        assert_eq!(apply_notation("2d6", &notation), "2d6");
+    }
+
+    #[test]
+    fn reports_bad_calls_and_loops() {
+        let macros = macros(&[("gwf(n)", "nd6"), ("loop", "loop + 1")]);
+        assert_eq!(
+            macros.expand("gwf(1, 2)").unwrap_err(),
+            "gwf takes 1 arguments, got 2"
+        );
+        assert!(macros.expand("gwf(1").is_err());
+        assert_eq!(
+            macros.expand("loop").unwrap_err(),
+            "macros nest more than 16 deep at loop"
+        );
+        assert_eq!(macros.expand("gwf + 1").unwrap(), "gwf + 1");
+    }
+}
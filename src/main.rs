@@ -1,52 +1,39 @@
-mod eval;
-mod parser;
+mod cli;
 
 use std::env;
 
-use eval::{EvalResult, eval_expr};
-use parser::parse;
-
 fn main() {
-    let mut verbose = false;
-    let mut show_help = false;
-    let mut expr_parts = Vec::new();
-
-    for arg in env::args().skip(1) {
-        match arg.as_str() {
-            "-v" | "--verbose" => verbose = true,
-            "-h" | "--help" => show_help = true,
-            _ => expr_parts.push(arg.to_lowercase()),
-        }
-    }
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    if show_help || expr_parts.is_empty() {
-        eprintln!(
-            "Usage: roll [options] <expr>\n\n\
-             Options:\n\
-             \t-v, --verbose   Show individual rolls\n\
-             \t-h, --help      Show this help message"
-        );
-        std::process::exit(if show_help { 0 } else { 1 });
-    }
-
-    let input = expr_parts.join(" ");
-    let expressions = match parse(&input) {
-        Ok(exprs) => exprs,
-        Err(err) => {
-            eprintln!("Parse error: {}", err);
-            std::process::exit(1);
+    let status = match args.first().map(String::as_str) {
+        Some("aliases") => cli::aliases::run(&args[1..]),
+        Some("bag") => cli::bag::run(&args[1..]),
+        Some("calibrate") => cli::calibrate::run(&args[1..]),
+        Some("char") => cli::character::run(&args[1..]),
+        Some("dist") => cli::dist::run(&args[1..]),
+        Some("draw") => cli::draw::run(&args[1..]),
+        Some("explain") => cli::explain::run(&args[1..]),
+        Some("fill") => cli::fill::run(&args[1..]),
+        Some("fmt") => cli::fmt::run(&args[1..]),
+        Some("gen") => cli::generate::run(&args[1..]),
+        Some("history") => cli::history::run(&args[1..]),
+        Some("lint") => cli::lint::run(&args[1..]),
+        Some("replay") => cli::replay::run(&args[1..]),
+        Some("screen") => cli::screen::run(&args[1..]),
+        Some("sim") => cli::sim::run(&args[1..]),
+        Some("table") => cli::table::run(&args[1..]),
+        #[cfg(feature = "tui")]
+        Some("tui") => cli::tui::run(&args[1..]),
+        #[cfg(not(feature = "tui"))]
+        Some("tui") => {
+            eprintln!("roll tui needs reroll built with the `tui` feature");
+            1
         }
+        Some("wizard") => cli::wizard::run(&args[1..]),
+        _ => match args.first().and_then(|name| cli::preset::find(name)) {
+            Some(preset) => cli::preset::run(&args[0], preset, &args[1..]),
+            None => cli::roll::run(&args),
+        },
     };
-
-    for expr in expressions.iter() {
-        let eval = eval_expr(expr);
-        if verbose {
-            match eval {
-                EvalResult::Rolls(v) => println!("{:?}", v),
-                EvalResult::Number(n) => println!("{}", n),
-            }
-        } else {
-            println!("{}", eval.to_number());
-        }
-    }
+    std::process::exit(status);
 }
@@ -0,0 +1,79 @@
+use crate::ast::{Ast, ExprId, ExprKind};
+
+/// Replaces arithmetic on constant subtrees with its result, so that e.g.
+/// the threshold in `1000(1d8!(2*3))` is not recomputed on every repetition.
+///
+/// Operations that would fail at evaluation time (division by zero,
+/// overflow) are left in place so they still report as they would have.
+pub fn fold_constants(ast: &mut Ast, id: ExprId) {
+    for child in ast.children(id) {
+        fold_constants(ast, child);
+    }
+
+    if let ExprKind::BinaryOp(lhs, op, rhs) = ast[id].kind
+        && let (ExprKind::Number(a), ExprKind::Number(b)) = (&ast[lhs].kind, &ast[rhs].kind)
+        && let Some(n) = checked_op(*a, op, *b)
+    {
+        ast[id].kind = ExprKind::Number(n);
+    }
+}
+
+/// Folds every root expression in `ast`.
+pub fn fold_all(ast: &mut Ast) {
+    for root in ast.roots().to_vec() {
+        fold_constants(ast, root);
+    }
+}
+
+fn checked_op(a: i32, op: char, b: i32) -> Option<i32> {
+    match op {
+        '+' => a.checked_add(b),
+        '-' => a.checked_sub(b),
+        '*' => a.checked_mul(b),
+        '/' => a.checked_div(b),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, parse_unchecked};
+
+    fn folded(input: &str) -> String {
+        // Unchecked, since `parse` refuses to divide by zero.
+        let mut ast = parse_unchecked(input).unwrap();
+        fold_all(&mut ast);
+        ast.to_string()
+    }
+
+    #[test]
+    fn folds_arithmetic() {
+        assert_eq!(folded("3*2+1"), "7");
+        assert_eq!(folded("3*2+1d6"), "6 + 1d6");
+        assert_eq!(folded("1d6+2*3"), "1d6 + 6");
+    }
+
+    #[test]
+    fn folds_dice_terms_and_modifiers() {
+        assert_eq!(folded("(2+2)d6kh(1+1)"), "4d6kh2");
+        assert_eq!(folded("(1+2)(1d8!(2*3))"), "3(1d8!6)");
+    }
+
+    #[test]
+    fn keeps_failing_operations() {
+        assert_eq!(folded("1/0"), "1 / 0");
+        assert_eq!(folded("2147483647+1"), "2147483647 + 1");
+    }
+
+    #[test]
+    fn keeps_spans() {
+        let input = "1d6 + 2*3";
+        let mut ast = parse(input).unwrap();
+        fold_all(&mut ast);
+        let ExprKind::BinaryOp(_, _, rhs) = ast[ast.roots()[0]].kind else {
+            panic!("Expected binary op");
+        };
+        assert_eq!(&input[ast[rhs].span.start..ast[rhs].span.end], "2*3");
+    }
+}
@@ -1,276 +1,765 @@
 use pest::Parser;
 use pest_derive::Parser;
 
+use crate::ast::{
+    Annotation, Ast, Choice, Compare, Condition, DEFAULT_REROLL_LIMIT, DiceModifier,
+    DiceModifierType, ExprId, ExprKind, Function, Rounding, Span,
+};
+use crate::lint::{Severity, problems};
+
 #[derive(Parser)]
 #[grammar = "dice.pest"]
 pub struct DiceParser;
 
-#[derive(Debug, PartialEq)]
-pub enum Expr {
-    Number(i32),
-    Dice {
-        count: Box<Expr>,
-        sides: Box<Expr>,
-        modifiers: Vec<DiceModifier>,
-    },
-    BinaryOp(Box<Expr>, char, Box<Expr>),
-    Repetition {
-        count: Box<Expr>,
-        expr: Box<Expr>,
-        modifiers: Vec<DiceModifier>,
-    },
+pub fn parse_expressions(pair: pest::iterators::Pair<Rule>) -> Ast {
+    assert_eq!(pair.as_rule(), Rule::dice_expr);
+    let mut ast = Ast::new();
+    for expr in pair.into_inner() {
+        let root = parse_root(expr, &mut ast);
+        ast.push_root(root);
+    }
+    ast
 }
 
-#[derive(Debug, PartialEq)]
-pub enum DiceModifierType {
-    KeepHigh,
-    KeepLow,
-    DropHigh,
-    DropLow,
-    Explode,
+/// Parses a root along with its label and annotations, if any.
+fn parse_root(pair: pest::iterators::Pair<Rule>, ast: &mut Ast) -> ExprId {
+    match pair.as_rule() {
+        Rule::labeled => {
+            let mut inner = pair.into_inner();
+            let label = inner.next().unwrap().as_str().trim_end_matches(':');
+            let root = parse_root(inner.next().unwrap(), ast);
+            ast.set_label(root, label.to_string());
+            root
+        }
+        Rule::annotated => {
+            let mut inner = pair.into_inner();
+            let root = parse_expr(inner.next().unwrap(), ast);
+            let annotations = inner
+                .next()
+                .unwrap()
+                .into_inner()
+                .filter(|p| p.as_rule() == Rule::annotation)
+                .map(parse_annotation)
+                .collect();
+            ast.set_annotations(root, annotations);
+            root
+        }
+        _ => parse_expr(pair, ast),
+    }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct DiceModifier {
-    pub kind: DiceModifierType,
-    pub value: Option<Box<Expr>>,
+fn parse_annotation(pair: pest::iterators::Pair<Rule>) -> Annotation {
+    let mut inner = pair.into_inner();
+    let stat = inner.next().unwrap();
+    if stat.as_rule() == Rule::count {
+        let compare = parse_comparison(inner.next().unwrap().as_str());
+        let value = inner.next().unwrap().as_str().parse().unwrap();
+        return Annotation::Count(compare, value);
+    }
+    parse_stat(stat)
 }
 
-pub fn parse_expressions(pair: pest::iterators::Pair<Rule>) -> Vec<Expr> {
-    assert_eq!(pair.as_rule(), Rule::dice_expr);
-    pair.into_inner().map(|e| parse_expr(e)).collect()
+/// Parses a value read off a pool: `sum`, `max`, `min`, `distinct` or
+/// `straight`.
+fn parse_stat(stat: pest::iterators::Pair<Rule>) -> Annotation {
+    match stat.as_rule() {
+        Rule::sum => Annotation::Sum,
+        Rule::pool_stat if stat.as_str() == "straight" => Annotation::Straight,
+        Rule::pool_stat => Annotation::Distinct,
+        Rule::max => Annotation::Max,
+        Rule::min => Annotation::Min,
+        _ => unreachable!("from annotation, {:?}", stat.as_rule()),
+    }
+}
+
+fn parse_comparison(op: &str) -> Compare {
+    match op {
+        "<" => Compare::Less,
+        "<=" => Compare::LessOrEqual,
+        "=" => Compare::Equal,
+        ">=" => Compare::GreaterOrEqual,
+        ">" => Compare::Greater,
+        _ => unreachable!("unknown comparison {}", op),
+    }
 }
 
-pub fn parse_dice_modifier(pair: pest::iterators::Pair<Rule>) -> DiceModifier {
+pub fn parse_dice_modifier(pair: pest::iterators::Pair<Rule>, ast: &mut Ast) -> DiceModifier {
+    let span = pair.as_span().into();
     let mut mod_inner = pair.into_inner();
     let kind_pair = mod_inner.next().unwrap();
     let kind = match kind_pair.as_rule() {
+        Rule::compound => DiceModifierType::Compound,
+        Rule::reroll_add => DiceModifierType::RerollAdd,
+        Rule::reroll_die => DiceModifierType::Reroll,
+        Rule::reroll_once => DiceModifierType::RerollOnce,
         Rule::explode => DiceModifierType::Explode,
         Rule::keep_high => DiceModifierType::KeepHigh,
         Rule::keep_low => DiceModifierType::KeepLow,
         Rule::drop_high => DiceModifierType::DropHigh,
         Rule::drop_low => DiceModifierType::DropLow,
+        Rule::target => DiceModifierType::Target,
+        Rule::wod => DiceModifierType::Wod,
+        Rule::double => DiceModifierType::Double,
         _ => panic!("unknown modifier type!"),
     };
 
-    let value = if let Some(v) = mod_inner.next() {
-        Some(Box::new(parse_expr(v)))
-    } else {
-        None
-    };
-    DiceModifier { kind, value }
+    let mut value = None;
+    let mut faces = Vec::new();
+    if let Some(next) = mod_inner.next() {
+        if next.as_rule() == Rule::face_set {
+            faces = next
+                .into_inner()
+                .filter(|p| p.as_rule() == Rule::number)
+                .map(|p| p.as_str().parse::<i32>().unwrap())
+                .collect();
+        } else {
+            value = Some(parse_expr(next, ast));
+        }
+    }
+    DiceModifier {
+        kind,
+        value,
+        faces,
+        span,
+    }
 }
 
-pub fn parse_expr(pair: pest::iterators::Pair<Rule>) -> Expr {
+pub fn parse_expr(pair: pest::iterators::Pair<Rule>, ast: &mut Ast) -> ExprId {
+    let span: Span = pair.as_span().into();
     match pair.as_rule() {
-        Rule::number => Expr::Number(pair.as_str().parse::<i32>().unwrap()),
+        Rule::number => ast.push(
+            ExprKind::Number(pair.as_str().parse::<i32>().unwrap()),
+            span,
+        ),
 
         Rule::dice => {
-            let children: Vec<_> = pair.into_inner().collect();
-
-            let mut count = Box::new(Expr::Number(1));
-            let mut sides = Box::new(Expr::Number(1));
+            let mut count = None;
+            let mut sides = None;
             let mut modifiers = Vec::new();
-            let mut set_sides = false;
+            let mut after_roll = false;
+            let mut fudge = false;
 
-            for child in children.into_iter() {
+            for child in pair.into_inner() {
                 match child.as_rule() {
-                    Rule::dice_modifier => {
-                        modifiers.push(parse_dice_modifier(child.clone()));
-                    }
-                    Rule::number | Rule::expr => {
-                        if set_sides {
-                            count = sides;
-                            sides = Box::new(parse_expr(child.clone()));
-                        } else {
-                            sides = Box::new(parse_expr(child.clone()));
-                            set_sides = true;
-                        }
+                    Rule::roll => after_roll = true,
+                    Rule::fudge => fudge = true,
+                    Rule::dice_modifier => modifiers.push(parse_dice_modifier(child, ast)),
+                    Rule::number | Rule::parens if after_roll => {
+                        sides = Some(parse_expr(child, ast))
                     }
+                    Rule::number | Rule::parens => count = Some(parse_expr(child, ast)),
                     _ => unreachable!("from dice, {:?}", child.as_rule()),
                 }
             }
 
-            Expr::Dice {
-                count,
-                sides,
-                modifiers,
+            let implicit = Span::new(span.start, span.start);
+            let count = count.unwrap_or_else(|| ast.push(ExprKind::Number(1), implicit));
+            if fudge {
+                return ast.push(ExprKind::Fudge { count, modifiers }, span);
             }
+            let sides = sides.expect("dice without sides");
+
+            ast.push(
+                ExprKind::Dice {
+                    count,
+                    sides,
+                    modifiers,
+                },
+                span,
+            )
         }
 
-        Rule::repetition => {
+        Rule::roll_keep => {
+            // Sugar for `Xd10!!khZ`.
             let mut children = pair.into_inner();
+            let count = parse_expr(children.next().unwrap(), ast);
+            let keep = parse_expr(children.nth(1).unwrap(), ast);
+            let sides = ast.push(ExprKind::Number(10), Span::new(span.end, span.end));
+            let modifiers = vec![
+                DiceModifier::new(DiceModifierType::Compound, None),
+                DiceModifier::new(DiceModifierType::KeepHigh, Some(keep)),
+            ];
+
+            ast.push(
+                ExprKind::Dice {
+                    count,
+                    sides,
+                    modifiers,
+                },
+                span,
+            )
+        }
 
-            let count: Box<Expr> = Box::new(parse_expr(children.next().unwrap().clone()));
-            let expr: Box<Expr> = Box::new(parse_expr(children.next().unwrap().clone()));
+        Rule::repetition => {
+            let mut children = pair.into_inner();
 
+            let count = parse_expr(children.next().unwrap(), ast);
+            let expr = parse_expr(children.next().unwrap(), ast);
+            let modifiers = children.map(|m| parse_dice_modifier(m, ast)).collect();
+
+            ast.push(
+                ExprKind::Repetition {
+                    count,
+                    expr,
+                    modifiers,
+                },
+                span,
+            )
+        }
+        Rule::group => {
+            let mut members = Vec::new();
             let mut modifiers = Vec::new();
-
-            while let Some(child) = children.next() {
-                modifiers.push(parse_dice_modifier(child.clone()));
-            }
-
-            Expr::Repetition {
-                count,
-                expr,
-                modifiers,
+            for child in pair.into_inner() {
+                match child.as_rule() {
+                    Rule::comma => {}
+                    Rule::dice_modifier => modifiers.push(parse_dice_modifier(child, ast)),
+                    _ => members.push(parse_expr(child, ast)),
+                }
             }
+            ast.push(ExprKind::Group { members, modifiers }, span)
         }
         Rule::add_sub | Rule::mul_div => {
-            let mut inner = pair.into_inner();
-            let mut left = parse_expr(inner.next().unwrap());
+            let source = pair.as_str();
+            let mut inner = pair.into_inner().peekable();
+            let mut left = parse_expr(inner.next().unwrap(), ast);
 
             while let Some(op) = inner.next() {
                 let op_char = op.as_str().chars().next().unwrap();
-                let right = parse_expr(inner.next().unwrap());
-                left = Expr::BinaryOp(Box::new(left), op_char, Box::new(right));
+                let right = parse_expr(inner.next().unwrap(), ast);
+                // Each partial result of a left-associative chain spans up
+                // to the next operator, excluding the whitespace before it.
+                let end = match inner.peek() {
+                    Some(next) => {
+                        let before = &source[..next.as_span().start() - span.start];
+                        span.start + before.trim_end().len()
+                    }
+                    None => span.end,
+                };
+                left = ast.push(
+                    ExprKind::BinaryOp(left, op_char, right),
+                    Span::new(span.start, end),
+                );
             }
             left
         }
+        Rule::contest => {
+            let mut children = pair.into_inner();
+            let first = parse_expr(children.next().unwrap(), ast);
+            let second = children.nth(1).unwrap();
+            if second.as_rule() == Rule::tiers {
+                let thresholds = second
+                    .into_inner()
+                    .filter(|p| p.as_rule() == Rule::number)
+                    .map(|p| p.as_str().parse::<i32>().unwrap())
+                    .collect();
+                return ast.push(
+                    ExprKind::Tiers {
+                        expr: first,
+                        thresholds,
+                    },
+                    span,
+                );
+            }
+            let second = parse_expr(second, ast);
+            ast.push(ExprKind::Contest(first, second), span)
+        }
+        Rule::read => {
+            let mut children = pair.into_inner();
+            let pool = parse_expr(children.next().unwrap(), ast);
+            let reading = children.next().unwrap();
+            let reading = match reading.as_str() {
+                _ if reading.as_rule() == Rule::comparison => {
+                    let compare = parse_comparison(reading.as_str());
+                    let value = children.next().unwrap().as_str().parse().unwrap();
+                    Annotation::Count(compare, value)
+                }
+                "straight" => Annotation::Straight,
+                "distinct" => Annotation::Distinct,
+                "h" => Annotation::Max,
+                "l" => Annotation::Min,
+                other => unreachable!("unknown reading {}", other),
+            };
+            ast.push(ExprKind::Read { pool, reading }, span)
+        }
+        Rule::choose => {
+            let choices = pair
+                .into_inner()
+                .filter(|p| p.as_rule() == Rule::choice)
+                .map(|choice| {
+                    let mut inner = choice.into_inner();
+                    let text = inner.next().unwrap().as_str().to_string();
+                    let weight = match inner.nth(1) {
+                        Some(weight) => weight.as_str().parse().unwrap(),
+                        None => 1,
+                    };
+                    Choice { text, weight }
+                })
+                .collect();
+            ast.push(ExprKind::Choose(choices), span)
+        }
+        Rule::range_die => {
+            let args = pair
+                .into_inner()
+                .filter(|p| p.as_rule() == Rule::number)
+                .map(|p| parse_expr(p, ast))
+                .collect();
+            let function = Function::Between;
+            ast.push(ExprKind::Call { function, args }, span)
+        }
+        Rule::reroll => {
+            let mut children = pair.into_inner();
+            let pool = parse_expr(children.next().unwrap(), ast);
+            let mut condition = children.nth(1).unwrap().into_inner();
+            let condition = Condition {
+                stat: parse_stat(condition.next().unwrap()),
+                compare: parse_comparison(condition.next().unwrap().as_str()),
+                value: condition.next().unwrap().as_str().parse().unwrap(),
+            };
+            let limit = match children.nth(1) {
+                Some(limit) => limit.as_str().parse().unwrap_or(u32::MAX),
+                None => DEFAULT_REROLL_LIMIT,
+            };
+            let kind = ExprKind::Reroll {
+                pool,
+                condition,
+                limit,
+            };
+            ast.push(kind, span)
+        }
+        Rule::call => {
+            let mut function = None;
+            let mut rounding = Rounding::Down;
+            let mut args = Vec::new();
+            for child in pair.into_inner() {
+                match child.as_rule() {
+                    Rule::fn_half => function = Some(Function::Half(Rounding::Down)),
+                    Rule::fn_double => function = Some(Function::Double),
+                    Rule::fn_clamp => function = Some(Function::Clamp),
+                    Rule::fn_between => function = Some(Function::Between),
+                    Rule::rounding if child.as_str() == "up" => rounding = Rounding::Up,
+                    Rule::rounding | Rule::comma => {}
+                    _ => args.push(parse_expr(child, ast)),
+                }
+            }
+            let function = match function.expect("call without a function") {
+                Function::Half(_) => Function::Half(rounding),
+                function => function,
+            };
+            ast.push(ExprKind::Call { function, args }, span)
+        }
+        Rule::parens => {
+            let inner = parse_expr(pair.into_inner().next().unwrap(), ast);
+            ast[inner].span = span;
+            inner
+        }
         _ => unreachable!("from expr, {:?}", pair.as_rule()),
     }
 }
 
-pub fn parse(input: &str) -> Result<Vec<Expr>, String> {
-    let pairs =
-        DiceParser::parse(Rule::dice_expr, input).map_err(|e| format!("Parse error: {}", e))?;
+/// Parses `input` into an expression tree, refusing expressions that parse
+/// but could not finish rolling, such as `4d6kh` or `1d6!1`: every problem
+/// [`lint`](crate::lint::lint) reports as an error.
+pub fn parse(input: &str) -> Result<Ast, String> {
+    let ast = parse_unchecked(input)?;
+    match problems(&ast)
+        .into_iter()
+        .find(|p| p.severity == Severity::Error)
+    {
+        Some(problem) => Err(format!(
+            "`{}` {}",
+            &input[problem.span.start..problem.span.end],
+            problem.message
+        )),
+        None => Ok(ast),
+    }
+}
 
-    let pair = pairs.into_iter().next().ok_or("No expressions found")?;
+/// Parses `input` without checking that it can roll.
+pub(crate) fn parse_unchecked(input: &str) -> Result<Ast, String> {
+    let mut pairs =
+        DiceParser::parse(Rule::input, input).map_err(|e| format!("Parse error: {}", e))?;
+
+    let pair = pairs
+        .next()
+        .and_then(|p| p.into_inner().next())
+        .ok_or("No expressions found")?;
+
+    // Every number is read as an `i32` while building the tree.
+    for token in pair.clone().into_inner().flatten() {
+        if matches!(token.as_rule(), Rule::number | Rule::weight)
+            && token.as_str().parse::<i32>().is_err()
+        {
+            return Err(format!("number too large: {}", token.as_str()));
+        }
+    }
 
-    Ok(parse_expressions(pair))
+    let ast = parse_expressions(pair);
+    for &root in ast.roots() {
+        if let ExprKind::Tiers { thresholds, .. } = &ast[root].kind
+            && thresholds.windows(2).any(|w| w[0] >= w[1])
+        {
+            return Err(format!(
+                "tier thresholds must increase: {}",
+                crate::ast::format_thresholds(thresholds)
+            ));
+        }
+    }
+    Ok(ast)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn parse_and_compare(input: &str, expected: Expr) {
+    fn parse_and_compare(input: &str, expected: impl FnOnce(&mut Ast) -> ExprId) {
         let pairs = DiceParser::parse(Rule::dice_expr, input)
             .expect("Failed to parse")
             .next()
             .unwrap();
 
-        let exprs = parse_expressions(pairs);
+        let parsed = parse_expressions(pairs);
+
+        let mut ast = Ast::new();
+        let root = expected(&mut ast);
+        assert!(
+            parsed.same_structure(parsed.roots()[0], &ast, root),
+            "{} parsed as {}",
+            input,
+            parsed.display(parsed.roots()[0])
+        );
+    }
+
+    fn dice(ast: &mut Ast, count: i32, sides: i32, modifiers: Vec<DiceModifier>) -> ExprId {
+        let (count, sides) = (ast.number(count), ast.number(sides));
+        ast.dice(count, sides, modifiers)
+    }
 
-        assert_eq!(exprs[0], expected);
+    fn modifier(ast: &mut Ast, kind: DiceModifierType, value: Option<i32>) -> DiceModifier {
+        DiceModifier::new(kind, value.map(|v| ast.number(v)))
     }
 
     #[test]
     fn test_simple_number() {
-        parse_and_compare("4", Expr::Number(4));
+        parse_and_compare("4", |ast| ast.number(4));
     }
 
     #[test]
     fn test_simple_dice() {
-        parse_and_compare(
-            "3d6",
-            Expr::Dice {
-                count: Box::new(Expr::Number(3)),
-                sides: Box::new(Expr::Number(6)),
-                modifiers: vec![],
-            },
-        );
+        parse_and_compare("3d6", |ast| dice(ast, 3, 6, vec![]));
     }
 
     #[test]
     fn test_dice_with_keep_high() {
-        parse_and_compare(
-            "4d6kh3",
-            Expr::Dice {
-                count: Box::new(Expr::Number(4)),
-                sides: Box::new(Expr::Number(6)),
-                modifiers: vec![DiceModifier {
-                    kind: DiceModifierType::KeepHigh,
-                    value: Some(Box::new(Expr::Number(3))),
-                }],
-            },
-        );
+        parse_and_compare("4d6kh3", |ast| {
+            let keep = modifier(ast, DiceModifierType::KeepHigh, Some(3));
+            dice(ast, 4, 6, vec![keep])
+        });
+    }
+
+    #[test]
+    fn test_dice_in_modifier_values() {
+        parse_and_compare("10d6kh(1d4+1)", |ast| {
+            let d4 = dice(ast, 1, 4, vec![]);
+            let one = ast.number(1);
+            let count = ast.binary(d4, '+', one);
+            let keep = DiceModifier::new(DiceModifierType::KeepHigh, Some(count));
+            dice(ast, 10, 6, vec![keep])
+        });
+        // Without parentheses, a `d` after the value is a drop modifier.
+        parse_and_compare("4d6kh3d1", |ast| {
+            let keep = modifier(ast, DiceModifierType::KeepHigh, Some(3));
+            let drop = modifier(ast, DiceModifierType::DropHigh, Some(1));
+            dice(ast, 4, 6, vec![keep, drop])
+        });
+    }
+
+    #[test]
+    fn test_dice_with_keep_low() {
+        parse_and_compare("4d6kl1", |ast| {
+            let keep = modifier(ast, DiceModifierType::KeepLow, Some(1));
+            dice(ast, 4, 6, vec![keep])
+        });
     }
 
     #[test]
     fn test_dice_with_explode() {
-        parse_and_compare(
-            "3d3!",
-            Expr::Dice {
-                count: Box::new(Expr::Number(3)),
-                sides: Box::new(Expr::Number(3)),
-                modifiers: vec![DiceModifier {
-                    kind: DiceModifierType::Explode,
-                    value: None,
-                }],
-            },
+        parse_and_compare("3d3!", |ast| {
+            let explode = modifier(ast, DiceModifierType::Explode, None);
+            dice(ast, 3, 3, vec![explode])
+        });
+        parse_and_compare("4d6!{1, 6}", |ast| {
+            let mut explode = modifier(ast, DiceModifierType::Explode, None);
+            explode.faces = vec![1, 6];
+            dice(ast, 4, 6, vec![explode])
+        });
+        assert!(parse("4d6kh{1}").is_err());
+    }
+
+    #[test]
+    fn test_fudge_dice() {
+        parse_and_compare("4dF", |ast| {
+            let count = ast.number(4);
+            ast.fudge(count, vec![])
+        });
+    }
+
+    #[test]
+    fn test_roll_and_keep() {
+        parse_and_compare("5k3", |ast| {
+            let compound = modifier(ast, DiceModifierType::Compound, None);
+            let keep = modifier(ast, DiceModifierType::KeepHigh, Some(3));
+            dice(ast, 5, 10, vec![compound, keep])
+        });
+        assert_eq!(parse("5k3 + 2").unwrap().to_string(), "5d10!!kh3 + 2");
+    }
+
+    #[test]
+    fn test_contest() {
+        parse_and_compare("1d20+5 vs 1d20", |ast| {
+            let d20 = dice(ast, 1, 20, vec![]);
+            let five = ast.number(5);
+            let first = ast.binary(d20, '+', five);
+            let second = dice(ast, 1, 20, vec![]);
+            ast.contest(first, second)
+        });
+        let ast = parse("2d6 vs 2d6 3d6").unwrap();
+        assert_eq!(ast.roots().len(), 2);
+        assert!(parse("(1d20 vs 1d20) + 1").is_err());
+    }
+
+    #[test]
+    fn test_tiers() {
+        parse_and_compare("1d20+7 vs 10/15/20", |ast| {
+            let d20 = dice(ast, 1, 20, vec![]);
+            let seven = ast.number(7);
+            let total = ast.binary(d20, '+', seven);
+            ast.tiers(total, vec![10, 15, 20])
+        });
+        assert_eq!(parse("1d20 vs 10/15 2d6").unwrap().roots().len(), 2);
+        // Followed by an operator, it is a division instead.
+        let ast = parse("1d20 vs 10/15 + 2").unwrap();
+        assert!(matches!(ast[ast.roots()[0]].kind, ExprKind::Contest(..)));
+        assert_eq!(
+            parse("1d20 vs 15/10").unwrap_err(),
+            "tier thresholds must increase: 15/10"
         );
     }
 
     #[test]
-    fn test_binary_operation() {
-        parse_and_compare(
-            "2d6 + 3",
-            Expr::BinaryOp(
-                Box::new(Expr::Dice {
-                    count: Box::new(Expr::Number(2)),
-                    sides: Box::new(Expr::Number(6)),
-                    modifiers: vec![],
-                }),
-                '+',
-                Box::new(Expr::Number(3)),
-            ),
+    fn test_readings() {
+        parse_and_compare("4d6kh3distinct", |ast| {
+            let keep = modifier(ast, DiceModifierType::KeepHigh, Some(3));
+            let pool = dice(ast, 4, 6, vec![keep]);
+            ast.read(pool, Annotation::Distinct)
+        });
+        parse_and_compare("3(1d6)straight + 1", |ast| {
+            let count = ast.number(3);
+            let d6 = dice(ast, 1, 6, vec![]);
+            let pool = ast.repetition(count, d6, vec![]);
+            let straight = ast.read(pool, Annotation::Straight);
+            let one = ast.number(1);
+            ast.binary(straight, '+', one)
+        });
+        assert!(parse("(2d6)straight").is_err());
+        parse_and_compare("6d6h", |ast| {
+            let pool = dice(ast, 6, 6, vec![]);
+            ast.read(pool, Annotation::Max)
+        });
+        parse_and_compare("2d20l + 3", |ast| {
+            let pool = dice(ast, 2, 20, vec![]);
+            let lowest = ast.read(pool, Annotation::Min);
+            let three = ast.number(3);
+            ast.binary(lowest, '+', three)
+        });
+        assert!(parse("6d6 [h]").is_err());
+        parse_and_compare("10(1d20 + 5) >= 15", |ast| {
+            let d20 = dice(ast, 1, 20, vec![]);
+            let five = ast.number(5);
+            let attack = ast.binary(d20, '+', five);
+            let ten = ast.number(10);
+            let attacks = ast.repetition(ten, attack, vec![]);
+            ast.read(attacks, Annotation::Count(Compare::GreaterOrEqual, 15))
+        });
+        assert!(parse("1d20 + 5 >= 15").is_err());
+    }
+
+    #[test]
+    fn test_group() {
+        parse_and_compare("{1d12, 2d6}kh1", |ast| {
+            let d12 = dice(ast, 1, 12, vec![]);
+            let d6 = dice(ast, 2, 6, vec![]);
+            let one = ast.number(1);
+            let keep = DiceModifier::new(DiceModifierType::KeepHigh, Some(one));
+            ast.group(vec![d12, d6], vec![keep])
+        });
+        assert!(parse("{}kh1").is_err());
+    }
+
+    #[test]
+    fn test_choose() {
+        parse_and_compare("choose{sword:3, bow}", |ast| {
+            ast.choose(vec![
+                Choice {
+                    text: "sword".to_string(),
+                    weight: 3,
+                },
+                Choice {
+                    text: "bow".to_string(),
+                    weight: 1,
+                },
+            ])
+        });
+        assert!(parse("choose{sword:0, bow}").is_err());
+        assert!(parse("choose{}").is_err());
+    }
+
+    #[test]
+    fn test_ranges() {
+        parse_and_compare("d[ 37 .. 213 ]", |ast| {
+            let (low, high) = (ast.number(37), ast.number(213));
+            ast.call(Function::Between, vec![low, high])
+        });
+        assert!(parse("2d[1..4]").is_err());
+        assert!(parse("between(1)").is_err());
+    }
+
+    #[test]
+    fn test_modifiers_missing_their_value() {
+        assert_eq!(
+            parse("8d10t").map(|_| ()),
+            Err("`t` needs a number, as in `4d6kh3` or `8d10t7`".to_string())
         );
+        assert!(parse("3(1d6)t").is_err());
+        assert!(parse("{1d6, 1d8}t").is_err());
+        assert!(parse("4dFdl").is_err());
+        // Explodes, `ra` and `wod` have defaults.
+        assert!(parse("8d10t7 + 1d6! + 1d6ra + 8d10wod").is_ok());
     }
 
     #[test]
-    fn test_nested_binary_ops() {
-        parse_and_compare(
-            "2d6 + 3 * 2",
-            Expr::BinaryOp(
-                Box::new(Expr::Dice {
-                    count: Box::new(Expr::Number(2)),
-                    sides: Box::new(Expr::Number(6)),
-                    modifiers: vec![],
-                }),
-                '+',
-                Box::new(Expr::BinaryOp(
-                    Box::new(Expr::Number(3)),
-                    '*',
-                    Box::new(Expr::Number(2)),
-                )),
-            ),
+    fn test_numbers_too_large() {
+        for (input, number) in [
+            ("1d99999999999", "99999999999"),
+            ("choose{a:99999999999}", "99999999999"),
+            ("1d20 vs 10/99999999999", "99999999999"),
+            ("4d6rr(sum<3000000000)", "3000000000"),
+            ("10(1d20) >= 2147483648", "2147483648"),
+        ] {
+            assert_eq!(
+                parse(input).map(|_| ()),
+                Err(format!("number too large: {}", number))
+            );
+        }
+        assert!(parse("1d2147483647").is_ok());
+    }
+
+    #[test]
+    fn test_pool_reroll() {
+        parse_and_compare("4d6rr(max<3, 1)", |ast| {
+            let pool = dice(ast, 4, 6, vec![]);
+            let condition = Condition {
+                stat: Annotation::Max,
+                compare: Compare::Less,
+                value: 3,
+            };
+            ast.reroll(pool, condition, 1)
+        });
+        assert!(parse("4d6rr(count>=5<2)").is_err());
+        assert!(parse("(4d6)rr(sum<8)").is_err());
+        assert!(parse("{1d6,}").is_err());
+    }
+
+    #[test]
+    fn test_functions() {
+        parse_and_compare("half(8d6, up) + 1", |ast| {
+            let damage = dice(ast, 8, 6, vec![]);
+            let half = ast.call(Function::Half(Rounding::Up), vec![damage]);
+            let one = ast.number(1);
+            ast.binary(half, '+', one)
+        });
+        assert!(parse("double(2d6, up)").is_err());
+        assert!(parse("half()").is_err());
+
+        let ast = parse("clamp(1d20 - 5, 1, 20)").unwrap();
+        let ExprKind::Call { function, args } = &ast[ast.roots()[0]].kind else {
+            panic!("expected a call");
+        };
+        assert_eq!(*function, Function::Clamp);
+        assert_eq!(args.len(), 3);
+        assert!(parse("clamp(1d20, 1)").is_err());
+    }
+
+    #[test]
+    fn test_annotations() {
+        let ast = parse("hits: 8d6 [sum, count>=5, max] 2d6 [min]").unwrap();
+        let [first, second] = ast.roots() else {
+            panic!("expected two roots");
+        };
+        assert_eq!(
+            ast.annotations(*first),
+            [
+                Annotation::Sum,
+                Annotation::Count(Compare::GreaterOrEqual, 5),
+                Annotation::Max
+            ]
         );
+        assert_eq!(ast.label(*first), Some("hits"));
+        assert_eq!(ast.annotations(*second), [Annotation::Min]);
+        assert_eq!(ast.to_string(), "hits: 8d6 [sum, count>=5, max] 2d6 [min]");
+        // A bracketed expression is still a separate root.
+        assert_eq!(parse("8d6 [2d6]").unwrap().roots().len(), 2);
+        assert!(parse("8d6 [count]").is_err());
     }
 
     #[test]
-    fn test_repetition_syntax() {
-        parse_and_compare(
-            "3(1d6)",
-            Expr::Repetition {
-                count: Box::new(Expr::Number(3)),
-                expr: Box::new(Expr::Dice {
-                    count: Box::new(Expr::Number(1)),
-                    sides: Box::new(Expr::Number(6)),
-                    modifiers: vec![],
-                }),
-                modifiers: vec![],
-            },
+    fn test_labels() {
+        let ast = parse("attack: 1d20+5 dmg:2d6 1d4 d20:1d20 vs 1d20").unwrap();
+        let labels: Vec<_> = ast.roots().iter().map(|&r| ast.label(r)).collect();
+        assert_eq!(labels, [Some("attack"), Some("dmg"), None, Some("d20")]);
+        assert_eq!(
+            ast.to_string(),
+            "attack: 1d20 + 5 dmg: 2d6 1d4 d20: 1d20 vs 1d20"
         );
+        assert!(parse("attack:").is_err());
+        assert!(parse("(attack: 1d20)").is_err());
+    }
+
+    #[test]
+    fn test_binary_operation() {
+        parse_and_compare("2d6 + 3", |ast| {
+            let lhs = dice(ast, 2, 6, vec![]);
+            let rhs = ast.number(3);
+            ast.binary(lhs, '+', rhs)
+        });
+    }
+
+    #[test]
+    fn test_nested_binary_ops() {
+        parse_and_compare("2d6 + 3 * 2", |ast| {
+            let lhs = dice(ast, 2, 6, vec![]);
+            let (three, two) = (ast.number(3), ast.number(2));
+            let rhs = ast.binary(three, '*', two);
+            ast.binary(lhs, '+', rhs)
+        });
+    }
+
+    #[test]
+    fn test_repetition_syntax() {
+        parse_and_compare("3(1d6)", |ast| {
+            let count = ast.number(3);
+            let expr = dice(ast, 1, 6, vec![]);
+            ast.repetition(count, expr, vec![])
+        });
     }
 
     #[test]
     fn test_repetition_with_modifiers() {
-        parse_and_compare(
-            "2(4d6)kh3",
-            Expr::Repetition {
-                count: Box::new(Expr::Number(2)),
-                expr: Box::new(Expr::Dice {
-                    count: Box::new(Expr::Number(4)),
-                    sides: Box::new(Expr::Number(6)),
-                    modifiers: vec![],
-                }),
-                modifiers: vec![DiceModifier {
-                    kind: DiceModifierType::KeepHigh,
-                    value: Some(Box::new(Expr::Number(3))),
-                }],
-            },
-        );
+        parse_and_compare("2(4d6)kh3", |ast| {
+            let count = ast.number(2);
+            let expr = dice(ast, 4, 6, vec![]);
+            let keep = modifier(ast, DiceModifierType::KeepHigh, Some(3));
+            ast.repetition(count, expr, vec![keep])
+        });
     }
 
     #[test]
@@ -282,10 +771,113 @@ mod tests {
             .next()
             .unwrap();
 
-        let exprs = parse_expressions(pairs);
-        assert_eq!(exprs.len(), 2);
+        let ast = parse_expressions(pairs);
+        assert_eq!(ast.roots().len(), 2);
+
+        assert!(matches!(ast[ast.roots()[0]].kind, ExprKind::Dice { .. }));
+        assert!(matches!(
+            ast[ast.roots()[1]].kind,
+            ExprKind::BinaryOp(_, '+', _)
+        ));
+    }
+
+    #[test]
+    fn test_spans() {
+        let input = "d8 (2d6+1)*2 4d6kh3";
+        let ast = parse(input).unwrap();
+        let text = |span: Span| &input[span.start..span.end];
+        let roots = ast.roots();
+
+        let ExprKind::Dice { count, sides, .. } = &ast[roots[0]].kind else {
+            panic!("Expected dice");
+        };
+        assert_eq!(text(ast[roots[0]].span), "d8");
+        assert_eq!(ast[*count].span, Span::new(0, 0));
+        assert_eq!(text(ast[*sides].span), "8");
+
+        let ExprKind::BinaryOp(lhs, _, rhs) = &ast[roots[1]].kind else {
+            panic!("Expected binary op");
+        };
+        assert_eq!(text(ast[roots[1]].span), "(2d6+1)*2");
+        assert_eq!(text(ast[*lhs].span), "(2d6+1)");
+        assert_eq!(text(ast[*rhs].span), "2");
+
+        let ExprKind::Dice { modifiers, .. } = &ast[roots[2]].kind else {
+            panic!("Expected dice");
+        };
+        assert_eq!(text(ast[roots[2]].span), "4d6kh3");
+        assert_eq!(text(modifiers[0].span), "kh3");
+        assert_eq!(text(ast[modifiers[0].value.unwrap()].span), "3");
+    }
+
+    #[test]
+    fn test_chained_op_spans() {
+        let input = "1 + 2 - 3";
+        let ast = parse(input).unwrap();
+        let root = ast.roots()[0];
+
+        let ExprKind::BinaryOp(lhs, '-', _) = &ast[root].kind else {
+            panic!("Expected subtraction");
+        };
+        let span = ast[*lhs].span;
+        assert_eq!(&input[span.start..span.end], "1 + 2");
+        assert_eq!(ast[root].span, Span::new(0, input.len()));
+    }
+
+    #[test]
+    fn test_trailing_garbage_is_rejected() {
+        assert!(parse("4d6kh3xyz").is_err());
+        assert!(parse(" 4d6kh3 ").is_ok());
+    }
 
-        assert!(matches!(exprs[0], Expr::Dice { .. }));
-        assert!(matches!(exprs[1], Expr::BinaryOp(_, '+', _)));
+    #[test]
+    fn test_display_canonical_form() {
+        let cases = [
+            ("4d6k3+2", "4d6kh3 + 2"),
+            ("d8", "1d8"),
+            ("(2)d6d1", "2d6dh1"),
+            ("(2d6+1)*2", "(2d6 + 1) * 2"),
+            ("8-(2-1)", "8 - (2 - 1)"),
+            ("3([1d6+2])kh(1d2)", "3(1d6 + 2)kh(1d2)"),
+            ("1d6!", "1d6!"),
+            ("3d6!!5", "3d6!!5"),
+            ("4df+3", "4dF + 3"),
+            ("d20+5vs d20", "1d20 + 5 vs 1d20"),
+            ("d20+7 vs 10/15/20", "1d20 + 7 vs 10/15/20"),
+            ("4d6!!{ 1 ,6}", "4d6!!{1,6}"),
+            ("2d6ra1", "2d6ra1"),
+            ("half( 8d6 ,down)", "half(8d6)"),
+            ("double(2d6)*2", "double(2d6) * 2"),
+            ("clamp(1d20-5,1,20)", "clamp(1d20 - 5, 1, 20)"),
+            ("6d6straight", "6d6straight"),
+            ("6d6h", "6d6h"),
+            ("{ 1d12,2d6 }kh1", "{1d12, 2d6}kh1"),
+            ("{1d8, 1d6}h", "{1d8, 1d6}h"),
+            ("4d6rr( sum<8 )", "4d6rr(sum<8)"),
+            (
+                "choose{ sword : 3,bow,staff:1 }",
+                "choose{sword:3, bow, staff}",
+            ),
+            ("choose{long sword, bow} + 1", "choose{long sword, bow} + 1"),
+            ("between(5,1d20)", "between(5, 1d20)"),
+            ("d[37..213] + 1", "between(37, 213) + 1"),
+            ("10(1d20+5)>=15", "10(1d20 + 5) >= 15"),
+            ("8d6 > 4 * 2", "8d6 > 4 * 2"),
+            ("6(4d6kh3)rr(sum<=70,3)", "6(4d6kh3)rr(sum<=70, 3)"),
+            ("3(2d6)l", "3(2d6)l"),
+            ("5d6kh3distinct*2", "5d6kh3distinct * 2"),
+            ("6d6[distinct,straight]", "6d6 [distinct, straight]"),
+            ("2d6ra{1,2}", "2d6ra{1,2}"),
+            ("1d20r1 + 2d6ro2", "1d20r1 + 2d6ro2"),
+            ("4d6r{1, 2}kh3", "4d6r{1,2}kh3"),
+            ("4d6rkh3rr(sum<8)", "4d6rkh3rr(sum<8)"),
+        ];
+        for (input, expected) in cases {
+            let ast = parse(input).unwrap();
+            assert_eq!(ast.to_string(), expected);
+
+            let reparsed = parse(expected).unwrap();
+            assert!(ast.same_structure(ast.roots()[0], &reparsed, reparsed.roots()[0]));
+        }
     }
 }
@@ -0,0 +1,94 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// A Blades in the Dark action roll.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    pub dice: Vec<i32>,
+    /// Rolled with zero dice: two dice, keeping the lower.
+    pub zero_dice: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Failure,
+    Partial,
+    Success,
+    Critical,
+}
+
+pub fn roll_pool(pool: u32) -> Roll {
+    let zero_dice = pool == 0;
+    let count = if zero_dice { 2 } else { pool };
+    Roll {
+        dice: (0..count).map(|_| roll(6)).collect(),
+        zero_dice,
+    }
+}
+
+impl Roll {
+    /// The die that counts: the highest, or the lowest with zero dice.
+    pub fn result(&self) -> i32 {
+        let dice = self.dice.iter().copied();
+        if self.zero_dice {
+            dice.min().unwrap()
+        } else {
+            dice.max().unwrap()
+        }
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        let sixes = self.dice.iter().filter(|&&d| d == 6).count();
+        match self.result() {
+            6 if sixes >= 2 && !self.zero_dice => Outcome::Critical,
+            6 => Outcome::Success,
+            4 | 5 => Outcome::Partial,
+            _ => Outcome::Failure,
+        }
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = match self.outcome() {
+            Outcome::Failure => "failure",
+            Outcome::Partial => "partial success",
+            Outcome::Success => "success",
+            Outcome::Critical => "critical",
+        };
+        write!(f, "{}: {}", self.result(), outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_the_highest_die() {
+        let roll = |dice: Vec<i32>| Roll {
+            dice,
+            zero_dice: false,
+        };
+        assert_eq!(roll(vec![6, 2, 6]).to_string(), "6: critical");
+        assert_eq!(roll(vec![6, 2]).outcome(), Outcome::Success);
+        assert_eq!(roll(vec![1, 5, 3]).to_string(), "5: partial success");
+        assert_eq!(roll(vec![3, 1]).outcome(), Outcome::Failure);
+    }
+
+    #[test]
+    fn zero_dice_keep_the_lower() {
+        let roll = Roll {
+            dice: vec![6, 6],
+            zero_dice: true,
+        };
+        assert_eq!(roll.outcome(), Outcome::Success);
+        let roll = Roll {
+            dice: vec![5, 2],
+            zero_dice: true,
+        };
+        assert_eq!(roll.to_string(), "2: failure");
+        assert_eq!(roll_pool(0).dice.len(), 2);
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+
+use crate::ast::DiceModifierType;
+use crate::eval::{EvalResult, Rolls, apply_modifiers, roll};
+
+/// The shade of a Burning Wheel pool, which sets what counts as a success.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shade {
+    Black,
+    Gray,
+    White,
+}
+
+impl Shade {
+    pub fn from_letter(letter: char) -> Option<Shade> {
+        match letter.to_ascii_lowercase() {
+            'b' => Some(Shade::Black),
+            'g' => Some(Shade::Gray),
+            'w' => Some(Shade::White),
+            _ => None,
+        }
+    }
+
+    /// The lowest face that counts as a success.
+    pub fn target(self) -> i32 {
+        match self {
+            Shade::Black => 4,
+            Shade::Gray => 3,
+            Shade::White => 2,
+        }
+    }
+}
+
+/// A Burning Wheel test against an obstacle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Test {
+    pub dice: Rolls,
+    pub successes: i32,
+    pub obstacle: i32,
+}
+
+/// Rolls `pool` d6 of `shade` against `obstacle`. Open-ended tests reroll
+/// every 6 and add the new die to the pool.
+pub fn roll_test(pool: u32, shade: Shade, obstacle: i32, open_ended: bool) -> Test {
+    let dice: Rolls = (0..pool).map(|_| roll(6)).collect();
+    let mut modifiers = Vec::new();
+    if open_ended {
+        modifiers.push((DiceModifierType::Explode, None, &[][..]));
+    }
+    modifiers.push((DiceModifierType::Target, Some(shade.target()), &[][..]));
+
+    let result = apply_modifiers(dice, modifiers, Some(6));
+    let successes = result.to_number();
+    let EvalResult::Successes { rolls, .. } = result else {
+        unreachable!("a target always yields successes");
+    };
+    Test {
+        dice: rolls,
+        successes,
+        obstacle,
+    }
+}
+
+impl Test {
+    pub fn passed(&self) -> bool {
+        self.successes >= self.obstacle
+    }
+
+    /// Successes over the obstacle; negative on a failure.
+    pub fn margin(&self) -> i32 {
+        self.successes - self.obstacle
+    }
+}
+
+impl fmt::Display for Test {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let noun = if self.successes == 1 {
+            "success"
+        } else {
+            "successes"
+        };
+        let result = if self.passed() { "pass" } else { "fail" };
+        write!(
+            f,
+            "{} {} vs Ob {}: {} by {}",
+            self.successes,
+            noun,
+            self.obstacle,
+            result,
+            self.margin().abs()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smallvec::smallvec;
+
+    #[test]
+    fn reports_margin_against_obstacle() {
+        let test = Test {
+            dice: smallvec![4, 6, 1, 5, 2, 3],
+            successes: 3,
+            obstacle: 2,
+        };
+        assert_eq!(test.to_string(), "3 successes vs Ob 2: pass by 1");
+        let test = Test {
+            successes: 1,
+            obstacle: 3,
+            ..test
+        };
+        assert_eq!(test.to_string(), "1 success vs Ob 3: fail by 2");
+    }
+
+    #[test]
+    fn shades_set_the_target() {
+        fastrand::seed(6);
+        let test = roll_test(10, Shade::Gray, 3, false);
+        let expected = test.dice.iter().filter(|&&d| d >= 3).count() as i32;
+        assert_eq!(test.successes, expected);
+        assert_eq!(Shade::from_letter('W'), Some(Shade::White));
+    }
+}
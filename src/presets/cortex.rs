@@ -0,0 +1,111 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// How many dice make up a Cortex total.
+pub const TOTAL_DICE: usize = 2;
+/// The effect die when no die is left over.
+pub const DEFAULT_EFFECT: i32 = 4;
+
+/// One die of a mixed Cortex pool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Die {
+    pub sides: i32,
+    pub value: i32,
+}
+
+/// A Cortex Prime roll, with each die's role decided after rolling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    pub dice: Vec<Die>,
+    /// Indices of the dice summed for the total.
+    pub total_dice: Vec<usize>,
+    /// Size of the effect die, picked from the dice not in the total.
+    pub effect: i32,
+}
+
+pub fn roll_pool(sides: &[i32]) -> Roll {
+    let dice = sides
+        .iter()
+        .map(|&sides| Die {
+            sides,
+            value: roll(sides),
+        })
+        .collect();
+    assess(dice)
+}
+
+/// Sums the best two dice and takes the largest remaining die as the
+/// effect. Hitches (1s) can't be used for either.
+pub fn assess(dice: Vec<Die>) -> Roll {
+    let mut usable: Vec<usize> = (0..dice.len()).filter(|&i| dice[i].value > 1).collect();
+    usable.sort_by_key(|&i| std::cmp::Reverse(dice[i].value));
+    let split = usable.len().min(TOTAL_DICE);
+    let (total_dice, rest) = usable.split_at(split);
+    let effect = rest
+        .iter()
+        .map(|&i| dice[i].sides)
+        .max()
+        .unwrap_or(DEFAULT_EFFECT);
+    Roll {
+        total_dice: total_dice.to_vec(),
+        effect,
+        dice,
+    }
+}
+
+impl Roll {
+    pub fn total(&self) -> i32 {
+        self.total_dice.iter().map(|&i| self.dice[i].value).sum()
+    }
+
+    pub fn hitches(&self) -> usize {
+        self.dice.iter().filter(|d| d.value == 1).count()
+    }
+
+    /// Every die came up 1.
+    pub fn is_botch(&self) -> bool {
+        self.hitches() == self.dice.len()
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_botch() {
+            return write!(f, "botch");
+        }
+        write!(f, "total {}, effect d{}", self.total(), self.effect)?;
+        match self.hitches() {
+            0 => Ok(()),
+            1 => write!(f, ", 1 hitch"),
+            n => write!(f, ", {} hitches", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(dice: &[(i32, i32)]) -> Roll {
+        let dice = dice
+            .iter()
+            .map(|&(sides, value)| Die { sides, value })
+            .collect();
+        assess(dice)
+    }
+
+    #[test]
+    fn totals_best_two_and_picks_effect() {
+        let roll = pool(&[(8, 5), (8, 1), (6, 4), (10, 7), (12, 2)]);
+        assert_eq!(roll.total(), 12);
+        assert_eq!(roll.effect, 12);
+        assert_eq!(roll.to_string(), "total 12, effect d12, 1 hitch");
+    }
+
+    #[test]
+    fn defaults_effect_and_detects_botch() {
+        assert_eq!(pool(&[(8, 3), (6, 6)]).to_string(), "total 9, effect d4");
+        assert!(pool(&[(8, 1), (6, 1)]).is_botch());
+    }
+}
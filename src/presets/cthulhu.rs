@@ -0,0 +1,126 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// A Call of Cthulhu percentile roll, with any bonus or penalty dice rolled
+/// as extra tens digits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    /// Every tens digit rolled, 0–9.
+    pub tens: Vec<i32>,
+    pub units: i32,
+    /// Net bonus dice; negative for penalty dice.
+    pub bonus: i32,
+    pub skill: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Fumble,
+    Failure,
+    Regular,
+    Hard,
+    Extreme,
+    Critical,
+}
+
+/// Rolls d100 against `skill`. A positive `bonus` adds bonus dice and keeps
+/// the best tens digit; a negative one adds penalty dice and keeps the worst.
+pub fn roll_against(skill: i32, bonus: i32) -> Roll {
+    // Percentile dice read 0–9, so each is a d10 less one.
+    let tens = (0..=bonus.abs()).map(|_| roll(10) - 1).collect();
+    Roll {
+        tens,
+        units: roll(10) - 1,
+        bonus,
+        skill,
+    }
+}
+
+impl Roll {
+    /// The result, 1–100; 00 with a 0 units die reads as 100.
+    pub fn total(&self) -> i32 {
+        let result = |tens: i32| match tens * 10 + self.units {
+            0 => 100,
+            n => n,
+        };
+        let results = self.tens.iter().map(|&t| result(t));
+        if self.bonus >= 0 {
+            results.min().unwrap()
+        } else {
+            results.max().unwrap()
+        }
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        let (total, skill) = (self.total(), self.skill);
+        let fumble_at = if skill < 50 { 96 } else { 100 };
+        if total == 1 {
+            Outcome::Critical
+        } else if total >= fumble_at {
+            Outcome::Fumble
+        } else if total <= skill / 5 {
+            Outcome::Extreme
+        } else if total <= skill / 2 {
+            Outcome::Hard
+        } else if total <= skill {
+            Outcome::Regular
+        } else {
+            Outcome::Failure
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Outcome::Fumble => "fumble",
+            Outcome::Failure => "failure",
+            Outcome::Regular => "regular success",
+            Outcome::Hard => "hard success",
+            Outcome::Extreme => "extreme success",
+            Outcome::Critical => "critical success",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.total(), self.outcome())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roll(tens: Vec<i32>, units: i32, bonus: i32, skill: i32) -> Roll {
+        Roll {
+            tens,
+            units,
+            bonus,
+            skill,
+        }
+    }
+
+    #[test]
+    fn bonus_and_penalty_dice_pick_tens() {
+        assert_eq!(roll(vec![7, 2], 5, 1, 50).total(), 25);
+        assert_eq!(roll(vec![7, 2], 5, -1, 50).total(), 75);
+        assert_eq!(roll(vec![0], 0, 0, 50).total(), 100);
+        assert_eq!(roll(vec![0, 3], 0, 1, 50).total(), 30);
+    }
+
+    #[test]
+    fn classifies_outcomes() {
+        let outcome = |total: i32, skill| roll(vec![total / 10], total % 10, 0, skill).outcome();
+        assert_eq!(outcome(1, 65), Outcome::Critical);
+        assert_eq!(outcome(13, 65), Outcome::Extreme);
+        assert_eq!(outcome(32, 65), Outcome::Hard);
+        assert_eq!(outcome(65, 65), Outcome::Regular);
+        assert_eq!(outcome(66, 65), Outcome::Failure);
+        assert_eq!(outcome(97, 40), Outcome::Fumble);
+        assert_eq!(outcome(97, 65), Outcome::Failure);
+    }
+}
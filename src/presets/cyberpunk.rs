@@ -0,0 +1,71 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// A Cyberpunk RED check: d10 plus a modifier, where a 10 adds a second
+/// d10 (critical success) and a 1 subtracts one (critical failure).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    pub die: i32,
+    /// The extra d10 rolled on a 10 or a 1.
+    pub critical: Option<i32>,
+    pub modifier: i32,
+}
+
+pub fn roll_check(modifier: i32) -> Roll {
+    let die = roll(10);
+    let critical = (die == 10 || die == 1).then(|| roll(10));
+    Roll {
+        die,
+        critical,
+        modifier,
+    }
+}
+
+impl Roll {
+    pub fn total(&self) -> i32 {
+        let critical = match (self.die, self.critical) {
+            (10, Some(extra)) => extra,
+            (1, Some(extra)) => -extra,
+            _ => 0,
+        };
+        self.die + critical + self.modifier
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.total())?;
+        match (self.die, self.critical) {
+            (10, Some(extra)) => write!(f, " (critical success, +{})", extra),
+            (1, Some(extra)) => write!(f, " (critical failure, -{})", extra),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn criticals_add_or_subtract_a_die() {
+        let roll = |die, critical| Roll {
+            die,
+            critical,
+            modifier: 14,
+        };
+        assert_eq!(roll(6, None).to_string(), "20");
+        assert_eq!(roll(10, Some(7)).to_string(), "31 (critical success, +7)");
+        assert_eq!(roll(1, Some(4)).to_string(), "11 (critical failure, -4)");
+    }
+
+    #[test]
+    fn rolls_a_critical_die_only_on_ten_or_one() {
+        fastrand::seed(8);
+        for _ in 0..200 {
+            let roll = roll_check(0);
+            assert_eq!(roll.critical.is_some(), roll.die == 10 || roll.die == 1);
+        }
+    }
+}
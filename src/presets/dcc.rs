@@ -0,0 +1,40 @@
+use crate::eval::roll;
+
+/// The Dungeon Crawl Classics dice chain, including the Zocchi dice (d3, d5,
+/// d7, d14, d16, d24, d30). Action dice move up and down it in steps.
+pub const DICE_CHAIN: [i32; 13] = [3, 4, 5, 6, 7, 8, 10, 12, 14, 16, 20, 24, 30];
+
+/// Moves a die `steps` along the chain (negative steps go down), stopping
+/// at either end. Returns `None` if `sides` is not on the chain.
+pub fn step(sides: i32, steps: i32) -> Option<i32> {
+    let index = DICE_CHAIN.iter().position(|&s| s == sides)? as i32;
+    let stepped = (index + steps).clamp(0, DICE_CHAIN.len() as i32 - 1);
+    Some(DICE_CHAIN[stepped as usize])
+}
+
+/// Rolls a die moved `steps` along the chain, returning the die used and
+/// the roll.
+pub fn roll_stepped(sides: i32, steps: i32) -> Option<(i32, i32)> {
+    let sides = step(sides, steps)?;
+    Some((sides, roll(sides)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_along_the_chain() {
+        assert_eq!(step(20, -1), Some(16));
+        assert_eq!(step(20, 1), Some(24));
+        assert_eq!(step(6, -2), Some(4));
+        assert_eq!(step(20, 0), Some(20));
+    }
+
+    #[test]
+    fn stops_at_the_ends_and_rejects_other_dice() {
+        assert_eq!(step(3, -1), Some(3));
+        assert_eq!(step(24, 5), Some(30));
+        assert_eq!(step(9, 1), None);
+    }
+}
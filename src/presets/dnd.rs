@@ -0,0 +1,641 @@
+use std::fmt;
+
+use crate::ast::{Ast, ExprId, ExprKind};
+use crate::compile::Program;
+use crate::distribution::exact;
+use crate::eval::{eval_total, roll};
+use crate::lint::MAX_POOL;
+use crate::optimize::fold_all;
+use crate::parser::parse;
+use crate::sim::{expected_total, simulate};
+
+/// Samples used to estimate the mean damage of expressions that have no
+/// exact mean, such as `4d6!`.
+const MEAN_SAMPLES: u64 = 100_000;
+
+/// A damage expression such as `1d8+4`, together with its critical
+/// version, which rolls every die twice but adds modifiers once.
+#[derive(Debug)]
+pub struct Damage {
+    normal: Ast,
+    critical: Ast,
+}
+
+impl Damage {
+    pub fn parse(input: &str) -> Result<Damage, String> {
+        let normal = parse(input)?;
+        if normal.roots().len() != 1 {
+            return Err(format!("expected one damage expression, got `{}`", input));
+        }
+        let mut critical = parse(input)?;
+        let root = critical.roots()[0];
+        double_dice(&mut critical, root);
+        fold_all(&mut critical);
+        Ok(Damage { normal, critical })
+    }
+
+    pub fn roll(&self, critical: bool) -> i32 {
+        let ast = if critical {
+            &self.critical
+        } else {
+            &self.normal
+        };
+        eval_total(ast, ast.roots()[0])
+    }
+
+    /// The average damage; exact where possible, otherwise simulated.
+    pub fn mean(&self, critical: bool) -> f64 {
+        let ast = if critical {
+            &self.critical
+        } else {
+            &self.normal
+        };
+        let root = ast.roots()[0];
+        expected_total(ast, root)
+            .or_else(|| exact(ast, root).map(|dist| dist.mean()))
+            .unwrap_or_else(|| simulate(&Program::compile(ast, root), MEAN_SAMPLES).mean())
+    }
+}
+
+/// Doubles the count of every plain pool of dice under `id`. Pools that
+/// keep, drop, explode or are read some other way are rolled twice and
+/// added instead: a crit on `4d6kh3` is two rolls of `4d6kh3`, not the
+/// best three of 8d6.
+fn double_dice(ast: &mut Ast, id: ExprId) {
+    let rolled_twice = match &ast[id].kind {
+        ExprKind::Dice { modifiers, .. }
+        | ExprKind::Fudge { modifiers, .. }
+        | ExprKind::Repetition { modifiers, .. }
+        | ExprKind::Group { modifiers, .. } => !modifiers.is_empty(),
+        ExprKind::Read { .. } | ExprKind::Reroll { .. } => true,
+        _ => false,
+    };
+    if rolled_twice {
+        let span = ast[id].span;
+        let kind = std::mem::replace(&mut ast[id].kind, ExprKind::Number(0));
+        let once = ast.push(kind, span);
+        ast[id].kind = ExprKind::BinaryOp(once, '+', once);
+        return;
+    }
+    for child in ast.children(id) {
+        double_dice(ast, child);
+    }
+    let (ExprKind::Dice { count, .. } | ExprKind::Fudge { count, .. }) = ast[id].kind else {
+        return;
+    };
+    let two = ast.number(2);
+    let doubled = ast.binary(count, '*', two);
+    if let ExprKind::Dice { count, .. } | ExprKind::Fudge { count, .. } = &mut ast[id].kind {
+        *count = doubled;
+    }
+}
+
+/// A d20 attack roll against an armor class.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attack {
+    pub d20: i32,
+    pub bonus: i32,
+    pub ac: i32,
+    /// The lowest natural roll that crits, 20 by default.
+    pub crit_range: i32,
+    /// Rolled only on a hit.
+    pub damage: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Critical,
+    Hit,
+    Miss,
+}
+
+pub fn roll_attack(bonus: i32, ac: i32, crit_range: i32, damage: Option<&Damage>) -> Attack {
+    let mut attack = Attack {
+        d20: roll(20),
+        bonus,
+        ac,
+        crit_range,
+        damage: None,
+    };
+    attack.damage = match attack.outcome() {
+        Outcome::Miss => None,
+        outcome => damage.map(|d| d.roll(outcome == Outcome::Critical)),
+    };
+    attack
+}
+
+impl Attack {
+    pub fn total(&self) -> i32 {
+        self.d20.saturating_add(self.bonus)
+    }
+
+    /// Natural rolls in the crit range always hit and a natural 1 always
+    /// misses.
+    pub fn outcome(&self) -> Outcome {
+        if self.d20 >= self.crit_range {
+            Outcome::Critical
+        } else if self.d20 > 1 && self.total() >= self.ac {
+            Outcome::Hit
+        } else {
+            Outcome::Miss
+        }
+    }
+}
+
+impl fmt::Display for Attack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} vs AC {}: ", self.total(), self.ac)?;
+        match self.outcome() {
+            Outcome::Critical => write!(f, "critical hit")?,
+            Outcome::Hit => write!(f, "hit")?,
+            Outcome::Miss => return write!(f, "miss"),
+        }
+        match self.damage {
+            Some(damage) => write!(f, ", {} damage", damage),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A group check: everyone rolls, and the group passes if at least half
+/// of them meet the DC.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupCheck {
+    pub totals: Vec<i32>,
+    pub dc: i32,
+}
+
+/// Rolls the root expression of `check` once per member.
+pub fn group_check(check: &Ast, members: u32, dc: i32) -> GroupCheck {
+    let root = check.roots()[0];
+    GroupCheck {
+        totals: (0..members).map(|_| eval_total(check, root)).collect(),
+        dc,
+    }
+}
+
+impl GroupCheck {
+    pub fn successes(&self) -> usize {
+        self.totals.iter().filter(|&&t| t >= self.dc).count()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.successes() * 2 >= self.totals.len()
+    }
+}
+
+impl fmt::Display for GroupCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} succeeded: the group {}",
+            self.successes(),
+            self.totals.len(),
+            if self.passed() { "passes" } else { "fails" }
+        )
+    }
+}
+
+/// One combatant's place in the turn order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Initiative {
+    pub name: String,
+    pub modifier: i32,
+    pub d20: i32,
+}
+
+impl Initiative {
+    pub fn total(&self) -> i32 {
+        self.d20.saturating_add(self.modifier)
+    }
+}
+
+/// Rolls initiative for each named combatant and sorts them into turn
+/// order: highest total first, ties going to the higher modifier.
+pub fn roll_initiative(combatants: &[(String, i32)]) -> Vec<Initiative> {
+    let mut order: Vec<Initiative> = combatants
+        .iter()
+        .map(|(name, modifier)| Initiative {
+            name: name.clone(),
+            modifier: *modifier,
+            d20: roll(20),
+        })
+        .collect();
+    order.sort_by_key(|i| std::cmp::Reverse((i.total(), i.modifier)));
+    order
+}
+
+/// Whether a d20 is rolled once, or twice keeping the higher or lower.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RollMode {
+    Normal,
+    Advantage,
+    Disadvantage,
+}
+
+/// The chances that an attack hits, crits included, and that it crits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttackOdds {
+    pub hit: f64,
+    pub crit: f64,
+}
+
+pub fn attack_odds(bonus: i32, ac: i32, crit_range: i32, mode: RollMode) -> AttackOdds {
+    let mut odds = AttackOdds {
+        hit: 0.0,
+        crit: 0.0,
+    };
+    for d20 in 1..=20 {
+        let (below, at_most) = ((d20 - 1) as f64 / 20.0, d20 as f64 / 20.0);
+        let chance = match mode {
+            RollMode::Normal => 0.05,
+            RollMode::Advantage => at_most.powi(2) - below.powi(2),
+            RollMode::Disadvantage => (1.0 - below).powi(2) - (1.0 - at_most).powi(2),
+        };
+        let attack = Attack {
+            d20,
+            bonus,
+            ac,
+            crit_range,
+            damage: None,
+        };
+        match attack.outcome() {
+            Outcome::Critical => {
+                odds.hit += chance;
+                odds.crit += chance;
+            }
+            Outcome::Hit => odds.hit += chance,
+            Outcome::Miss => {}
+        }
+    }
+    odds
+}
+
+impl AttackOdds {
+    /// Average damage per attack, counting misses as zero.
+    pub fn expected_damage(&self, damage: &Damage) -> f64 {
+        (self.hit - self.crit) * damage.mean(false) + self.crit * damage.mean(true)
+    }
+}
+
+/// The scores every character may take instead of rolling.
+pub const STANDARD_ARRAY: [i32; 6] = [15, 14, 13, 12, 10, 8];
+
+/// The highest minimum total [`roll_ability_scores`] accepts. Higher totals
+/// can roll (up to 108), but less than once in a thousand sets.
+pub const MAX_MIN_TOTAL: i32 = 90;
+
+/// How many sets [`roll_ability_scores`] rolls before giving up; a 90 or
+/// better turns up within this many sets all but certainly.
+pub const ABILITY_REROLL_LIMIT: u32 = 10_000;
+
+/// The modifier for an ability score, e.g. +2 for 14 and -1 for 8 or 9.
+pub fn ability_modifier(score: i32) -> i32 {
+    (score - 10).div_euclid(2)
+}
+
+/// A set of six ability scores, each the best three of 4d6.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbilityScores {
+    /// The four dice behind each score, highest score first.
+    pub dice: [[i32; 4]; 6],
+    /// Sets thrown away for totalling less than the minimum.
+    pub rerolls: u32,
+}
+
+/// Rolls ability scores, rerolling whole sets that total less than
+/// `min_total`, or fails after [`ABILITY_REROLL_LIMIT`] rerolls.
+pub fn roll_ability_scores(min_total: i32) -> Result<AbilityScores, String> {
+    let mut rerolls = 0;
+    loop {
+        let mut dice = [[0; 4]; 6];
+        for score in &mut dice {
+            *score = [roll(6), roll(6), roll(6), roll(6)];
+            score.sort_unstable_by(|a, b| b.cmp(a));
+        }
+        dice.sort_unstable_by_key(|d| -best_three(d));
+        let scores = AbilityScores { dice, rerolls };
+        if scores.total() >= min_total {
+            return Ok(scores);
+        }
+        if rerolls == ABILITY_REROLL_LIMIT {
+            return Err(format!(
+                "no set totalled {} or more in {} tries",
+                min_total,
+                ABILITY_REROLL_LIMIT + 1
+            ));
+        }
+        rerolls += 1;
+    }
+}
+
+fn best_three(dice: &[i32; 4]) -> i32 {
+    dice.iter().sum::<i32>() - dice.iter().min().unwrap()
+}
+
+impl AbilityScores {
+    pub fn scores(&self) -> [i32; 6] {
+        self.dice.map(|d| best_three(&d))
+    }
+
+    pub fn total(&self) -> i32 {
+        self.scores().iter().sum()
+    }
+}
+
+impl fmt::Display for AbilityScores {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scores: Vec<String> = self
+            .scores()
+            .iter()
+            .map(|&s| format!("{} ({:+})", s, ability_modifier(s)))
+            .collect();
+        write!(f, "{}, total {}", scores.join(", "), self.total())
+    }
+}
+
+/// The point-buy budget in the Player's Handbook.
+pub const POINT_BUY_BUDGET: i32 = 27;
+
+/// What a score costs under point buy, from 0 for an 8 to 9 for a 15.
+/// Scores outside 8 to 15 cannot be bought.
+pub fn point_buy_cost(score: i32) -> Option<i32> {
+    match score {
+        8..=13 => Some(score - 8),
+        14 => Some(7),
+        15 => Some(9),
+        _ => None,
+    }
+}
+
+/// Checks six scores against a point-buy budget and returns what they
+/// cost.
+pub fn check_point_buy(scores: &[i32], budget: i32) -> Result<i32, String> {
+    if scores.len() != 6 {
+        return Err(format!("expected 6 scores, got {}", scores.len()));
+    }
+    let mut cost = 0;
+    for &score in scores {
+        cost += point_buy_cost(score)
+            .ok_or_else(|| format!("{} cannot be bought; scores run from 8 to 15", score))?;
+    }
+    if cost > budget {
+        return Err(format!(
+            "costs {} points, over the budget of {}",
+            cost, budget
+        ));
+    }
+    Ok(cost)
+}
+
+/// Whether six scores are the standard array in some order.
+pub fn is_standard_array(scores: &[i32]) -> bool {
+    let mut sorted = scores.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    sorted == STANDARD_ARRAY
+}
+
+/// Buys six random scores with up to `budget` points, raising a random
+/// score by one for as long as any raise is affordable. Highest first.
+pub fn random_point_buy(budget: i32) -> [i32; 6] {
+    let mut scores = [8; 6];
+    let mut left = budget;
+    loop {
+        let raise = |score: i32| Some(point_buy_cost(score + 1)? - point_buy_cost(score)?);
+        let affordable: Vec<usize> = (0..6)
+            .filter(|&i| raise(scores[i]).is_some_and(|cost| cost <= left))
+            .collect();
+        if affordable.is_empty() {
+            break;
+        }
+        let i = affordable[fastrand::usize(..affordable.len())];
+        left -= raise(scores[i]).unwrap();
+        scores[i] += 1;
+    }
+    scores.sort_unstable_by(|a, b| b.cmp(a));
+    scores
+}
+
+/// Hit points for a character level: the hit die's maximum at level 1,
+/// then a roll (or the fixed average) per level, plus the Constitution
+/// modifier each level. Every level adds at least 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitPoints {
+    pub sides: i32,
+    pub con: i32,
+    /// The hit die for each level after the first.
+    pub gains: Vec<i32>,
+}
+
+/// Refuses levels past [`MAX_POOL`], one hit die each, and characters
+/// whose most hit points would not fit an `i32`.
+pub fn hit_points(sides: i32, level: u32, con: i32, average: bool) -> Result<HitPoints, String> {
+    if level > MAX_POOL as u32 {
+        return Err(format!("level {} is more than {}", level, MAX_POOL));
+    }
+    let most = (i64::from(sides) + i64::from(con)).max(1) * i64::from(level);
+    if most > i64::from(i32::MAX) {
+        return Err(format!(
+            "level {} with d{} and {:+} could reach {} HP, too many to count",
+            level, sides, con, most
+        ));
+    }
+    let gains = (1..level)
+        .map(|_| if average { sides / 2 + 1 } else { roll(sides) })
+        .collect();
+    Ok(HitPoints { sides, con, gains })
+}
+
+impl HitPoints {
+    pub fn total(&self) -> i32 {
+        let first = (self.sides + self.con).max(1);
+        let rest: i32 = self.gains.iter().map(|g| (g + self.con).max(1)).sum();
+        first + rest
+    }
+}
+
+impl fmt::Display for HitPoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} HP", self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attack(d20: i32, bonus: i32, ac: i32) -> Attack {
+        Attack {
+            d20,
+            bonus,
+            ac,
+            crit_range: 20,
+            damage: None,
+        }
+    }
+
+    #[test]
+    fn naturals_override_the_armor_class() {
+        assert_eq!(attack(9, 7, 16).outcome(), Outcome::Hit);
+        assert_eq!(attack(8, 7, 16).outcome(), Outcome::Miss);
+        assert_eq!(attack(1, 20, 10).outcome(), Outcome::Miss);
+        assert_eq!(attack(20, 0, 30).outcome(), Outcome::Critical);
+        let wide = Attack {
+            crit_range: 19,
+            ..attack(19, 0, 30)
+        };
+        assert_eq!(wide.outcome(), Outcome::Critical);
+
+        let hit = Attack {
+            damage: Some(9),
+            ..attack(12, 7, 16)
+        };
+        assert_eq!(hit.to_string(), "19 vs AC 16: hit, 9 damage");
+        assert_eq!(attack(2, 7, 16).to_string(), "9 vs AC 16: miss");
+        assert_eq!(attack(20, i32::MAX, 30).total(), i32::MAX);
+    }
+
+    #[test]
+    fn crits_double_the_dice_but_not_the_modifier() {
+        let damage = Damage::parse("1d8+4").unwrap();
+        assert_eq!(damage.critical.to_string(), "2d8 + 4");
+        for _ in 0..100 {
+            assert!((5..=12).contains(&damage.roll(false)));
+            assert!((6..=20).contains(&damage.roll(true)));
+        }
+        assert!(Damage::parse("1d8 1d6").is_err());
+
+        let kept = Damage::parse("4d6kh3 + 2d6 + 1").unwrap();
+        assert_eq!(kept.critical.to_string(), "4d6kh3 + 4d6kh3 + 4d6 + 1");
+        for _ in 0..100 {
+            assert!((11..=61).contains(&kept.roll(true)));
+        }
+        assert!((kept.mean(true) - (2.0 * 12.2446 + 15.0)).abs() < 0.1);
+    }
+
+    #[test]
+    fn attack_odds_account_for_crits_and_advantage() {
+        let odds = attack_odds(7, 16, 20, RollMode::Normal);
+        assert!((odds.hit - 0.6).abs() < 1e-9);
+        assert!((odds.crit - 0.05).abs() < 1e-9);
+
+        let damage = Damage::parse("1d8+4").unwrap();
+        assert!((odds.expected_damage(&damage) - (0.55 * 8.5 + 0.05 * 13.0)).abs() < 1e-9);
+
+        let advantage = attack_odds(7, 16, 19, RollMode::Advantage);
+        assert!((advantage.hit - (1.0 - 0.4 * 0.4)).abs() < 1e-9);
+        assert!((advantage.crit - (1.0 - 0.9 * 0.9)).abs() < 1e-9);
+        let disadvantage = attack_odds(7, 16, 20, RollMode::Disadvantage);
+        assert!((disadvantage.hit - 0.36).abs() < 1e-9);
+    }
+
+    #[test]
+    fn group_checks_need_half_to_succeed() {
+        let check = GroupCheck {
+            totals: vec![14, 18, 9, 21],
+            dc: 15,
+        };
+        assert_eq!(check.to_string(), "2 of 4 succeeded: the group passes");
+        let check = GroupCheck {
+            totals: vec![14, 18, 9, 21, 12],
+            dc: 15,
+        };
+        assert_eq!(check.to_string(), "2 of 5 succeeded: the group fails");
+
+        let ast = parse("1d20 + 2").unwrap();
+        let rolled = group_check(&ast, 5, 15);
+        assert_eq!(rolled.totals.len(), 5);
+        assert!(rolled.totals.iter().all(|t| (3..=22).contains(t)));
+    }
+
+    #[test]
+    fn initiative_ties_go_to_the_higher_modifier() {
+        let combatants = [("Wizard".to_string(), 2), ("Fighter".to_string(), 5)];
+        fastrand::seed(3);
+        for _ in 0..50 {
+            let order = roll_initiative(&combatants);
+            let (first, second) = (&order[0], &order[1]);
+            assert!(
+                first.total() > second.total()
+                    || (first.total() == second.total() && first.name == "Fighter")
+            );
+        }
+    }
+
+    #[test]
+    fn hit_points_max_the_first_level() {
+        let hp = hit_points(10, 7, 3, true).unwrap();
+        assert_eq!(hp.gains, vec![6; 6]);
+        assert_eq!(hp.total(), 13 + 6 * 9);
+        assert_eq!(hp.to_string(), "67 HP");
+
+        let frail = HitPoints {
+            sides: 6,
+            con: -3,
+            gains: vec![1, 2],
+        };
+        assert_eq!(frail.total(), 3 + 1 + 1);
+
+        assert_eq!(hit_points(8, 1, 2, false).unwrap().total(), 10);
+        assert!(hit_points(2147483647, 3, 2147483647, false).is_err());
+        assert!(hit_points(10, 2_000_000_000, 0, true).is_err());
+        assert!(hit_points(1, 1, i32::MIN, false).is_ok());
+    }
+
+    #[test]
+    fn ability_scores_keep_the_best_three_dice() {
+        assert_eq!(ability_modifier(8), -1);
+        assert_eq!(ability_modifier(9), -1);
+        assert_eq!(ability_modifier(10), 0);
+        assert_eq!(ability_modifier(15), 2);
+
+        let scores = AbilityScores {
+            dice: [
+                [6, 6, 5, 1],
+                [6, 4, 4, 4],
+                [5, 4, 4, 2],
+                [5, 4, 3, 3],
+                [4, 3, 3, 1],
+                [3, 3, 2, 2],
+            ],
+            rerolls: 0,
+        };
+        assert_eq!(scores.scores(), [17, 14, 13, 12, 10, 8]);
+        assert_eq!(
+            scores.to_string(),
+            "17 (+3), 14 (+2), 13 (+1), 12 (+1), 10 (+0), 8 (-1), total 74"
+        );
+
+        let rolled = roll_ability_scores(75).unwrap();
+        assert!(rolled.total() >= 75);
+        assert!(rolled.scores().windows(2).all(|w| w[0] >= w[1]));
+        assert!(roll_ability_scores(109).is_err());
+    }
+
+    #[test]
+    fn point_buy_checks_and_generates_arrays() {
+        assert_eq!(check_point_buy(&STANDARD_ARRAY, POINT_BUY_BUDGET), Ok(27));
+        assert_eq!(check_point_buy(&[15, 15, 15, 8, 8, 8], 27), Ok(27));
+        assert_eq!(
+            check_point_buy(&[15, 15, 15, 15, 8, 8], 27),
+            Err("costs 36 points, over the budget of 27".to_string())
+        );
+        assert_eq!(
+            check_point_buy(&[16, 8, 8, 8, 8, 8], 27),
+            Err("16 cannot be bought; scores run from 8 to 15".to_string())
+        );
+        assert!(check_point_buy(&[8, 8], 27).is_err());
+        assert!(is_standard_array(&[8, 10, 12, 13, 14, 15]));
+        assert!(!is_standard_array(&[15, 15, 15, 8, 8, 8]));
+
+        fastrand::seed(4);
+        for budget in [0, 27, 32, 54] {
+            let scores = random_point_buy(budget);
+            let cost = check_point_buy(&scores, budget).unwrap();
+            // Only a raise costing more than the points left stops the buy.
+            assert!(budget - cost < 2 || scores.iter().all(|&s| s >= 13));
+            assert!(scores.windows(2).all(|w| w[0] >= w[1]));
+        }
+        assert_eq!(random_point_buy(54), [15; 6]);
+    }
+}
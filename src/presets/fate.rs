@@ -0,0 +1,39 @@
+/// The Fate ladder adjective for a result, e.g. `Good` for +3. Results past
+/// either end take the nearest rung.
+pub fn ladder(result: i32) -> &'static str {
+    match result {
+        8.. => "Legendary",
+        7 => "Epic",
+        6 => "Fantastic",
+        5 => "Superb",
+        4 => "Great",
+        3 => "Good",
+        2 => "Fair",
+        1 => "Average",
+        0 => "Mediocre",
+        -1 => "Poor",
+        -2 => "Terrible",
+        -3 => "Catastrophic",
+        _ => "Horrifying",
+    }
+}
+
+/// A result with its rung on the ladder, e.g. `+3 Good`.
+pub fn format_ladder(result: i32) -> String {
+    format!("{:+} {}", result, ladder(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_rungs() {
+        assert_eq!(format_ladder(3), "+3 Good");
+        assert_eq!(format_ladder(5), "+5 Superb");
+        assert_eq!(format_ladder(0), "+0 Mediocre");
+        assert_eq!(format_ladder(-2), "-2 Terrible");
+        assert_eq!(ladder(12), "Legendary");
+        assert_eq!(ladder(-9), "Horrifying");
+    }
+}
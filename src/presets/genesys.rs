@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub use crate::symbols::Symbols;
+use crate::symbols::{self, roll_face};
+
+/// The six narrative dice, named by the letter used on the command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Die {
+    Boost,
+    Setback,
+    Ability,
+    Difficulty,
+    Proficiency,
+    Challenge,
+}
+
+impl Die {
+    pub const ALL: [Die; 6] = [
+        Die::Ability,
+        Die::Proficiency,
+        Die::Boost,
+        Die::Difficulty,
+        Die::Challenge,
+        Die::Setback,
+    ];
+
+    pub fn faces(self) -> &'static [Symbols] {
+        match self {
+            Die::Boost => symbols::BOOST,
+            Die::Setback => symbols::SETBACK,
+            Die::Ability => symbols::ABILITY,
+            Die::Difficulty => symbols::DIFFICULTY,
+            Die::Proficiency => symbols::PROFICIENCY,
+            Die::Challenge => symbols::CHALLENGE,
+        }
+    }
+
+    pub fn letter(self) -> char {
+        match self {
+            Die::Boost => 'b',
+            Die::Setback => 's',
+            Die::Ability => 'a',
+            Die::Difficulty => 'd',
+            Die::Proficiency => 'p',
+            Die::Challenge => 'c',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Die> {
+        Die::ALL.into_iter().find(|die| die.letter() == letter)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Die::Boost => "boost",
+            Die::Setback => "setback",
+            Die::Ability => "ability",
+            Die::Difficulty => "difficulty",
+            Die::Proficiency => "proficiency",
+            Die::Challenge => "challenge",
+        }
+    }
+
+    pub fn roll(self) -> Symbols {
+        roll_face(self.faces())
+    }
+
+    /// The symbols one roll of the die shows on average.
+    pub fn expected(self) -> Expected {
+        let faces = self.faces();
+        let mean = |symbol: fn(&Symbols) -> i32| {
+            faces.iter().map(symbol).sum::<i32>() as f64 / faces.len() as f64
+        };
+        Expected {
+            success: mean(|s| s.success),
+            failure: mean(|s| s.failure),
+            advantage: mean(|s| s.advantage),
+            threat: mean(|s| s.threat),
+            triumph: mean(|s| s.triumph),
+            despair: mean(|s| s.despair),
+        }
+    }
+}
+
+/// Average symbols over many rolls, for balancing a pool.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Expected {
+    pub success: f64,
+    pub failure: f64,
+    pub advantage: f64,
+    pub threat: f64,
+    pub triumph: f64,
+    pub despair: f64,
+}
+
+impl Expected {
+    /// The averages of a pool of `pool`, per kind of die and in total.
+    pub fn of_pool(pool: &[(Die, u32)]) -> (Vec<Expected>, Expected) {
+        let mut total = Expected::default();
+        let per_die = pool
+            .iter()
+            .map(|&(die, count)| {
+                let one = die.expected();
+                let n = count as f64;
+                let all = Expected {
+                    success: one.success * n,
+                    failure: one.failure * n,
+                    advantage: one.advantage * n,
+                    threat: one.threat * n,
+                    triumph: one.triumph * n,
+                    despair: one.despair * n,
+                };
+                total.success += all.success;
+                total.failure += all.failure;
+                total.advantage += all.advantage;
+                total.threat += all.threat;
+                total.triumph += all.triumph;
+                total.despair += all.despair;
+                all
+            })
+            .collect();
+        (per_die, total)
+    }
+}
+
+/// The exact chance that a pool passes, with more successes than failures.
+pub fn chance_to_pass(pool: &[(Die, u32)]) -> f64 {
+    // The chance of each net success count so far, one die at a time.
+    let mut net = BTreeMap::from([(0, 1.0)]);
+    for &(die, count) in pool {
+        let faces = die.faces();
+        for _ in 0..count {
+            let mut next = BTreeMap::new();
+            for (&so_far, &p) in &net {
+                for face in faces {
+                    *next
+                        .entry(so_far + face.success - face.failure)
+                        .or_insert(0.0) += p / faces.len() as f64;
+                }
+            }
+            net = next;
+        }
+    }
+    net.range(1..).map(|(_, p)| p).sum()
+}
+
+/// A roll of narrative dice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    pub dice: Vec<(Die, Symbols)>,
+}
+
+pub fn roll_pool(pool: &[(Die, u32)]) -> Roll {
+    let dice = pool
+        .iter()
+        .flat_map(|&(die, count)| (0..count).map(move |_| (die, die.roll())))
+        .collect();
+    Roll { dice }
+}
+
+impl Roll {
+    pub fn symbols(&self) -> Symbols {
+        let mut total = Symbols::default();
+        for (_, face) in &self.dice {
+            total += *face;
+        }
+        total
+    }
+
+    /// Successes minus failures; the check passes when this is positive.
+    pub fn net_successes(&self) -> i32 {
+        let symbols = self.symbols();
+        symbols.success - symbols.failure
+    }
+
+    /// Advantages minus threats.
+    pub fn net_advantage(&self) -> i32 {
+        let symbols = self.symbols();
+        symbols.advantage - symbols.threat
+    }
+}
+
+fn plural(count: i32, one: &str, many: &str) -> String {
+    format!("{} {}", count, if count == 1 { one } else { many })
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (successes, advantage) = (self.net_successes(), self.net_advantage());
+        let mut parts = Vec::new();
+        if successes > 0 {
+            parts.push(format!(
+                "success ({})",
+                plural(successes, "success", "successes")
+            ));
+        } else {
+            parts.push(format!(
+                "failure ({})",
+                plural(-successes, "failure", "failures")
+            ));
+        }
+        if advantage > 0 {
+            parts.push(plural(advantage, "advantage", "advantages"));
+        } else if advantage < 0 {
+            parts.push(plural(-advantage, "threat", "threats"));
+        }
+        let symbols = self.symbols();
+        if symbols.triumph > 0 {
+            parts.push(plural(symbols.triumph, "triumph", "triumphs"));
+        }
+        if symbols.despair > 0 {
+            parts.push(plural(symbols.despair, "despair", "despairs"));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::{AA, DESPAIR, FT, S, SS, T, TRIUMPH};
+
+    #[test]
+    fn cancels_opposing_symbols() {
+        let roll = Roll {
+            dice: vec![
+                (Die::Ability, SS),
+                (Die::Proficiency, TRIUMPH),
+                (Die::Difficulty, FT),
+                (Die::Boost, AA),
+            ],
+        };
+        assert_eq!(roll.net_successes(), 2);
+        assert_eq!(roll.net_advantage(), 1);
+        assert_eq!(
+            roll.to_string(),
+            "success (2 successes), 1 advantage, 1 triumph"
+        );
+
+        let roll = Roll {
+            dice: vec![
+                (Die::Ability, S),
+                (Die::Challenge, DESPAIR),
+                (Die::Setback, T),
+            ],
+        };
+        assert_eq!(
+            roll.to_string(),
+            "failure (0 failures), 1 threat, 1 despair"
+        );
+    }
+
+    #[test]
+    fn expected_symbols_and_chance_to_pass() {
+        let ability = Die::Ability.expected();
+        assert_eq!((ability.success, ability.advantage), (0.625, 0.625));
+        assert!((Die::Proficiency.expected().triumph - 1.0 / 12.0).abs() < 1e-12);
+
+        let pool = [(Die::Ability, 2), (Die::Difficulty, 1)];
+        let (per_die, total) = Expected::of_pool(&pool);
+        assert_eq!(per_die[0].success, 1.25);
+        assert_eq!(total.failure, 0.5);
+
+        // One ability die alone passes on any face with a success.
+        assert_eq!(chance_to_pass(&[(Die::Ability, 1)]), 0.5);
+        assert_eq!(chance_to_pass(&[(Die::Setback, 3)]), 0.0);
+    }
+
+    #[test]
+    fn dice_have_the_right_number_of_faces() {
+        let sides: Vec<usize> = Die::ALL.iter().map(|d| d.faces().len()).collect();
+        assert_eq!(sides, [8, 12, 6, 8, 12, 6]);
+        assert_eq!(Die::from_letter('p'), Some(Die::Proficiency));
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// A GURPS success roll: 3d6 against an effective skill.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    pub dice: [i32; 3],
+    pub skill: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    CriticalSuccess,
+    Success,
+    Failure,
+    CriticalFailure,
+}
+
+pub fn roll_against(skill: i32) -> Roll {
+    Roll {
+        dice: [roll(6), roll(6), roll(6)],
+        skill,
+    }
+}
+
+impl Roll {
+    pub fn total(&self) -> i32 {
+        self.dice.iter().sum()
+    }
+
+    /// How far the roll was under the skill; negative on a failure.
+    pub fn margin(&self) -> i32 {
+        self.skill - self.total()
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        let (total, skill) = (self.total(), self.skill);
+        let critical_success =
+            total <= 4 || (total == 5 && skill >= 15) || (total == 6 && skill >= 16);
+        let critical_failure = total == 18 || (total == 17 && skill <= 15) || total >= skill + 10;
+        if critical_success {
+            Outcome::CriticalSuccess
+        } else if critical_failure {
+            Outcome::CriticalFailure
+        } else if total <= skill.min(16) {
+            Outcome::Success
+        } else {
+            Outcome::Failure
+        }
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let margin = self.margin();
+        write!(f, "{}: ", self.total())?;
+        match self.outcome() {
+            Outcome::CriticalSuccess => write!(f, "critical success (margin {})", margin),
+            Outcome::Success => write!(f, "success by {}", margin),
+            Outcome::Failure => write!(f, "failure by {}", -margin),
+            Outcome::CriticalFailure => write!(f, "critical failure (margin {})", margin),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(total: i32, skill: i32) -> Outcome {
+        // Any three dice with the right total will do.
+        let first = (total - 2).min(6);
+        let second = (total - first - 1).min(6);
+        let roll = Roll {
+            dice: [first, second, total - first - second],
+            skill,
+        };
+        assert_eq!(roll.total(), total);
+        roll.outcome()
+    }
+
+    #[test]
+    fn classifies_by_gurps_rules() {
+        assert_eq!(outcome(4, 3), Outcome::CriticalSuccess);
+        assert_eq!(outcome(5, 14), Outcome::Success);
+        assert_eq!(outcome(5, 15), Outcome::CriticalSuccess);
+        assert_eq!(outcome(6, 16), Outcome::CriticalSuccess);
+        assert_eq!(outcome(14, 14), Outcome::Success);
+        assert_eq!(outcome(15, 14), Outcome::Failure);
+        assert_eq!(outcome(17, 15), Outcome::CriticalFailure);
+        assert_eq!(outcome(17, 18), Outcome::Failure);
+        assert_eq!(outcome(16, 20), Outcome::Success);
+        assert_eq!(outcome(14, 4), Outcome::CriticalFailure);
+    }
+
+    #[test]
+    fn reports_margin() {
+        let roll = Roll {
+            dice: [3, 3, 3],
+            skill: 14,
+        };
+        assert_eq!(roll.to_string(), "9: success by 5");
+        let roll = Roll {
+            dice: [6, 5, 5],
+            skill: 12,
+        };
+        assert_eq!(roll.to_string(), "16: failure by 4");
+    }
+}
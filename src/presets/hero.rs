@@ -0,0 +1,56 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// Hero System normal damage: one pool of d6s read two ways.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Damage {
+    pub dice: Vec<i32>,
+}
+
+pub fn roll_damage(dice: u32) -> Damage {
+    Damage {
+        dice: (0..dice).map(|_| roll(6)).collect(),
+    }
+}
+
+/// BODY for one die: 1 is none, 6 is two, anything else is one.
+fn body(die: i32) -> i32 {
+    match die {
+        1 => 0,
+        6 => 2,
+        _ => 1,
+    }
+}
+
+impl Damage {
+    /// STUN is the sum of the dice.
+    pub fn stun(&self) -> i32 {
+        self.dice.iter().sum()
+    }
+
+    pub fn body(&self) -> i32 {
+        self.dice.iter().map(|&d| body(d)).sum()
+    }
+}
+
+impl fmt::Display for Damage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "STUN {}, BODY {}", self.stun(), self.body())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_stun_and_body_from_one_roll() {
+        let damage = Damage {
+            dice: vec![1, 6, 3, 5, 6, 2],
+        };
+        assert_eq!(damage.stun(), 23);
+        assert_eq!(damage.body(), 7);
+        assert_eq!(damage.to_string(), "STUN 23, BODY 7");
+    }
+}
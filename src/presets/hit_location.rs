@@ -0,0 +1,29 @@
+use crate::table::Table;
+
+/// The default humanoid hit-location table, rolled on a d20.
+pub const HUMANOID: &str = "\
+1-4: right leg
+5-8: left leg
+9-11: abdomen
+12: chest
+13-15: right arm
+16-18: left arm
+19-20: head
+";
+
+pub fn humanoid() -> Table {
+    Table::parse(HUMANOID, false).expect("the built-in table is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn humanoid_table_covers_a_d20() {
+        let table = humanoid();
+        assert_eq!(table.sides(), 20);
+        assert_eq!(table.lookup(12), Some("chest"));
+        assert_eq!(table.lookup(20), Some("head"));
+    }
+}
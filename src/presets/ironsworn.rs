@@ -0,0 +1,102 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// The action score can't go above this, whatever the stat and adds.
+pub const MAX_ACTION_SCORE: i32 = 10;
+
+/// An Ironsworn action roll: d6 plus a stat against two d10 challenge dice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    pub action_die: i32,
+    pub stat: i32,
+    pub challenge: [i32; 2],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Miss,
+    WeakHit,
+    StrongHit,
+}
+
+pub fn roll_action(stat: i32) -> Roll {
+    Roll {
+        action_die: roll(6),
+        stat,
+        challenge: [roll(10), roll(10)],
+    }
+}
+
+impl Roll {
+    pub fn action_score(&self) -> i32 {
+        self.action_die
+            .saturating_add(self.stat)
+            .min(MAX_ACTION_SCORE)
+    }
+
+    /// How many challenge dice the action score beats (ties go to the
+    /// challenge).
+    pub fn outcome(&self) -> Outcome {
+        let score = self.action_score();
+        match self.challenge.iter().filter(|&&c| score > c).count() {
+            2 => Outcome::StrongHit,
+            1 => Outcome::WeakHit,
+            _ => Outcome::Miss,
+        }
+    }
+
+    /// Both challenge dice show the same number.
+    pub fn is_match(&self) -> bool {
+        self.challenge[0] == self.challenge[1]
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = match self.outcome() {
+            Outcome::Miss => "miss",
+            Outcome::WeakHit => "weak hit",
+            Outcome::StrongHit => "strong hit",
+        };
+        write!(
+            f,
+            "{} vs {} and {}: {}",
+            self.action_score(),
+            self.challenge[0],
+            self.challenge[1],
+            outcome
+        )?;
+        if self.is_match() {
+            write!(f, " (match)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roll(action_die: i32, stat: i32, challenge: [i32; 2]) -> Roll {
+        Roll {
+            action_die,
+            stat,
+            challenge,
+        }
+    }
+
+    #[test]
+    fn compares_against_both_challenge_dice() {
+        assert_eq!(roll(4, 2, [3, 5]).to_string(), "6 vs 3 and 5: strong hit");
+        assert_eq!(roll(4, 2, [6, 2]).outcome(), Outcome::WeakHit);
+        assert_eq!(roll(1, 1, [8, 8]).to_string(), "2 vs 8 and 8: miss (match)");
+    }
+
+    #[test]
+    fn action_score_is_capped() {
+        assert_eq!(roll(6, 5, [10, 9]).action_score(), 10);
+        assert_eq!(roll(6, 5, [10, 9]).outcome(), Outcome::WeakHit);
+        assert_eq!(roll(6, i32::MAX, [1, 1]).action_score(), 10);
+    }
+}
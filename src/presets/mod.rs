@@ -0,0 +1,23 @@
+//! Game-specific rolls. Each module rolls one system's dice and interprets
+//! the result using that system's rules and vocabulary.
+
+pub mod blades;
+pub mod burning_wheel;
+pub mod cortex;
+pub mod cthulhu;
+pub mod cyberpunk;
+pub mod dcc;
+pub mod dnd;
+pub mod fate;
+pub mod genesys;
+pub mod gurps;
+pub mod hero;
+pub mod hit_location;
+pub mod ironsworn;
+pub mod pbta;
+pub mod risk;
+pub mod savage_worlds;
+pub mod scatter;
+pub mod shadowrun;
+pub mod wfrp;
+pub mod year_zero;
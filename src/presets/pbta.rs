@@ -0,0 +1,76 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// A Powered by the Apocalypse move: 2d6 plus a stat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    pub dice: [i32; 2],
+    pub stat: i32,
+    /// Whether 12+ counts as its own band, for advanced moves.
+    pub advanced: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Band {
+    Miss,
+    WeakHit,
+    StrongHit,
+    Critical,
+}
+
+pub fn roll_move(stat: i32, advanced: bool) -> Roll {
+    Roll {
+        dice: [roll(6), roll(6)],
+        stat,
+        advanced,
+    }
+}
+
+impl Roll {
+    pub fn total(&self) -> i32 {
+        (self.dice[0] + self.dice[1]).saturating_add(self.stat)
+    }
+
+    pub fn band(&self) -> Band {
+        match self.total() {
+            ..=6 => Band::Miss,
+            7..=9 => Band::WeakHit,
+            12.. if self.advanced => Band::Critical,
+            _ => Band::StrongHit,
+        }
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let band = match self.band() {
+            Band::Miss => "miss",
+            Band::WeakHit => "weak hit",
+            Band::StrongHit => "strong hit",
+            Band::Critical => "critical",
+        };
+        write!(f, "{}: {}", self.total(), band)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands() {
+        let roll = |dice, stat, advanced| Roll {
+            dice,
+            stat,
+            advanced,
+        };
+        assert_eq!(roll([3, 2], 1, false).to_string(), "6: miss");
+        assert_eq!(roll([4, 3], 0, false).band(), Band::WeakHit);
+        assert_eq!(roll([4, 4], 2, false).to_string(), "10: strong hit");
+        assert_eq!(roll([6, 5], 2, false).band(), Band::StrongHit);
+        assert_eq!(roll([6, 5], 2, true).to_string(), "13: critical");
+        assert_eq!(roll([2, 1], -1, false).total(), 2);
+        assert_eq!(roll([6, 6], i32::MAX, false).band(), Band::StrongHit);
+    }
+}
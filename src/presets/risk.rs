@@ -0,0 +1,76 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// A Risk battle: each side's dice are sorted and paired highest against
+/// highest, and each pair costs the loser one unit. Defenders win ties.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Battle {
+    /// Sorted from highest to lowest.
+    pub attacker: Vec<i32>,
+    /// Sorted from highest to lowest.
+    pub defender: Vec<i32>,
+}
+
+fn sorted_roll(count: u32, sides: i32) -> Vec<i32> {
+    let mut dice: Vec<i32> = (0..count).map(|_| roll(sides)).collect();
+    dice.sort_unstable_by(|a, b| b.cmp(a));
+    dice
+}
+
+pub fn roll_battle(attacker: (u32, i32), defender: (u32, i32)) -> Battle {
+    Battle {
+        attacker: sorted_roll(attacker.0, attacker.1),
+        defender: sorted_roll(defender.0, defender.1),
+    }
+}
+
+impl Battle {
+    /// Units lost by the attacker and by the defender.
+    pub fn casualties(&self) -> (u32, u32) {
+        let mut losses = (0, 0);
+        for (a, d) in self.attacker.iter().zip(&self.defender) {
+            if a > d {
+                losses.1 += 1;
+            } else {
+                losses.0 += 1;
+            }
+        }
+        losses
+    }
+}
+
+impl fmt::Display for Battle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (attacker, defender) = self.casualties();
+        write!(
+            f,
+            "attacker loses {}, defender loses {}",
+            attacker, defender
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_highest_dice_and_defender_wins_ties() {
+        let battle = Battle {
+            attacker: vec![6, 4, 1],
+            defender: vec![5, 4],
+        };
+        assert_eq!(battle.casualties(), (1, 1));
+        assert_eq!(battle.to_string(), "attacker loses 1, defender loses 1");
+    }
+
+    #[test]
+    fn only_pairs_as_many_dice_as_the_smaller_side() {
+        fastrand::seed(9);
+        let battle = roll_battle((3, 6), (1, 6));
+        let (a, d) = battle.casualties();
+        assert_eq!(a + d, 1);
+        assert!(battle.attacker.windows(2).all(|w| w[0] >= w[1]));
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// The wild die every Wild Card rolls alongside their trait die.
+pub const WILD_DIE: i32 = 6;
+pub const DEFAULT_TARGET: i32 = 4;
+/// Each full step above the target number is a raise.
+pub const RAISE_STEP: i32 = 4;
+
+/// A Savage Worlds trait roll: the trait die and the wild die, both acing,
+/// with the higher total counting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    pub trait_dice: Vec<i32>,
+    pub wild_dice: Vec<i32>,
+    pub target: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// Both dice came up 1 (snake eyes).
+    CriticalFailure,
+    Failure,
+    Success {
+        raises: i32,
+    },
+}
+
+/// Rolls one die, rolling again and adding whenever it shows its maximum.
+pub fn ace(sides: i32) -> Vec<i32> {
+    let mut dice = vec![roll(sides)];
+    while sides > 1 && *dice.last().unwrap() == sides {
+        dice.push(roll(sides));
+    }
+    dice
+}
+
+pub fn roll_trait(sides: i32, target: i32) -> Roll {
+    Roll {
+        trait_dice: ace(sides),
+        wild_dice: ace(WILD_DIE),
+        target,
+    }
+}
+
+impl Roll {
+    pub fn trait_total(&self) -> i32 {
+        self.trait_dice.iter().sum()
+    }
+
+    pub fn wild_total(&self) -> i32 {
+        self.wild_dice.iter().sum()
+    }
+
+    pub fn total(&self) -> i32 {
+        self.trait_total().max(self.wild_total())
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        let total = self.total();
+        if self.trait_dice[0] == 1 && self.wild_dice[0] == 1 {
+            Outcome::CriticalFailure
+        } else if total < self.target {
+            Outcome::Failure
+        } else {
+            Outcome::Success {
+                raises: (total - self.target) / RAISE_STEP,
+            }
+        }
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.total())?;
+        match self.outcome() {
+            Outcome::CriticalFailure => write!(f, "critical failure"),
+            Outcome::Failure => write!(f, "failure"),
+            Outcome::Success { raises: 0 } => write!(f, "success"),
+            Outcome::Success { raises: 1 } => write!(f, "success with 1 raise"),
+            Outcome::Success { raises } => write!(f, "success with {} raises", raises),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roll_of(trait_dice: Vec<i32>, wild_dice: Vec<i32>) -> Roll {
+        Roll {
+            trait_dice,
+            wild_dice,
+            target: DEFAULT_TARGET,
+        }
+    }
+
+    #[test]
+    fn takes_the_higher_die_and_counts_raises() {
+        let roll = roll_of(vec![8, 5], vec![3]);
+        assert_eq!(roll.total(), 13);
+        assert_eq!(roll.outcome(), Outcome::Success { raises: 2 });
+        assert_eq!(roll.to_string(), "13: success with 2 raises");
+
+        assert_eq!(roll_of(vec![2], vec![6, 1]).to_string(), "7: success");
+        assert_eq!(roll_of(vec![3], vec![2]).to_string(), "3: failure");
+        assert_eq!(
+            roll_of(vec![1], vec![1]).outcome(),
+            Outcome::CriticalFailure
+        );
+    }
+
+    #[test]
+    fn aces_reroll_the_maximum() {
+        fastrand::seed(1);
+        for _ in 0..100 {
+            let dice = ace(4);
+            let (last, aced) = dice.split_last().unwrap();
+            assert!(*last < 4);
+            assert!(aced.iter().all(|&d| d == 4));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+
+use crate::ast::Ast;
+use crate::eval::{eval_total, roll};
+
+/// The eight faces of a scatter die, clockwise from north.
+pub const DIRECTIONS: [(char, &str); 8] = [
+    ('↑', "north"),
+    ('↗', "northeast"),
+    ('→', "east"),
+    ('↘', "southeast"),
+    ('↓', "south"),
+    ('↙', "southwest"),
+    ('←', "west"),
+    ('↖', "northwest"),
+];
+
+/// A wargame scatter: a d8 direction and a distance in inches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scatter {
+    /// The d8 face, 1 for north.
+    pub direction: i32,
+    pub distance: i32,
+}
+
+/// Rolls the direction, and the distance from the root of `distance`.
+pub fn roll_scatter(distance: &Ast) -> Scatter {
+    Scatter {
+        direction: roll(8),
+        distance: eval_total(distance, distance.roots()[0]),
+    }
+}
+
+impl Scatter {
+    pub fn arrow(&self) -> char {
+        DIRECTIONS[(self.direction - 1) as usize].0
+    }
+
+    pub fn name(&self) -> &'static str {
+        DIRECTIONS[(self.direction - 1) as usize].1
+    }
+}
+
+impl fmt::Display for Scatter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}\"", self.arrow(), self.name(), self.distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn renders_direction_and_distance() {
+        let scatter = Scatter {
+            direction: 2,
+            distance: 7,
+        };
+        assert_eq!(scatter.to_string(), "↗ northeast 7\"");
+
+        let distance = parse("2d6").unwrap();
+        for _ in 0..20 {
+            let scatter = roll_scatter(&distance);
+            assert!((1..=8).contains(&scatter.direction));
+            assert!((2..=12).contains(&scatter.distance));
+        }
+    }
+}
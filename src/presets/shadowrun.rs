@@ -0,0 +1,89 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// A Shadowrun dice pool roll.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    /// Every die rolled, including extra dice from the rule of six.
+    pub dice: Vec<i32>,
+    pub hits: u32,
+    pub glitch: Glitch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Glitch {
+    None,
+    Glitch,
+    /// A glitch with no hits.
+    Critical,
+}
+
+/// Rolls `pool` d6. With `edge`, every 6 adds another die (the rule of six).
+pub fn roll_pool(pool: u32, edge: bool) -> Roll {
+    let mut dice = Vec::with_capacity(pool as usize);
+    let mut remaining = pool;
+    while remaining > 0 {
+        remaining -= 1;
+        let die = roll(6);
+        if edge && die == 6 {
+            remaining += 1;
+        }
+        dice.push(die);
+    }
+    assess(pool, dice)
+}
+
+/// Counts hits (5 or 6) and checks for glitches: half or more of the
+/// original `pool` showing 1.
+pub fn assess(pool: u32, dice: Vec<i32>) -> Roll {
+    let hits = dice.iter().filter(|&&d| d >= 5).count() as u32;
+    let ones = dice.iter().filter(|&&d| d == 1).count() as u32;
+    let glitch = match (pool > 0 && ones * 2 >= pool, hits) {
+        (false, _) => Glitch::None,
+        (true, 0) => Glitch::Critical,
+        (true, _) => Glitch::Glitch,
+    };
+    Roll { dice, hits, glitch }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let noun = if self.hits == 1 { "hit" } else { "hits" };
+        write!(f, "{} {}", self.hits, noun)?;
+        match self.glitch {
+            Glitch::None => Ok(()),
+            Glitch::Glitch => write!(f, ", glitch"),
+            Glitch::Critical => write!(f, ", critical glitch"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_hits_and_glitches() {
+        let roll = assess(6, vec![6, 5, 1, 1, 1, 2]);
+        assert_eq!(roll.hits, 2);
+        assert_eq!(roll.glitch, Glitch::Glitch);
+        assert_eq!(roll.to_string(), "2 hits, glitch");
+
+        let roll = assess(4, vec![1, 1, 3, 4]);
+        assert_eq!(roll.glitch, Glitch::Critical);
+        assert_eq!(roll.to_string(), "0 hits, critical glitch");
+
+        assert_eq!(assess(3, vec![5, 1, 2]).to_string(), "1 hit");
+    }
+
+    #[test]
+    fn rule_of_six_adds_dice() {
+        fastrand::seed(3);
+        for _ in 0..100 {
+            let roll = roll_pool(8, true);
+            let sixes = roll.dice.iter().filter(|&&d| d == 6).count();
+            assert_eq!(roll.dice.len(), 8 + sixes);
+        }
+    }
+}
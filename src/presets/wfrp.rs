@@ -0,0 +1,118 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// A roll-under d100 test scored in success levels, as in Warhammer
+/// Fantasy Roleplay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    /// The d100 as rolled, 1–100.
+    pub rolled: i32,
+    /// The roll after any digit reversal; this is what is scored.
+    pub result: i32,
+    pub target: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    CriticalSuccess,
+    Success,
+    Failure,
+    Fumble,
+}
+
+/// Rolls d100 against `target`. With `reverse`, the digits may be swapped
+/// (37 read as 73) when that scores better.
+pub fn roll_against(target: i32, reverse: bool) -> Roll {
+    let rolled = roll(100);
+    let result = if reverse {
+        rolled.min(reversed(rolled))
+    } else {
+        rolled
+    };
+    Roll {
+        rolled,
+        result,
+        target,
+    }
+}
+
+/// Swaps the tens and units digits; 100 reads as 00 and stays 100.
+fn reversed(roll: i32) -> i32 {
+    match roll {
+        100 => 100,
+        n => match (n % 10) * 10 + n / 10 {
+            0 => 100,
+            n => n,
+        },
+    }
+}
+
+impl Roll {
+    /// Success levels: the target's tens digit minus the roll's.
+    pub fn success_levels(&self) -> i32 {
+        self.target / 10 - self.result / 10
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        // 01–05 always succeed and 96–00 always fail.
+        let success = self.result <= 5 || (self.result <= self.target && self.result < 96);
+        let double = self.result % 11 == 0 || self.result == 100;
+        match (success, double) {
+            (true, true) => Outcome::CriticalSuccess,
+            (true, false) => Outcome::Success,
+            (false, false) => Outcome::Failure,
+            (false, true) => Outcome::Fumble,
+        }
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = match self.outcome() {
+            Outcome::CriticalSuccess => "critical success",
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+            Outcome::Fumble => "fumble",
+        };
+        write!(
+            f,
+            "{}: {}, SL {:+}",
+            self.result,
+            outcome,
+            self.success_levels()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scored(result: i32, target: i32) -> Roll {
+        Roll {
+            rolled: result,
+            result,
+            target,
+        }
+    }
+
+    #[test]
+    fn scores_success_levels_and_doubles() {
+        assert_eq!(scored(23, 45).to_string(), "23: success, SL +2");
+        assert_eq!(scored(33, 45).to_string(), "33: critical success, SL +1");
+        assert_eq!(scored(67, 45).to_string(), "67: failure, SL -2");
+        assert_eq!(scored(88, 45).to_string(), "88: fumble, SL -4");
+        assert_eq!(scored(100, 99).outcome(), Outcome::Fumble);
+        assert_eq!(scored(4, 2).outcome(), Outcome::Success);
+        assert_eq!(scored(97, 99).outcome(), Outcome::Failure);
+    }
+
+    #[test]
+    fn reverses_digits() {
+        assert_eq!(reversed(73), 37);
+        assert_eq!(reversed(40), 4);
+        assert_eq!(reversed(10), 1);
+        assert_eq!(reversed(100), 100);
+    }
+}
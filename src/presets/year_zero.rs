@@ -0,0 +1,160 @@
+use std::fmt;
+
+use crate::eval::roll;
+
+/// A Year Zero Engine roll, with base, skill and gear dice kept apart since
+/// 1s mean different things on each.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Pool {
+    pub base: Vec<i32>,
+    pub skill: Vec<i32>,
+    pub gear: Vec<i32>,
+}
+
+fn d6s(count: u32) -> Vec<i32> {
+    (0..count).map(|_| roll(6)).collect()
+}
+
+fn count(dice: &[i32], face: i32) -> u32 {
+    dice.iter().filter(|&&d| d == face).count() as u32
+}
+
+impl Pool {
+    pub fn roll(base: u32, skill: u32, gear: u32) -> Pool {
+        Pool {
+            base: d6s(base),
+            skill: d6s(skill),
+            gear: d6s(gear),
+        }
+    }
+
+    /// Every 6 is a success, whatever its color.
+    pub fn successes(&self) -> u32 {
+        count(&self.base, 6) + count(&self.skill, 6) + count(&self.gear, 6)
+    }
+
+    /// 1s on base dice, which damage the attribute when pushed.
+    pub fn base_banes(&self) -> u32 {
+        count(&self.base, 1)
+    }
+
+    /// 1s on gear dice, which degrade the gear when pushed.
+    pub fn gear_banes(&self) -> u32 {
+        count(&self.gear, 1)
+    }
+
+    /// Pushes the roll: rerolls every die not showing a 6 or a 1.
+    pub fn push(&self) -> Pool {
+        let reroll = |dice: &[i32]| {
+            dice.iter()
+                .map(|&d| if d == 6 || d == 1 { d } else { roll(6) })
+                .collect()
+        };
+        Pool {
+            base: reroll(&self.base),
+            skill: reroll(&self.skill),
+            gear: reroll(&self.gear),
+        }
+    }
+}
+
+/// The chance that one die shows a face passing `test`.
+fn chance_of(test: impl Fn(i32) -> bool) -> f64 {
+    (1..=6).filter(|&face| test(face)).count() as f64 / 6.0
+}
+
+/// The chance that `dice` dice, of any color, show at least one success.
+pub fn chance_of_success(dice: u32) -> f64 {
+    1.0 - (1.0 - chance_of(|face| face == 6)).powi(dice as i32)
+}
+
+/// Average successes and banes over many rolls, for balancing a pool.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Expected {
+    pub successes: f64,
+    pub banes: f64,
+}
+
+/// The averages of a pool of `base`, `skill` and `gear` dice, per color in
+/// that order and in total. Every 6 is a success; only base and gear dice
+/// bane on a 1.
+pub fn expected(base: u32, skill: u32, gear: u32) -> ([Expected; 3], Expected) {
+    let (success, bane) = (chance_of(|face| face == 6), chance_of(|face| face == 1));
+    let per_color = [(base, true), (skill, false), (gear, true)].map(|(count, banes)| {
+        let n = count as f64;
+        Expected {
+            successes: n * success,
+            banes: if banes { n * bane } else { 0.0 },
+        }
+    });
+    let total = Expected {
+        successes: per_color.iter().map(|e| e.successes).sum(),
+        banes: per_color.iter().map(|e| e.banes).sum(),
+    };
+    (per_color, total)
+}
+
+impl fmt::Display for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.successes() {
+            1 => write!(f, "1 success")?,
+            n => write!(f, "{} successes", n)?,
+        }
+        let (base, gear) = (self.base_banes(), self.gear_banes());
+        match base + gear {
+            0 => Ok(()),
+            1 => write!(f, ", 1 bane (base {}, gear {})", base, gear),
+            n => write!(f, ", {} banes (base {}, gear {})", n, base, gear),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_successes_and_banes_by_color() {
+        let pool = Pool {
+            base: vec![6, 1, 3],
+            skill: vec![1, 6],
+            gear: vec![1],
+        };
+        assert_eq!(pool.successes(), 2);
+        assert_eq!((pool.base_banes(), pool.gear_banes()), (1, 1));
+        assert_eq!(pool.to_string(), "2 successes, 2 banes (base 1, gear 1)");
+    }
+
+    #[test]
+    fn chance_of_any_success() {
+        assert_eq!(chance_of_success(0), 0.0);
+        assert!((chance_of_success(1) - 1.0 / 6.0).abs() < 1e-12);
+        assert!((chance_of_success(2) - 11.0 / 36.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn expected_successes_and_banes() {
+        let (per_color, total) = expected(6, 3, 0);
+        assert!((per_color[0].successes - 1.0).abs() < 1e-12);
+        assert!((per_color[0].banes - 1.0).abs() < 1e-12);
+        assert!((per_color[1].successes - 0.5).abs() < 1e-12);
+        assert_eq!(per_color[1].banes, 0.0);
+        assert_eq!(per_color[2], Expected::default());
+        assert!((total.successes - 1.5).abs() < 1e-12);
+        assert!((total.banes - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn push_keeps_sixes_and_ones() {
+        fastrand::seed(2);
+        let pool = Pool {
+            base: vec![6, 1, 3, 4],
+            skill: vec![2],
+            gear: vec![1, 5],
+        };
+        let pushed = pool.push();
+        assert_eq!(&pushed.base[..2], &[6, 1]);
+        assert_eq!(pushed.gear[0], 1);
+        assert_eq!(pushed.skill.len(), 1);
+    }
+}
@@ -0,0 +1,161 @@
+#[cfg(feature = "parallel")]
+use crate::eval::{collecting, pairing};
+
+/// Derives the seed of an independent random stream from a base seed, so
+/// that work split into streams gives the same results however it is
+/// scheduled. This is the SplitMix64 output function.
+pub fn derive_seed(base: u64, stream: u64) -> u64 {
+    let mut z = base ^ stream.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// A stream number for a label (64-bit FNV-1a), stable across runs and
+/// platforms, so a labeled roll keeps its stream wherever it appears.
+pub fn label_stream(label: &str) -> u64 {
+    label.bytes().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+/// The number of worker threads to use when none is requested: the
+/// available parallelism, or one if that cannot be determined.
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Caps the threads used by [`map_streams`] and [`sum_streams`]. Only the
+/// first call has any effect, and without the `parallel` feature it does
+/// nothing.
+pub fn set_threads(threads: usize) {
+    #[cfg(feature = "parallel")]
+    let _ = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global();
+    #[cfg(not(feature = "parallel"))]
+    let _ = threads;
+}
+
+/// Below this many streams the parallel paths run sequentially, since the
+/// scheduling overhead would outweigh the work.
+#[cfg(feature = "parallel")]
+const PARALLEL_THRESHOLD: usize = 64;
+
+/// Runs `f` for each index in `0..count`, seeding the thread-local RNG from
+/// a stream of its own before each call. With the `parallel` feature the
+/// calls are spread across threads; the results are identical either way.
+/// While an antithetic pair is rolling (see [`Antithetic`]) they stay on
+/// this thread, in order, so every die is mirrored, and likewise while
+/// [`kept_dice`] is collecting.
+///
+/// [`Antithetic`]: crate::eval::Antithetic
+/// [`kept_dice`]: crate::eval::kept_dice
+pub fn map_streams<T, F>(count: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync + Send,
+{
+    let base = fastrand::u64(..);
+    let run = |i: usize| {
+        fastrand::seed(derive_seed(base, i as u64));
+        f(i)
+    };
+
+    #[cfg(feature = "parallel")]
+    let results = if count >= PARALLEL_THRESHOLD && !pairing() && !collecting() {
+        use rayon::prelude::*;
+        (0..count).into_par_iter().map(run).collect()
+    } else {
+        (0..count).map(run).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let results = (0..count).map(run).collect();
+
+    // Other streams may have run on this thread, so continue from a state
+    // that does not depend on scheduling.
+    fastrand::seed(derive_seed(base, count as u64));
+    results
+}
+
+/// Like [`map_streams`], but always on this thread and in order, handing
+/// each result to `f` as soon as it is ready. The streams, and so the
+/// results, are the same as [`map_streams`] gives.
+pub fn for_each_stream<F>(count: usize, mut f: F)
+where
+    F: FnMut(usize),
+{
+    let base = fastrand::u64(..);
+    for i in 0..count {
+        fastrand::seed(derive_seed(base, i as u64));
+        f(i);
+    }
+    fastrand::seed(derive_seed(base, count as u64));
+}
+
+/// Like [`map_streams`], but sums the results without collecting them.
+pub fn sum_streams<F>(count: usize, f: F) -> i32
+where
+    F: Fn() -> i32 + Sync + Send,
+{
+    let base = fastrand::u64(..);
+    let run = |i: usize| {
+        fastrand::seed(derive_seed(base, i as u64));
+        f()
+    };
+
+    #[cfg(feature = "parallel")]
+    let total = if count >= PARALLEL_THRESHOLD && !pairing() && !collecting() {
+        use rayon::prelude::*;
+        (0..count).into_par_iter().map(run).sum()
+    } else {
+        (0..count).map(run).sum()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let total = (0..count).map(run).sum();
+
+    fastrand::seed(derive_seed(base, count as u64));
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derived_seeds_differ_per_stream() {
+        assert_ne!(derive_seed(1, 0), derive_seed(1, 1));
+        assert_ne!(derive_seed(1, 0), derive_seed(2, 0));
+        assert_eq!(derive_seed(1, 5), derive_seed(1, 5));
+    }
+
+    #[test]
+    fn label_streams_are_stable() {
+        assert_eq!(label_stream(""), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(label_stream("a"), 0xAF63_DC4C_8601_EC8C);
+        assert_ne!(label_stream("attack"), label_stream("damage"));
+    }
+
+    #[test]
+    fn streams_are_reproducible() {
+        let roll = |_| fastrand::i32(1..=1000);
+
+        fastrand::seed(42);
+        let first = map_streams(500, roll);
+        let after_first = fastrand::u64(..);
+
+        fastrand::seed(42);
+        let second = map_streams(500, roll);
+        assert_eq!(first, second);
+        assert_eq!(after_first, fastrand::u64(..));
+
+        fastrand::seed(42);
+        assert_eq!(first.iter().sum::<i32>(), sum_streams(500, || roll(0)));
+
+        fastrand::seed(42);
+        let mut streamed = Vec::new();
+        for_each_stream(500, |i| streamed.push(roll(i)));
+        assert_eq!(streamed, first);
+        assert_eq!(after_first, fastrand::u64(..));
+    }
+}
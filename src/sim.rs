@@ -0,0 +1,438 @@
+use std::ops::ControlFlow;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+
+use crate::ast::{Ast, ExprId, ExprKind, Function};
+use crate::compile::Program;
+use crate::eval::Antithetic;
+use crate::rng::derive_seed;
+
+/// How [`simulate_with`] draws its samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Method {
+    #[default]
+    Independent,
+    /// In mirrored pairs (see [`Antithetic`]), which settles sums of dice
+    /// and the chances of their tails with fewer samples.
+    Antithetic,
+}
+
+impl Method {
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Independent => "independent",
+            Method::Antithetic => "antithetic",
+        }
+    }
+}
+
+/// How [`simulate_with`] runs.
+#[derive(Debug, Clone, Default)]
+pub struct Sampling {
+    /// Worker threads; zero runs one.
+    pub threads: usize,
+    pub method: Method,
+    /// Also estimate the chance of a total at least this high.
+    pub at_least: Option<i32>,
+}
+
+/// The running mean of independent draws and its standard error.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Estimate {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Estimate {
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn merge(&mut self, other: &Estimate) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * self.count as f64 * other.count as f64 / count as f64;
+        self.count = count;
+    }
+
+    pub fn value(&self) -> f64 {
+        self.mean
+    }
+
+    /// The standard error of [`Estimate::value`]; zero with fewer than two
+    /// draws.
+    pub fn std_error(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            (self.m2 / (self.count - 1) as f64 / self.count as f64).sqrt()
+        }
+    }
+}
+
+/// Running summary statistics over simulated totals.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub count: u64,
+    pub min: i32,
+    pub max: i32,
+    mean: f64,
+    /// Sum of squared differences from the mean (Welford's algorithm).
+    m2: f64,
+    /// The mean again, over independent draws: single totals, or the
+    /// averages of antithetic pairs, whose spread is what limits accuracy.
+    estimate: Estimate,
+    /// The threshold of [`Sampling::at_least`] and how often it was reached.
+    at_least: Option<(i32, Estimate)>,
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Summary::new(None)
+    }
+}
+
+impl Summary {
+    /// An empty summary, counting totals of at least `at_least` if given.
+    pub fn new(at_least: Option<i32>) -> Summary {
+        Summary {
+            count: 0,
+            min: i32::MAX,
+            max: i32::MIN,
+            mean: 0.0,
+            m2: 0.0,
+            estimate: Estimate::default(),
+            at_least: at_least.map(|t| (t, Estimate::default())),
+        }
+    }
+
+    pub fn add(&mut self, value: i32) {
+        self.record(value);
+        self.estimate.add(value as f64);
+        if let Some((threshold, hits)) = &mut self.at_least {
+            hits.add(f64::from(u8::from(value >= *threshold)));
+        }
+    }
+
+    /// Adds the two totals of an antithetic pair, which count as one draw
+    /// towards the standard errors.
+    pub fn add_pair(&mut self, first: i32, second: i32) {
+        self.record(first);
+        self.record(second);
+        self.estimate.add((first as f64 + second as f64) / 2.0);
+        if let Some((threshold, hits)) = &mut self.at_least {
+            let reached = u8::from(first >= *threshold) + u8::from(second >= *threshold);
+            hits.add(f64::from(reached) / 2.0);
+        }
+    }
+
+    fn record(&mut self, value: i32) {
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+
+        let delta = value as f64 - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value as f64 - self.mean);
+    }
+
+    /// Combines two summaries as if all their samples had been added to one.
+    pub fn merge(&mut self, other: &Summary) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * self.count as f64 * other.count as f64 / count as f64;
+        self.count = count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.estimate.merge(&other.estimate);
+        if let (Some((_, hits)), Some((_, other))) = (&mut self.at_least, &other.at_least) {
+            hits.merge(other);
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Sample standard deviation; zero with fewer than two samples.
+    pub fn std_dev(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            (self.m2 / (self.count - 1) as f64).sqrt()
+        }
+    }
+
+    /// The standard error of [`Summary::mean`].
+    pub fn std_error(&self) -> f64 {
+        self.estimate.std_error()
+    }
+
+    /// The threshold of [`Sampling::at_least`] and the estimated chance of
+    /// reaching it.
+    pub fn at_least(&self) -> Option<(i32, Estimate)> {
+        self.at_least
+    }
+}
+
+/// How many samples [`simulate_with`] runs between progress callbacks.
+/// Each chunk draws from its own random stream, so results do not depend on
+/// the number of threads.
+pub const CHUNK_SIZE: u64 = 4096;
+
+/// Runs `program` `samples` times and summarizes the totals.
+pub fn simulate(program: &Program, samples: u64) -> Summary {
+    simulate_with(program, samples, &Sampling::default(), |_| {
+        ControlFlow::Continue(())
+    })
+}
+
+/// Like [`simulate`], but spreads the work across `sampling.threads`
+/// workers, draws as `sampling` says and calls `progress` with the number
+/// of samples done after every [`CHUNK_SIZE`] samples. Breaking stops early
+/// and returns the summary of the chunks finished so far.
+pub fn simulate_with<F>(
+    program: &Program,
+    samples: u64,
+    sampling: &Sampling,
+    progress: F,
+) -> Summary
+where
+    F: FnMut(u64) -> ControlFlow<()> + Send,
+{
+    let chunks = samples.div_ceil(CHUNK_SIZE);
+    let base = fastrand::u64(..);
+    let next_chunk = AtomicU64::new(0);
+    let done = AtomicU64::new(0);
+    let stop = AtomicBool::new(false);
+    let progress = Mutex::new(progress);
+
+    let worker = || {
+        let mut summary = Summary::new(sampling.at_least);
+        let mut stack = Vec::new();
+        let antithetic = (sampling.method == Method::Antithetic).then(Antithetic::start);
+        while !stop.load(Ordering::Relaxed) {
+            let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+            if chunk >= chunks {
+                break;
+            }
+            fastrand::seed(derive_seed(base, chunk));
+            let len = CHUNK_SIZE.min(samples - chunk * CHUNK_SIZE);
+            match &antithetic {
+                Some(antithetic) => {
+                    for _ in 0..len / 2 {
+                        let (first, second) = antithetic.pair(|| program.run(&mut stack));
+                        summary.add_pair(first, second);
+                    }
+                    if len % 2 == 1 {
+                        summary.add(program.run(&mut stack));
+                    }
+                }
+                None => {
+                    for _ in 0..len {
+                        summary.add(program.run(&mut stack));
+                    }
+                }
+            }
+            let done = done.fetch_add(len, Ordering::Relaxed) + len;
+            let mut progress = progress.lock().unwrap();
+            if (*progress)(done).is_break() {
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+        summary
+    };
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..sampling.threads.max(1))
+            .map(|_| scope.spawn(worker))
+            .collect();
+        workers
+            .into_iter()
+            .fold(Summary::new(sampling.at_least), |mut total, handle| {
+                total.merge(&handle.join().unwrap());
+                total
+            })
+    })
+}
+
+/// The exact mean of an expression's total, for expressions built from
+/// unmodified dice, sums and constant multiples. Anything else (modifiers,
+/// division, dice with rolled counts) returns `None`; simulate those.
+pub fn expected_total(ast: &Ast, id: ExprId) -> Option<f64> {
+    let constant = |id: ExprId| match ast[id].kind {
+        ExprKind::Number(n) => Some(n as f64),
+        _ => None,
+    };
+    match &ast[id].kind {
+        ExprKind::Number(n) => Some(*n as f64),
+        ExprKind::Dice {
+            count,
+            sides,
+            modifiers,
+        } if modifiers.is_empty() => Some(constant(*count)? * (constant(*sides)? + 1.0) / 2.0),
+        ExprKind::Fudge { modifiers, .. } if modifiers.is_empty() => Some(0.0),
+        ExprKind::BinaryOp(lhs, op, rhs) => match op {
+            '+' => Some(expected_total(ast, *lhs)? + expected_total(ast, *rhs)?),
+            '-' => Some(expected_total(ast, *lhs)? - expected_total(ast, *rhs)?),
+            '*' => match (constant(*lhs), constant(*rhs)) {
+                (Some(n), _) => Some(n * expected_total(ast, *rhs)?),
+                (_, Some(n)) => Some(expected_total(ast, *lhs)? * n),
+                _ => None,
+            },
+            _ => None,
+        },
+        ExprKind::Repetition {
+            count,
+            expr,
+            modifiers,
+        } if modifiers.is_empty() => Some(constant(*count)? * expected_total(ast, *expr)?),
+        ExprKind::Contest(first, second) => {
+            Some(expected_total(ast, *first)? - expected_total(ast, *second)?)
+        }
+        ExprKind::Call {
+            function: Function::Double,
+            args,
+        } => Some(2.0 * expected_total(ast, args[0])?),
+        ExprKind::Call {
+            function: Function::Between,
+            args,
+        } => Some((constant(args[0])? + constant(args[1])?) / 2.0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn summary_statistics() {
+        let mut summary = Summary::default();
+        for value in [2, 4, 4, 4, 5, 5, 7, 9] {
+            summary.add(value);
+        }
+        assert_eq!(summary.count, 8);
+        assert_eq!((summary.min, summary.max), (2, 9));
+        assert!((summary.mean() - 5.0).abs() < 1e-9);
+        assert!((summary.std_dev() - (32.0f64 / 7.0).sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn merged_summaries_match_a_single_pass() {
+        let values = [3, 1, 4, 1, 5, 9, 2, 6, 5, 3];
+        let mut whole = Summary::default();
+        values.iter().for_each(|&v| whole.add(v));
+
+        let (mut left, mut right) = (Summary::default(), Summary::default());
+        values[..4].iter().for_each(|&v| left.add(v));
+        values[4..].iter().for_each(|&v| right.add(v));
+        left.merge(&right);
+
+        assert_eq!(left.count, whole.count);
+        assert_eq!((left.min, left.max), (whole.min, whole.max));
+        assert!((left.mean() - whole.mean()).abs() < 1e-9);
+        assert!((left.std_dev() - whole.std_dev()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn simulate_with_stops_early() {
+        let ast = parse("1d6").unwrap();
+        let program = Program::compile(&ast, ast.roots()[0]);
+
+        let mut calls = 0;
+        let summary = simulate_with(&program, CHUNK_SIZE * 10, &Sampling::default(), |_| {
+            calls += 1;
+            if calls == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(summary.count, CHUNK_SIZE * 3);
+    }
+
+    #[test]
+    fn thread_count_does_not_change_results() {
+        let ast = parse("4d6kh3").unwrap();
+        let program = Program::compile(&ast, ast.roots()[0]);
+        let run = |threads| {
+            fastrand::seed(7);
+            let sampling = Sampling {
+                threads,
+                ..Sampling::default()
+            };
+            simulate_with(&program, CHUNK_SIZE * 8 + 5, &sampling, |_| {
+                ControlFlow::Continue(())
+            })
+        };
+
+        let (one, four) = (run(1), run(4));
+        assert_eq!(one.count, four.count);
+        assert_eq!((one.min, one.max), (four.min, four.max));
+        assert!((one.mean() - four.mean()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn antithetic_pairs_narrow_the_estimates() {
+        let ast = parse("8d6").unwrap();
+        let program = Program::compile(&ast, ast.roots()[0]);
+        let run = |method| {
+            fastrand::seed(11);
+            let sampling = Sampling {
+                threads: 2,
+                method,
+                at_least: Some(30),
+            };
+            simulate_with(&program, 20_001, &sampling, |_| ControlFlow::Continue(()))
+        };
+
+        let (independent, antithetic) = (run(Method::Independent), run(Method::Antithetic));
+        assert_eq!(antithetic.count, 20_001);
+        assert!((antithetic.mean() - 28.0).abs() < 0.05);
+        assert!(antithetic.std_error() < independent.std_error() / 10.0);
+
+        // P(8d6 >= 30) is about 0.3802.
+        let (threshold, chance) = antithetic.at_least().unwrap();
+        assert_eq!(threshold, 30);
+        assert!((chance.value() - 0.3802).abs() < 4.0 * chance.std_error());
+        assert!(chance.std_error() < independent.at_least().unwrap().1.std_error());
+    }
+
+    #[test]
+    fn expected_total_is_exact_for_linear_expressions() {
+        let mean = |input: &str| {
+            let ast = parse(input).unwrap();
+            expected_total(&ast, ast.roots()[0])
+        };
+        assert_eq!(mean("1d8 + 4"), Some(8.5));
+        assert_eq!(mean("2 * (3d6 - 1) + 4dF"), Some(19.0));
+        assert_eq!(mean("3(1d4)"), Some(7.5));
+        assert_eq!(mean("4d6kh3"), None);
+        assert_eq!(mean("1d6 / 2"), None);
+    }
+
+    #[test]
+    fn simulate_stays_in_range() {
+        let ast = parse("3d6").unwrap();
+        let summary = simulate(&Program::compile(&ast, ast.roots()[0]), 2000);
+        assert_eq!(summary.count, 2000);
+        assert!(summary.min >= 3 && summary.max <= 18);
+        assert!((summary.mean() - 10.5).abs() < 0.5);
+    }
+}
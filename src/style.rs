@@ -0,0 +1,291 @@
+//! Coloring rules for rendered results, such as `total >= 20` in green or
+//! `die = max` in bold. Rules judge the total and dice a roll produced, each
+//! die against its own size, and color them where the line shows them. The
+//! same [`Test`]s pick out the dice that set off crit alerts.
+
+use crate::ast::Compare;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    Bold,
+}
+
+impl Color {
+    pub fn parse(name: &str) -> Result<Color, String> {
+        Ok(match name {
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "magenta" => Color::Magenta,
+            "cyan" => Color::Cyan,
+            "bold" => Color::Bold,
+            _ => return Err(format!("unknown color `{}`", name)),
+        })
+    }
+
+    /// Wraps `text` in the ANSI escape codes for this color.
+    pub fn paint(self, text: &str) -> String {
+        let code = match self {
+            Color::Red => 31,
+            Color::Green => 32,
+            Color::Yellow => 33,
+            Color::Blue => 34,
+            Color::Magenta => 35,
+            Color::Cyan => 36,
+            Color::Bold => 1,
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+}
+
+/// What a test looks at: the total, any die, or only dice of one size
+/// (`d20`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subject {
+    Total,
+    Die(Option<i32>),
+}
+
+/// What a test compares against: a number, or a die's highest or lowest
+/// face.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Value(i32),
+    Max,
+    Min,
+}
+
+/// A condition on a total or a die, like `total >= 20`, `die = max` or
+/// `d20 = 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Test {
+    pub subject: Subject,
+    pub compare: Compare,
+    pub bound: Bound,
+}
+
+impl Test {
+    pub fn parse(condition: &str) -> Result<Test, String> {
+        let error = || format!("bad condition `{}`", condition.trim());
+        let condition = condition.trim();
+        let (subject, rest) = if let Some(rest) = condition.strip_prefix("total") {
+            (Subject::Total, rest)
+        } else if let Some(rest) = condition.strip_prefix("die") {
+            (Subject::Die(None), rest)
+        } else if let Some(rest) = condition.strip_prefix('d') {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let sides = rest[..digits].parse().map_err(|_| error())?;
+            (Subject::Die(Some(sides)), &rest[digits..])
+        } else {
+            return Err(error());
+        };
+        let rest = rest.trim_start();
+        let (compare, rest) = [
+            (">=", Compare::GreaterOrEqual),
+            ("<=", Compare::LessOrEqual),
+            (">", Compare::Greater),
+            ("<", Compare::Less),
+            ("=", Compare::Equal),
+        ]
+        .into_iter()
+        .find_map(|(symbol, compare)| rest.strip_prefix(symbol).map(|rest| (compare, rest)))
+        .ok_or_else(error)?;
+        let bound = match rest.trim() {
+            "max" if subject != Subject::Total => Bound::Max,
+            "min" if subject != Subject::Total => Bound::Min,
+            n => Bound::Value(n.parse().map_err(|_| error())?),
+        };
+        Ok(Test {
+            subject,
+            compare,
+            bound,
+        })
+    }
+
+    /// Whether a die showing `value` passes, given its number of sides if
+    /// known.
+    pub fn matches_die(&self, value: i32, sides: Option<i32>) -> bool {
+        let Subject::Die(size) = self.subject else {
+            return false;
+        };
+        if size.is_some() && size != sides {
+            return false;
+        }
+        let bound = match (self.bound, sides) {
+            (Bound::Value(n), _) => n,
+            (Bound::Max, Some(sides)) => sides,
+            (Bound::Min, Some(_)) => 1,
+            (_, None) => return false,
+        };
+        self.compare.test(value, bound)
+    }
+
+    pub fn matches_total(&self, total: i32) -> bool {
+        match (self.subject, self.bound) {
+            (Subject::Total, Bound::Value(n)) => self.compare.test(total, n),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub test: Test,
+    pub color: Color,
+}
+
+/// Rules in the order they were given; the first that matches wins.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    /// Adds a rule for each of the comma-separated `conditions`.
+    pub fn add(&mut self, color: Color, conditions: &str) -> Result<(), String> {
+        for condition in conditions.split(',') {
+            let test = Test::parse(condition)?;
+            self.rules.push(Rule { test, color });
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    fn total_color(&self, total: i32) -> Option<Color> {
+        let rule = self.rules.iter().find(|r| r.test.matches_total(total));
+        rule.map(|r| r.color)
+    }
+
+    fn die_color(&self, value: i32, sides: Option<i32>) -> Option<Color> {
+        let rule = self.rules.iter().find(|r| r.test.matches_die(value, sides));
+        rule.map(|r| r.color)
+    }
+
+    /// Colors a rendered line from what was rolled: `total`, which the line
+    /// ends in, and `dice`, the `(sides, value)` of each die that counted in
+    /// the order the line's bracketed lists show them. Where a list shows
+    /// something else, such as the totals of a group, its dice are left
+    /// alone from there on.
+    pub fn paint(&self, line: &str, total: Option<i32>, dice: &[(i32, i32)]) -> String {
+        let (head, tail) = match total.map(|total| total.to_string()) {
+            Some(total) if line.ends_with(&total) => line.split_at(line.len() - total.len()),
+            _ => (line, ""),
+        };
+        let tail = match total.and_then(|total| self.total_color(total)) {
+            Some(color) if !tail.is_empty() => color.paint(tail),
+            _ => tail.to_string(),
+        };
+
+        let mut dice = dice.iter();
+        let mut out = String::new();
+        let mut rest = head;
+        while let Some(open) = rest.find('[') {
+            let Some(close) = rest[open..].find(']').map(|i| open + i) else {
+                break;
+            };
+            out.push_str(&rest[..=open]);
+            let shown: Vec<String> = rest[open + 1..close]
+                .split(", ")
+                .map(|die| {
+                    let value = die.trim_end_matches('*').parse::<i32>().ok();
+                    let color = match dice.next() {
+                        Some(&(sides, rolled)) if value == Some(rolled) => {
+                            self.die_color(rolled, Some(sides))
+                        }
+                        _ => {
+                            dice = [].iter();
+                            None
+                        }
+                    };
+                    match color {
+                        Some(color) => color.paint(die),
+                        None => die.to_string(),
+                    }
+                })
+                .collect();
+            out.push_str(&shown.join(", "));
+            out.push(']');
+            rest = &rest[close + 1..];
+        }
+        out.push_str(rest);
+        out.push_str(&tail);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(definitions: &[(&str, &str)]) -> Rules {
+        let mut rules = Rules::default();
+        for (color, conditions) in definitions {
+            rules.add(Color::parse(color).unwrap(), conditions).unwrap();
+        }
+        rules
+    }
+
+    #[test]
+    fn parses_tests() {
+        let test = Test::parse(" die = max").unwrap();
+        assert_eq!(test.subject, Subject::Die(None));
+        assert_eq!(test.bound, Bound::Max);
+        let test = Test::parse("total>=20").unwrap();
+        assert_eq!(test.compare, Compare::GreaterOrEqual);
+        assert_eq!(test.bound, Bound::Value(20));
+        let test = Test::parse("d20 = 1").unwrap();
+        assert_eq!(test.subject, Subject::Die(Some(20)));
+        assert!(test.matches_die(1, Some(20)));
+        assert!(!test.matches_die(1, Some(6)));
+        assert!(Test::parse("total = max").is_err());
+        assert!(Test::parse("sum > 3").is_err());
+        assert!(Color::parse("mauve").is_err());
+    }
+
+    #[test]
+    fn paints_totals() {
+        let rules = rules(&[("green", "total >= 20"), ("red", "total <= 5")]);
+        assert_eq!(rules.paint("21", Some(21), &[]), "\x1b[32m21\x1b[0m");
+        assert_eq!(rules.paint("atk: 3", Some(3), &[]), "atk: \x1b[31m3\x1b[0m");
+        assert_eq!(
+            rules.paint("[12] + 9 = 21", Some(21), &[(20, 12)]),
+            "[12] + 9 = \x1b[32m21\x1b[0m"
+        );
+        assert_eq!(rules.paint("12", Some(12), &[]), "12");
+        // Only the total that was rolled is painted, not numbers that
+        // happen to end a line.
+        assert_eq!(rules.paint("3 successes", None, &[]), "3 successes");
+        assert_eq!(rules.paint("hit by 2", None, &[]), "hit by 2");
+    }
+
+    #[test]
+    fn paints_dice_against_their_faces() {
+        let rules = rules(&[("bold", "die = max"), ("red", "die = min")]);
+        assert_eq!(
+            rules.paint("[6, 1, 4] + 2 = 13", Some(13), &[(6, 6), (6, 1), (6, 4)]),
+            "[\x1b[1m6\x1b[0m, \x1b[31m1\x1b[0m, 4] + 2 = 13"
+        );
+        // Each die is judged against its own size.
+        assert_eq!(
+            rules.paint("[6] + [6] = 12", Some(12), &[(8, 6), (6, 6)]),
+            "[6] + [\x1b[1m6\x1b[0m] = 12"
+        );
+        // A group lists totals, not dice, so nothing there is painted.
+        assert_eq!(
+            rules.paint("[7, 6]", None, &[(6, 6), (6, 1), (6, 6)]),
+            "[7, 6]"
+        );
+    }
+}
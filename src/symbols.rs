@@ -0,0 +1,125 @@
+//! Dice whose faces show symbols instead of numbers, such as the narrative
+//! dice of Genesys and Star Wars.
+//!
+//! A face is a count of each symbol it shows, so faces add up into the
+//! symbols of a whole pool. Faces are picked with [`roll_face`], which
+//! rolls through [`roll`] like any numbered die.
+
+use std::fmt;
+use std::ops::AddAssign;
+
+use crate::eval::roll;
+
+/// Symbols on one or more narrative dice faces.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Symbols {
+    pub success: i32,
+    pub failure: i32,
+    pub advantage: i32,
+    pub threat: i32,
+    pub triumph: i32,
+    pub despair: i32,
+}
+
+/// A face showing these counts, without triumph or despair.
+pub const fn face(success: i32, advantage: i32, failure: i32, threat: i32) -> Symbols {
+    Symbols {
+        success,
+        failure,
+        advantage,
+        threat,
+        triumph: 0,
+        despair: 0,
+    }
+}
+
+// Faces by their symbols: S success, A advantage, F failure, T threat.
+pub const BLANK: Symbols = face(0, 0, 0, 0);
+pub const S: Symbols = face(1, 0, 0, 0);
+pub const SS: Symbols = face(2, 0, 0, 0);
+pub const A: Symbols = face(0, 1, 0, 0);
+pub const AA: Symbols = face(0, 2, 0, 0);
+pub const SA: Symbols = face(1, 1, 0, 0);
+pub const F: Symbols = face(0, 0, 1, 0);
+pub const FF: Symbols = face(0, 0, 2, 0);
+pub const T: Symbols = face(0, 0, 0, 1);
+pub const TT: Symbols = face(0, 0, 0, 2);
+pub const FT: Symbols = face(0, 0, 1, 1);
+/// A triumph also counts as a success.
+pub const TRIUMPH: Symbols = Symbols { triumph: 1, ..S };
+/// A despair also counts as a failure.
+pub const DESPAIR: Symbols = Symbols { despair: 1, ..F };
+
+/// The faces of the narrative dice.
+pub const BOOST: &[Symbols] = &[BLANK, BLANK, S, SA, AA, A];
+pub const SETBACK: &[Symbols] = &[BLANK, BLANK, F, F, T, T];
+pub const ABILITY: &[Symbols] = &[BLANK, S, S, SS, A, A, SA, AA];
+pub const DIFFICULTY: &[Symbols] = &[BLANK, F, FF, T, T, T, TT, FT];
+pub const PROFICIENCY: &[Symbols] = &[BLANK, S, S, SS, SS, A, SA, SA, SA, AA, AA, TRIUMPH];
+pub const CHALLENGE: &[Symbols] = &[BLANK, F, F, FF, FF, T, T, FT, FT, TT, TT, DESPAIR];
+
+/// One face of `faces`, each equally likely, rolled as a die with a side
+/// per face.
+pub fn roll_face<T: Copy>(faces: &[T]) -> T {
+    faces[(roll(faces.len() as i32) - 1) as usize]
+}
+
+impl AddAssign for Symbols {
+    fn add_assign(&mut self, other: Symbols) {
+        self.success += other.success;
+        self.failure += other.failure;
+        self.advantage += other.advantage;
+        self.threat += other.threat;
+        self.triumph += other.triumph;
+        self.despair += other.despair;
+    }
+}
+
+/// Shows a face as its symbols, e.g. `SA`, `Tr` or `-` for a blank.
+impl fmt::Display for Symbols {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *self == BLANK {
+            return write!(f, "-");
+        }
+        // Triumph and despair already carry their success or failure.
+        let symbols = [
+            ("S", self.success - self.triumph),
+            ("A", self.advantage),
+            ("Tr", self.triumph),
+            ("F", self.failure - self.despair),
+            ("T", self.threat),
+            ("De", self.despair),
+        ];
+        for (symbol, count) in symbols {
+            write!(f, "{}", symbol.repeat(count.max(0) as usize))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::Antithetic;
+
+    #[test]
+    fn faces_show_their_symbols() {
+        assert_eq!(TRIUMPH.to_string(), "Tr");
+        assert_eq!(FT.to_string(), "FT");
+        assert_eq!(BLANK.to_string(), "-");
+
+        let mut pool = Symbols::default();
+        pool += SA;
+        pool += DESPAIR;
+        assert_eq!(pool.to_string(), "SADe");
+    }
+
+    #[test]
+    fn faces_roll_through_the_engine() {
+        // Mirroring the die behind a face picks the opposite face.
+        let faces = [1, 2, 3, 4, 5, 6, 7, 8];
+        let (first, second) = Antithetic::start().pair(|| roll_face(&faces));
+        assert_eq!(first + second, 9);
+        assert!(ABILITY.contains(&roll_face(ABILITY)));
+    }
+}
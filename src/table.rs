@@ -0,0 +1,311 @@
+//! Random tables: lists of entries picked by a die roll.
+//!
+//! A table is written one entry per line as `<key>: <text>`, or
+//! `<key>,<text>` in CSV. The key is either a range on the table's die
+//! (`01-50`, `51`, where `00` stands for 100) or a weight (`w3`), and
+//! every entry needs one. Ranged tables roll a die as large as their
+//! highest range and must cover every face once. Weighted tables roll
+//! against the total weight. Blank lines and lines starting with `#` are
+//! ignored.
+//!
+//! Entries can embed dice expressions in braces and roll on other tables
+//! by name, e.g. `{2d6*10} gold and a roll on @gems`; see [`expand`].
+
+use crate::eval::{EvalResult, eval_expr, roll};
+use crate::parser::parse;
+
+/// How deeply `@table` references may nest, so a table that refers to
+/// itself fails instead of recursing forever.
+pub const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub low: i32,
+    pub high: i32,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    /// Sorted by range; together the ranges cover `1..=sides`.
+    entries: Vec<Entry>,
+    sides: i32,
+}
+
+/// How the entries of a table are keyed; a table cannot mix the two.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Keys {
+    Ranges,
+    Weights,
+}
+
+impl Table {
+    /// Parses a table; `csv` selects `,` instead of `:` as the separator.
+    pub fn parse(source: &str, csv: bool) -> Result<Table, String> {
+        let separator = if csv { ',' } else { ':' };
+        let mut keys = None;
+        let mut entries = Vec::new();
+        let mut next = 1;
+
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", i + 1, message);
+
+            let (key, text) = match line.split_once(separator) {
+                Some((key, text)) => match parse_key(key.trim()) {
+                    Some(key) => (key, text.trim()),
+                    None => {
+                        return Err(error(&format!("`{}` is not a range or weight", key.trim())));
+                    }
+                },
+                None => {
+                    return Err(error(&format!(
+                        "expected `<range>{0} <text>` or `w<n>{0} <text>`",
+                        separator
+                    )));
+                }
+            };
+            let (kind, low, high) = match key {
+                Key::Range(low, high) => (Keys::Ranges, low, high),
+                Key::Weight(weight) => (Keys::Weights, next, next + weight - 1),
+            };
+            if *keys.get_or_insert(kind) != kind {
+                return Err(error("a table cannot mix ranges and weights"));
+            }
+            if low > high {
+                return Err(error("empty range"));
+            }
+            next = high + 1;
+            entries.push(Entry {
+                low,
+                high,
+                text: text.trim_matches('"').to_string(),
+            });
+        }
+
+        entries.sort_by_key(|e| e.low);
+        let mut expected = 1;
+        for entry in &entries {
+            if entry.low != expected {
+                return Err(if entry.low < expected {
+                    format!("{} is covered twice", entry.low)
+                } else {
+                    format!("nothing covers {}", expected)
+                });
+            }
+            expected = entry.high + 1;
+        }
+        if entries.is_empty() {
+            return Err("the table has no entries".to_string());
+        }
+
+        Ok(Table {
+            sides: expected - 1,
+            entries,
+        })
+    }
+
+    /// The die the table is rolled on.
+    pub fn sides(&self) -> i32 {
+        self.sides
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// The entry for a roll of `value`, if it is on the die.
+    pub fn lookup(&self, value: i32) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|e| e.low <= value && value <= e.high)
+            .map(|e| e.text.as_str())
+    }
+
+    /// Rolls the table's die and returns the roll and the matched entry.
+    pub fn roll(&self) -> (i32, &str) {
+        let value = roll(self.sides);
+        (value, self.lookup(value).unwrap())
+    }
+}
+
+/// Expands an entry: each `{expr}` becomes the expression's total, or its
+/// text for `choose`, and each `@name` the expanded result of rolling on the
+/// table `resolve` returns for `name`.
+pub fn expand<F>(text: &str, resolve: &mut F) -> Result<String, String>
+where
+    F: FnMut(&str) -> Result<Table, String>,
+{
+    expand_at(text, resolve, 0)
+}
+
+fn expand_at<F>(text: &str, resolve: &mut F, depth: usize) -> Result<String, String>
+where
+    F: FnMut(&str) -> Result<Table, String>,
+{
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find(['{', '@']) {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if let Some(after) = rest.strip_prefix('{') {
+            let (expr, tail) =
+                split_braced(after).ok_or_else(|| format!("unclosed `{{` in `{}`", text))?;
+            let ast = parse(&expr.to_lowercase())?;
+            match ast.roots() {
+                [root] => out.push_str(&format_value(&eval_expr(&ast, *root))),
+                _ => return Err(format!("expected one expression in `{{{}}}`", expr)),
+            }
+            rest = tail;
+        } else {
+            let name_len = rest[1..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(rest.len() - 1);
+            let name = &rest[1..1 + name_len];
+            if name.is_empty() {
+                out.push('@');
+            } else if depth == MAX_DEPTH {
+                return Err(format!(
+                    "tables nest more than {} deep at @{}",
+                    MAX_DEPTH, name
+                ));
+            } else {
+                let table = resolve(name)?;
+                let (_, entry) = table.roll();
+                out.push_str(&expand_at(entry, resolve, depth + 1)?);
+            }
+            rest = &rest[1 + name_len..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Splits `text` after an opening `{` at its matching `}`, so expressions
+/// can contain braces of their own.
+fn split_braced(text: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some((&text[..i], &text[i + 1..])),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// An expression's total, or its text if it has one.
+fn format_value(result: &EvalResult) -> String {
+    match result {
+        EvalResult::Text { text, .. } => text.clone(),
+        result => result.to_number().to_string(),
+    }
+}
+
+enum Key {
+    Range(i32, i32),
+    Weight(i32),
+}
+
+fn parse_key(key: &str) -> Option<Key> {
+    if let Some(weight) = key.strip_prefix('w') {
+        return weight.parse().ok().filter(|&w| w > 0).map(Key::Weight);
+    }
+    let face = |s: &str| match s.trim() {
+        "00" => Some(100),
+        s if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) => s.parse().ok(),
+        _ => None,
+    };
+    match key.split_once('-') {
+        Some((low, high)) => Some(Key::Range(face(low)?, face(high)?)),
+        None => face(key).map(|n| Key::Range(n, n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_map_rolls_to_entries() {
+        let table = Table::parse(
+            "# loot\n01-50: nothing\n51-90: 2d6 gold\n\n91-00: a magic sword\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(table.sides(), 100);
+        assert_eq!(table.lookup(1), Some("nothing"));
+        assert_eq!(table.lookup(90), Some("2d6 gold"));
+        assert_eq!(table.lookup(100), Some("a magic sword"));
+        assert_eq!(table.lookup(101), None);
+    }
+
+    #[test]
+    fn weights_and_csv() {
+        let table = Table::parse("w3,goblins\nw1,orcs\nw2,\"an ogre, alone\"", true).unwrap();
+        assert_eq!(table.sides(), 6);
+        assert_eq!(table.lookup(3), Some("goblins"));
+        assert_eq!(table.lookup(4), Some("orcs"));
+        assert_eq!(table.lookup(6), Some("an ogre, alone"));
+
+        for _ in 0..20 {
+            let (value, entry) = table.roll();
+            assert_eq!(table.lookup(value), Some(entry));
+        }
+    }
+
+    #[test]
+    fn expands_dice_and_nested_tables() {
+        let gems = Table::parse("w1: a ruby", false).unwrap();
+        let mut resolve = |name: &str| match name {
+            "gems" => Ok(gems.clone()),
+            "loop" => Table::parse("w1: again @loop", false),
+            _ => Err(format!("no table named {}", name)),
+        };
+
+        let text = expand("{2d6*10} gold and @gems, mail@ home", &mut resolve).unwrap();
+        let (gold, rest) = text.split_once(' ').unwrap();
+        assert!((20..=120).contains(&gold.parse::<i32>().unwrap()));
+        assert_eq!(rest, "gold and a ruby, mail@ home");
+
+        assert_eq!(
+            expand("@loop", &mut resolve).unwrap_err(),
+            "tables nest more than 16 deep at @loop"
+        );
+        assert_eq!(
+            expand("@nope", &mut resolve).unwrap_err(),
+            "no table named nope"
+        );
+        assert!(expand("{1d6", &mut resolve).is_err());
+
+        let text = expand("a {choose{red, blue}} {{1d4, 1d6}kh1}", &mut resolve).unwrap();
+        let (colour, die) = text.strip_prefix("a ").unwrap().split_once(' ').unwrap();
+        assert!(colour == "red" || colour == "blue", "{}", text);
+        assert!((1..=6).contains(&die.parse::<i32>().unwrap()));
+    }
+
+    #[test]
+    fn rejects_gaps_overlaps_and_bad_keys() {
+        let error = |source| Table::parse(source, false).unwrap_err();
+        assert_eq!(error("1-3: a\n5-6: b"), "nothing covers 4");
+        assert_eq!(error("1-4: a\n3-6: b"), "3 is covered twice");
+        assert_eq!(
+            error("1-3: a\nw2: b"),
+            "line 2: a table cannot mix ranges and weights"
+        );
+        assert_eq!(error("# empty\n"), "the table has no entries");
+        assert_eq!(
+            error("1-3: a\n4-6 b"),
+            "line 2: expected `<range>: <text>` or `w<n>: <text>`"
+        );
+        assert_eq!(
+            error("01-5O: a"),
+            "line 1: `01-5O` is not a range or weight"
+        );
+    }
+}